@@ -0,0 +1,180 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use crate::clean_id;
+
+/// The character type a character belongs to. Fabled and loric aren't
+/// seated in the grimoire; they sit beside it and change the rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Team {
+    Townsfolk,
+    Outsider,
+    Minion,
+    Demon,
+    #[serde(alias = "traveler")]
+    Traveller,
+    Fabled,
+    Loric,
+}
+
+impl Team {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Team::Townsfolk => "townsfolk",
+            Team::Outsider => "outsider",
+            Team::Minion => "minion",
+            Team::Demon => "demon",
+            Team::Traveller => "traveller",
+            Team::Fabled => "fabled",
+            Team::Loric => "loric",
+        }
+    }
+
+    /// Whether characters of this team are given to players
+    pub fn is_seated(&self) -> bool {
+        !matches!(self, Team::Fabled | Team::Loric)
+    }
+}
+
+impl fmt::Display for Team {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Parses team names as scripts write them, accepting both spellings of
+/// traveller
+impl FromStr for Team {
+    type Err = ();
+
+    fn from_str(team: &str) -> Result<Self, Self::Err> {
+        match team {
+            "townsfolk" => Ok(Team::Townsfolk),
+            "outsider" => Ok(Team::Outsider),
+            "minion" => Ok(Team::Minion),
+            "demon" => Ok(Team::Demon),
+            "traveller" | "traveler" => Ok(Team::Traveller),
+            "fabled" => Ok(Team::Fabled),
+            "loric" => Ok(Team::Loric),
+            _ => Err(()),
+        }
+    }
+}
+
+/// An official character, fabled or loric
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Character {
+    pub id: String,
+    pub name: String,
+    /// Edition the character was released in, empty for characters outside
+    /// the three base editions
+    #[serde(default)]
+    pub edition: String,
+    pub team: Team,
+    #[serde(default)]
+    pub first_night_reminder: String,
+    #[serde(default)]
+    pub other_night_reminder: String,
+    #[serde(default)]
+    pub reminders: Vec<String>,
+    /// Reminders that can be placed even when the character isn't in play
+    #[serde(default)]
+    pub reminders_global: Vec<String>,
+    /// Whether the character changes the setup, such as the Baron
+    #[serde(default)]
+    pub setup: bool,
+    pub ability: String,
+}
+
+struct CharacterData {
+    characters: Vec<Character>,
+    non_player_characters: Vec<Character>,
+    /// Position of every character by id: seated ones first, then the rest
+    /// offset by the number of seated ones
+    by_id: HashMap<String, usize>,
+}
+
+fn data() -> &'static CharacterData {
+    static DATA: OnceLock<CharacterData> = OnceLock::new();
+    DATA.get_or_init(|| {
+        let characters: Vec<Character> = serde_json::from_str(include_str!("../../src/characters.json"))
+            .expect("characters.json is valid");
+        let non_player_characters: Vec<Character> =
+            serde_json::from_str(include_str!("../../src/non_player_characters.json"))
+                .expect("non_player_characters.json is valid");
+
+        let by_id = characters
+            .iter()
+            .chain(&non_player_characters)
+            .enumerate()
+            .map(|(index, character)| (character.id.clone(), index))
+            .collect();
+
+        CharacterData { characters, non_player_characters, by_id }
+    })
+}
+
+/// Every official character players can be given, travellers included
+pub fn characters() -> &'static [Character] {
+    &data().characters
+}
+
+/// Every fabled and loric
+pub fn non_player_characters() -> &'static [Character] {
+    &data().non_player_characters
+}
+
+/// Look up an official character, fabled or loric by id
+pub fn character(id: &str) -> Option<&'static Character> {
+    let data = data();
+    let index = *data.by_id.get(&clean_id(id))?;
+    data.characters
+        .get(index)
+        .or_else(|| data.non_player_characters.get(index - data.characters.len()))
+}
+
+/// Team of an official character, fabled or loric
+pub fn team_of(id: &str) -> Option<Team> {
+    character(id).map(|character| character.team)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn looks_up_characters_by_any_spelling_of_their_id() {
+        let fortune_teller = character("Fortune Teller").unwrap();
+        assert_eq!(fortune_teller.id, "fortuneteller");
+        assert_eq!(character("fortune_teller").unwrap().id, "fortuneteller");
+        assert!(character("notacharacter").is_none());
+    }
+
+    #[test]
+    fn finds_fabled_beside_seated_characters() {
+        assert_eq!(character("djinn").unwrap().team, Team::Fabled);
+        assert!(!Team::Fabled.is_seated());
+        assert!(characters().iter().all(|character| character.team.is_seated()));
+    }
+
+    #[test]
+    fn knows_the_team_of_official_characters_only() {
+        assert_eq!(team_of("imp"), Some(Team::Demon));
+        assert_eq!(team_of("Scarlet Woman"), Some(Team::Minion));
+        assert_eq!(team_of("saint"), Some(Team::Outsider));
+        assert_eq!(team_of("thief"), Some(Team::Traveller));
+        assert_eq!(team_of("myhomebrew"), None);
+    }
+
+    #[test]
+    fn parses_both_spellings_of_traveller() {
+        assert_eq!("traveller".parse(), Ok(Team::Traveller));
+        assert_eq!("traveler".parse(), Ok(Team::Traveller));
+        assert_eq!("Townsfolk".parse::<Team>(), Err(()));
+    }
+}
@@ -0,0 +1,49 @@
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+/// Fewest players a game can be set up for
+pub const MIN_PLAYERS: usize = 5;
+
+/// Most players a game can be set up for, travellers aside
+pub const MAX_PLAYERS: usize = 15;
+
+/// Characters of each seated team in play at one player count, before
+/// setup abilities such as the Baron's change them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct TeamCounts {
+    pub townsfolk: usize,
+    pub outsider: usize,
+    pub minion: usize,
+    pub demon: usize,
+}
+
+fn data() -> &'static [TeamCounts] {
+    static DATA: OnceLock<Vec<TeamCounts>> = OnceLock::new();
+    DATA.get_or_init(|| {
+        let counts: Vec<TeamCounts> = serde_json::from_str(include_str!("../../src/counts.json"))
+            .expect("counts.json is valid");
+        assert_eq!(counts.len(), MAX_PLAYERS - MIN_PLAYERS + 1, "counts.json covers every player count");
+        counts
+    })
+}
+
+/// Team counts for a number of players, from `MIN_PLAYERS` to `MAX_PLAYERS`
+pub fn team_counts(players: usize) -> Option<TeamCounts> {
+    data().get(players.checked_sub(MIN_PLAYERS)?).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn covers_every_player_count() {
+        assert_eq!(
+            team_counts(MIN_PLAYERS),
+            Some(TeamCounts { townsfolk: 3, outsider: 0, minion: 1, demon: 1 })
+        );
+        assert_eq!(team_counts(MAX_PLAYERS).map(|counts| counts.demon), Some(1));
+        assert_eq!(team_counts(MIN_PLAYERS - 1), None);
+        assert_eq!(team_counts(MAX_PLAYERS + 1), None);
+    }
+}
@@ -0,0 +1,70 @@
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+use crate::{characters::characters, Character};
+
+/// An official edition, with its own night order
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Edition {
+    pub id: String,
+    pub name: String,
+    pub author: String,
+    pub description: String,
+    /// Recommended experience, such as "Beginner"
+    pub level: String,
+    pub is_official: bool,
+    /// Characters on the edition beyond those released in it
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// First night order, including steps such as `dusk` and `minioninfo`
+    #[serde(default)]
+    pub first_night: Vec<String>,
+    #[serde(default)]
+    pub other_night: Vec<String>,
+}
+
+fn data() -> &'static [Edition] {
+    static DATA: OnceLock<Vec<Edition>> = OnceLock::new();
+    DATA.get_or_init(|| {
+        serde_json::from_str(include_str!("../../src/editions.json")).expect("editions.json is valid")
+    })
+}
+
+/// Every official edition
+pub fn editions() -> &'static [Edition] {
+    data()
+}
+
+/// Look up an official edition by id, such as `tb`
+pub fn edition(id: &str) -> Option<&'static Edition> {
+    data().iter().find(|edition| edition.id == id)
+}
+
+/// Characters on an edition, the way the grimoire lists them: those
+/// released in it plus any it adds
+pub fn edition_characters(id: &str) -> Vec<&'static Character> {
+    let Some(edition) = edition(id) else {
+        return Vec::new();
+    };
+
+    characters()
+        .iter()
+        .filter(|character| character.edition == edition.id || edition.roles.contains(&character.id))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_the_characters_of_an_edition() {
+        assert_eq!(edition("tb").unwrap().name, "Trouble Brewing");
+        let ids: Vec<&str> = edition_characters("tb").iter().map(|character| character.id.as_str()).collect();
+        assert!(ids.contains(&"washerwoman"));
+        assert!(ids.contains(&"imp"));
+        assert!(!ids.contains(&"po"));
+        assert!(edition_characters("notanedition").is_empty());
+    }
+}
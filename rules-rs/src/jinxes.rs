@@ -0,0 +1,63 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::clean_id;
+
+/// A jinx one character has with another, changing how they work together
+#[derive(Debug, Clone, Deserialize)]
+pub struct Jinx {
+    /// The other character
+    pub id: String,
+    pub reason: String,
+}
+
+#[derive(Deserialize)]
+struct JinxList {
+    id: String,
+    jinx: Vec<Jinx>,
+}
+
+fn data() -> &'static HashMap<String, Vec<Jinx>> {
+    static DATA: OnceLock<HashMap<String, Vec<Jinx>>> = OnceLock::new();
+    DATA.get_or_init(|| {
+        let lists: Vec<JinxList> = serde_json::from_str(include_str!("../../src/jinxes.json"))
+            .expect("jinxes.json is valid");
+        lists.into_iter().map(|list| (list.id, list.jinx)).collect()
+    })
+}
+
+/// Jinxes listed under a character. Each jinx is listed under one of its
+/// two characters only; use [`jinx_between`] to check a pair.
+pub fn jinxes(id: &str) -> &'static [Jinx] {
+    data().get(&clean_id(id)).map(Vec::as_slice).unwrap_or_default()
+}
+
+/// The jinx between two characters, whichever of them it's listed under
+pub fn jinx_between(first: &str, second: &str) -> Option<&'static Jinx> {
+    let (first, second) = (clean_id(first), clean_id(second));
+    let listed_under = |id: &str, other: &str| {
+        data().get(id).and_then(|jinxes| jinxes.iter().find(|jinx| jinx.id == other))
+    };
+
+    listed_under(&first, &second).or_else(|| listed_under(&second, &first))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_a_jinx_whichever_character_it_is_listed_under() {
+        let jinx = jinx_between("alchemist", "boffin").unwrap();
+        assert!(jinx.reason.contains("Boffin"));
+        assert_eq!(jinx_between("Boffin", "Alchemist").unwrap().reason, jinx.reason);
+    }
+
+    #[test]
+    fn has_no_jinx_between_unrelated_characters() {
+        assert!(jinx_between("washerwoman", "imp").is_none());
+        assert!(jinxes("washerwoman").is_empty());
+        assert!(jinxes("alchemist").iter().any(|jinx| jinx.id == "boffin"));
+    }
+}
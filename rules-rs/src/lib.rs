@@ -0,0 +1,32 @@
+//! Rules data for Blood on the Clocktower, shared by the Grimlive server and
+//! desktop app.
+//!
+//! The datasets are the frontend's own JSON files (`characters.json`,
+//! `non_player_characters.json`, `editions.json`, `jinxes.json`,
+//! `nightsheet.json` and `counts.json`), embedded at build time and parsed
+//! on first use, so every part of Grimlive agrees on the same characters.
+//!
+//! Lookups take ids in any form the grimoire accepts: they are matched
+//! ignoring case and anything but letters and digits, see [`clean_id`].
+
+mod characters;
+mod counts;
+mod editions;
+mod jinxes;
+mod night_order;
+
+pub use characters::{character, characters, non_player_characters, team_of, Character, Team};
+pub use counts::{team_counts, TeamCounts, MAX_PLAYERS, MIN_PLAYERS};
+pub use editions::{edition, edition_characters, editions, Edition};
+pub use jinxes::{jinx_between, jinxes, Jinx};
+pub use night_order::{first_night, first_night_position, other_night, other_night_position};
+
+/// Normalize a character id the way the grimoire does: lowercase letters
+/// and digits only, so "Fortune Teller", "fortune_teller" and
+/// "fortuneteller" are the same character
+pub fn clean_id(id: &str) -> String {
+    id.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
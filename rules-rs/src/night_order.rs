@@ -0,0 +1,66 @@
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+use crate::clean_id;
+
+/// The night sheet across every character, with steps such as `dusk`,
+/// `minioninfo` and `dawn` in between
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NightSheet {
+    first_night: Vec<String>,
+    other_night: Vec<String>,
+}
+
+fn data() -> &'static NightSheet {
+    static DATA: OnceLock<NightSheet> = OnceLock::new();
+    DATA.get_or_init(|| {
+        serde_json::from_str(include_str!("../../src/nightsheet.json")).expect("nightsheet.json is valid")
+    })
+}
+
+/// Every first night step in order
+pub fn first_night() -> &'static [String] {
+    &data().first_night
+}
+
+/// Every step of the other nights in order
+pub fn other_night() -> &'static [String] {
+    &data().other_night
+}
+
+/// Where a character wakes on the first night, counting from 1 at dusk as
+/// the grimoire's night order does. `None` if it doesn't wake.
+pub fn first_night_position(id: &str) -> Option<usize> {
+    position(first_night(), id)
+}
+
+/// Where a character wakes on other nights, counting from 1 at dusk.
+/// `None` if it doesn't wake.
+pub fn other_night_position(id: &str) -> Option<usize> {
+    position(other_night(), id)
+}
+
+fn position(order: &[String], id: &str) -> Option<usize> {
+    let id = clean_id(id);
+    order.iter().position(|step| *step == id).map(|index| index + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_night_positions_from_dusk() {
+        assert_eq!(first_night_position("dusk"), Some(1));
+        assert_eq!(first_night_position("poisoner"), Some(32));
+        assert_eq!(first_night_position("Poisoner"), first_night_position("poisoner"));
+    }
+
+    #[test]
+    fn leaves_out_characters_that_dont_wake() {
+        assert_eq!(first_night_position("imp"), None);
+        assert_eq!(other_night_position("imp"), Some(40));
+        assert_eq!(first_night_position("saint"), None);
+    }
+}
@@ -11,10 +11,10 @@ if ! command -v cargo &> /dev/null; then
 fi
 
 # Build release binary
-cargo build --release
+cargo build --profile server
 
 echo "✅ Build complete!"
-echo "📦 Binary location: target/release/grimlive-server"
+echo "📦 Binary location: ../target/server/grimlive-server"
 echo ""
 echo "To run:"
-echo "  ./target/release/grimlive-server"
+echo "  ../target/server/grimlive-server"
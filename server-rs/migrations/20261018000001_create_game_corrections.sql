@@ -0,0 +1,15 @@
+-- Audit trail of storyteller corrections to completed games. Each row records
+-- one changed field, either on the game itself or on a single seat.
+CREATE TABLE IF NOT EXISTS game_corrections (
+    id SERIAL PRIMARY KEY,
+    game_id INTEGER NOT NULL REFERENCES games (game_id) ON DELETE CASCADE,
+    seat_number INTEGER,
+    field TEXT NOT NULL,
+    old_value TEXT,
+    new_value TEXT,
+    corrected_by BIGINT NOT NULL,
+    reason TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_game_corrections_game_id ON game_corrections (game_id, created_at);
@@ -0,0 +1,10 @@
+-- Indexes supporting the filters on GET /api/v1/games
+CREATE INDEX IF NOT EXISTS idx_games_completed ON games (completed_at DESC, game_id DESC) WHERE is_active = false;
+CREATE INDEX IF NOT EXISTS idx_games_guild_completed ON games (guild_id, completed_at DESC) WHERE is_active = false;
+CREATE INDEX IF NOT EXISTS idx_games_script ON games (script);
+CREATE INDEX IF NOT EXISTS idx_games_custom_name ON games (custom_name) WHERE custom_name IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_games_storyteller ON games (storyteller_user_id, completed_at DESC);
+CREATE INDEX IF NOT EXISTS idx_games_winner ON games (winner);
+CREATE INDEX IF NOT EXISTS idx_games_player_count ON games (player_count);
+CREATE INDEX IF NOT EXISTS idx_game_players_game_id ON game_players (game_id);
+CREATE INDEX IF NOT EXISTS idx_game_players_discord_game ON game_players (discord_id, game_id) WHERE discord_id IS NOT NULL;
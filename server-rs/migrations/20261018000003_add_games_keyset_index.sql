@@ -0,0 +1,5 @@
+-- Keyset pagination over completed games orders by completion time, treating
+-- games without one as completed at the epoch
+CREATE INDEX IF NOT EXISTS idx_games_completed_keyset
+    ON games ((COALESCE(completed_at, TIMESTAMP '1970-01-01 00:00:00')) DESC, game_id DESC)
+    WHERE is_active = false;
@@ -0,0 +1,15 @@
+-- Elo-style ratings for linked players, tracked separately for each team
+CREATE TABLE IF NOT EXISTS player_ratings (
+    discord_id BIGINT PRIMARY KEY,
+    good_rating DOUBLE PRECISION NOT NULL DEFAULT 1500,
+    evil_rating DOUBLE PRECISION NOT NULL DEFAULT 1500,
+    good_games INTEGER NOT NULL DEFAULT 0,
+    evil_games INTEGER NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Games whose results have been applied to player_ratings
+CREATE TABLE IF NOT EXISTS rated_games (
+    game_id INTEGER PRIMARY KEY REFERENCES games (game_id) ON DELETE CASCADE,
+    rated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,2 @@
+-- Guilds an API key may read; NULL leaves the key unrestricted
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS guild_ids BIGINT[];
@@ -0,0 +1,71 @@
+-- Precomputed statistics, kept up to date per game by the server and fully
+-- rebuilt with `grimlive-server rebuild-aggregates`. Every table is keyed by
+-- guild so guild-scoped reads don't touch other communities' rows.
+
+-- Completed games per guild
+CREATE TABLE IF NOT EXISTS guild_aggregates (
+    guild_id BIGINT PRIMARY KEY,
+    games_played BIGINT NOT NULL,
+    seats BIGINT NOT NULL,
+    good_wins BIGINT NOT NULL,
+    evil_wins BIGINT NOT NULL,
+    cancelled_games BIGINT NOT NULL
+);
+
+-- Linked players' results per guild
+CREATE TABLE IF NOT EXISTS player_aggregates (
+    guild_id BIGINT NOT NULL,
+    discord_id BIGINT NOT NULL,
+    player_name TEXT NOT NULL,
+    games_played BIGINT NOT NULL,
+    wins BIGINT NOT NULL,
+    losses BIGINT NOT NULL,
+    survived BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, discord_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_player_aggregates_discord_id ON player_aggregates (discord_id);
+
+-- How often linked players ended as each role, for favourite roles
+CREATE TABLE IF NOT EXISTS player_role_aggregates (
+    guild_id BIGINT NOT NULL,
+    discord_id BIGINT NOT NULL,
+    role_name TEXT NOT NULL,
+    games BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, discord_id, role_name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_player_role_aggregates_discord_id ON player_role_aggregates (discord_id);
+
+-- Decided games per script and guild
+CREATE TABLE IF NOT EXISTS script_aggregates (
+    guild_id BIGINT NOT NULL,
+    script TEXT NOT NULL,
+    games_played BIGINT NOT NULL,
+    good_wins BIGINT NOT NULL,
+    evil_wins BIGINT NOT NULL,
+    player_count_sum BIGINT NOT NULL,
+    player_count_games BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, script)
+);
+
+CREATE INDEX IF NOT EXISTS idx_script_aggregates_script ON script_aggregates (script);
+
+-- Character appearances per guild and the team the seat played for
+CREATE TABLE IF NOT EXISTS role_aggregates (
+    guild_id BIGINT NOT NULL,
+    role_id TEXT NOT NULL,
+    team TEXT,
+    role_name TEXT,
+    appearances BIGINT NOT NULL,
+    games_played BIGINT NOT NULL,
+    starting_count BIGINT NOT NULL,
+    gained_count BIGINT NOT NULL,
+    final_wins BIGINT NOT NULL,
+    final_decided BIGINT NOT NULL,
+    final_survived BIGINT NOT NULL,
+    final_survival_known BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_role_aggregates_guild_role ON role_aggregates (guild_id, role_id);
+CREATE INDEX IF NOT EXISTS idx_role_aggregates_role ON role_aggregates (role_id);
@@ -0,0 +1,12 @@
+-- Log of events streamed from /api/v1/events, so clients reconnecting with
+-- Last-Event-ID can catch up on what they missed
+CREATE TABLE IF NOT EXISTS live_events (
+    id BIGSERIAL PRIMARY KEY,
+    event TEXT NOT NULL,
+    guild_id BIGINT NOT NULL,
+    data TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_live_events_guild ON live_events (guild_id, id);
+CREATE INDEX IF NOT EXISTS idx_live_events_created ON live_events (created_at);
@@ -0,0 +1,111 @@
+-- Scripts identified by content rather than by free-text name. Official
+-- editions keep their edition id; custom scripts are identified by a hash of
+-- their sorted character ids and bootlegger rules. Games recorded before
+-- character lists were sent are identified by their normalized name.
+--
+-- Other clients, such as the Grimkeeper bot, write games without knowing
+-- about script identity, so games can be left without a script id. The
+-- server identifies those from their name in the background.
+CREATE TABLE IF NOT EXISTS scripts (
+    script_id TEXT PRIMARY KEY,
+    -- Most recent display name
+    name TEXT NOT NULL,
+    -- Sorted character ids, for custom scripts identified by content
+    characters TEXT[],
+    is_official BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_seen_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Names a script has been played under, normalized to lowercase letters and
+-- digits. One name can belong to several scripts.
+CREATE TABLE IF NOT EXISTS script_aliases (
+    alias TEXT NOT NULL,
+    script_id TEXT NOT NULL REFERENCES scripts (script_id) ON DELETE CASCADE,
+    last_seen_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (alias, script_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_script_aliases_script ON script_aliases (script_id);
+
+INSERT INTO scripts (script_id, name, is_official) VALUES
+    ('tb', 'Trouble Brewing', true),
+    ('bmr', 'Bad Moon Rising', true),
+    ('snv', 'Sects & Violets', true)
+ON CONFLICT DO NOTHING;
+
+INSERT INTO script_aliases (alias, script_id) VALUES
+    ('tb', 'tb'),
+    ('troublebrewing', 'tb'),
+    ('bmr', 'bmr'),
+    ('badmoonrising', 'bmr'),
+    ('snv', 'snv'),
+    ('sectsviolets', 'snv'),
+    ('sectsandviolets', 'snv')
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE games ADD COLUMN IF NOT EXISTS script_id TEXT;
+
+-- Names are normalized here the way the server does it only when they are
+-- plain ASCII. Games played under any other name, or under a name without
+-- letters or digits, are left for the server to identify.
+WITH names AS (
+    SELECT game_id,
+           CASE WHEN script = 'Custom Script' THEN COALESCE(NULLIF(custom_name, ''), script)
+                ELSE script END AS display_name
+    FROM games
+    WHERE script_id IS NULL
+)
+UPDATE games
+SET script_id = COALESCE(
+    (SELECT script_aliases.script_id
+     FROM script_aliases
+     JOIN scripts ON scripts.script_id = script_aliases.script_id AND scripts.is_official
+     WHERE script_aliases.alias = lower(regexp_replace(games.script, '[^a-zA-Z0-9]', '', 'g'))),
+    'name-' || lower(regexp_replace(names.display_name, '[^a-zA-Z0-9]', '', 'g'))
+)
+FROM names
+WHERE names.game_id = games.game_id
+  AND octet_length(names.display_name) = char_length(names.display_name)
+  AND names.display_name ~ '[a-zA-Z0-9]';
+
+INSERT INTO scripts (script_id, name, created_at, last_seen_at)
+SELECT
+    script_id,
+    (ARRAY_AGG(
+        CASE WHEN script = 'Custom Script' THEN COALESCE(NULLIF(custom_name, ''), script) ELSE script END
+        ORDER BY game_id DESC))[1],
+    MIN(created_at),
+    MAX(created_at)
+FROM games
+WHERE script_id LIKE 'name-%'
+GROUP BY script_id
+ON CONFLICT DO NOTHING;
+
+INSERT INTO script_aliases (alias, script_id, last_seen_at)
+SELECT substr(script_id, 6), script_id, last_seen_at
+FROM scripts
+WHERE script_id LIKE 'name-%'
+ON CONFLICT DO NOTHING;
+
+CREATE INDEX IF NOT EXISTS idx_games_script_id ON games (script_id);
+CREATE INDEX IF NOT EXISTS idx_games_unidentified_script ON games (game_id) WHERE script_id IS NULL;
+
+-- Script aggregates are keyed by identity from now on
+DELETE FROM script_aggregates;
+ALTER TABLE script_aggregates RENAME COLUMN script TO script_id;
+ALTER INDEX IF EXISTS idx_script_aggregates_script RENAME TO idx_script_aggregates_script_id;
+
+INSERT INTO script_aggregates
+    (guild_id, script_id, games_played, good_wins, evil_wins, player_count_sum, player_count_games)
+SELECT
+    guild_id,
+    script_id,
+    COUNT(*),
+    COUNT(*) FILTER (WHERE winner = 'Good'),
+    COUNT(*) FILTER (WHERE winner = 'Evil'),
+    COALESCE(SUM(player_count), 0),
+    COUNT(player_count)
+FROM games
+WHERE is_active = false AND winner IN ('Good', 'Evil') AND script_id IS NOT NULL
+GROUP BY guild_id, script_id;
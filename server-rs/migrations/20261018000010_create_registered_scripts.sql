@@ -0,0 +1,17 @@
+-- Custom scripts uploaded as Script Tool JSON, served back from a stable id.
+-- Each upload is linked to the script identity games played on it share, so
+-- re-uploading an edited script moves it to the edited script's id.
+CREATE TABLE IF NOT EXISTS registered_scripts (
+    id TEXT PRIMARY KEY,
+    owner_discord_id BIGINT NOT NULL,
+    script_id TEXT NOT NULL REFERENCES scripts (script_id),
+    name TEXT NOT NULL,
+    author TEXT,
+    -- The uploaded JSON, served back to the grimoire
+    document TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_registered_scripts_owner ON registered_scripts (owner_discord_id);
+CREATE INDEX IF NOT EXISTS idx_registered_scripts_script ON registered_scripts (script_id);
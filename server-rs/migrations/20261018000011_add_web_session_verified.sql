@@ -0,0 +1,3 @@
+-- Whether a web session's Discord user was proven through the Discord OAuth
+-- flow. Sessions created from a bare Discord ID are not.
+ALTER TABLE web_sessions ADD COLUMN IF NOT EXISTS verified BOOLEAN NOT NULL DEFAULT false;
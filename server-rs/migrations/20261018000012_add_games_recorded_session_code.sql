@@ -0,0 +1,8 @@
+-- Code of the live session the server is recording a game from. Games the
+-- storyteller's client reports itself have none. A recorded game whose
+-- session went away, or that a server restart left behind, is cancelled
+-- rather than left blocking its guild session.
+ALTER TABLE games ADD COLUMN IF NOT EXISTS recorded_session_code TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_games_recorded_active ON games (game_id)
+    WHERE is_active = true AND recorded_session_code IS NOT NULL;
@@ -16,6 +16,9 @@ pub struct Config {
     pub rate_limit_window_ms: u64,
     #[allow(dead_code)]
     pub rate_limit_max_requests: u32,
+    pub auto_record_games: bool,
+    pub idempotency_retention_hours: u64,
+    pub admin_discord_ids: Vec<i64>,
 }
 
 impl Config {
@@ -48,6 +51,19 @@ impl Config {
                 .unwrap_or_else(|_| "100".to_string())
                 .parse()
                 .unwrap_or(100),
+            auto_record_games: env::var("AUTO_RECORD_GAMES")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .unwrap_or(false),
+            idempotency_retention_hours: env::var("IDEMPOTENCY_RETENTION_HOURS")
+                .unwrap_or_else(|_| "24".to_string())
+                .parse()
+                .unwrap_or(24),
+            admin_discord_ids: env::var("ADMIN_DISCORD_IDS")
+                .unwrap_or_default()
+                .split(',')
+                .filter_map(|id| id.trim().parse().ok())
+                .collect(),
         })
     }
 }
@@ -3,8 +3,9 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
-use serde_json::json;
+use serde::Serialize;
 use thiserror::Error;
+use utoipa::ToSchema;
 
 #[derive(Error, Debug)]
 pub enum AppError {
@@ -14,13 +15,15 @@ pub enum AppError {
     #[error("Unauthorized: {0}")]
     Unauthorized(String),
     
-    #[allow(dead_code)]
     #[error("Forbidden: {0}")]
     Forbidden(String),
     
     #[error("Not found: {0}")]
     NotFound(String),
     
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    
     #[error("Validation error: {0}")]
     Validation(String),
     
@@ -44,6 +47,7 @@ impl IntoResponse for AppError {
             AppError::Unauthorized(ref msg) => (StatusCode::UNAUTHORIZED, msg.as_str()),
             AppError::Forbidden(ref msg) => (StatusCode::FORBIDDEN, msg.as_str()),
             AppError::NotFound(ref msg) => (StatusCode::NOT_FOUND, msg.as_str()),
+            AppError::Conflict(ref msg) => (StatusCode::CONFLICT, msg.as_str()),
             AppError::Validation(ref msg) => (StatusCode::BAD_REQUEST, msg.as_str()),
             AppError::RateLimitExceeded => (StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded"),
             AppError::BadRequest(ref msg) => (StatusCode::BAD_REQUEST, msg.as_str()),
@@ -53,12 +57,18 @@ impl IntoResponse for AppError {
             }
         };
 
-        let body = Json(json!({
-            "error": error_message,
-        }));
+        let body = Json(ErrorBody {
+            error: error_message.to_string(),
+        });
 
         (status, body).into_response()
     }
 }
 
+/// JSON body of every error response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    pub error: String,
+}
+
 pub type AppResult<T> = Result<T, AppError>;
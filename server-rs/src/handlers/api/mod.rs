@@ -1 +1,3 @@
+pub mod openapi;
 pub mod v1;
+pub mod webhooks;
@@ -0,0 +1,87 @@
+use axum::Json;
+use utoipa::{
+    openapi::security::{ApiKey, ApiKeyValue, SecurityScheme},
+    Modify, OpenApi,
+};
+
+use crate::{
+    error::ErrorBody,
+    handlers::api::{v1, webhooks},
+    models::{Alignment, GameSort},
+    utils::export::ExportFormat,
+};
+
+/// OpenAPI description of the read API, generated from the v1 handlers
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Grimlive Stats API",
+        description = "Game statistics recorded by Grimlive, a validator for custom scripts, and webhooks for a guild's game events. Every endpoint needs an `X-API-Key` header; keys restricted to guilds can only use the `/api/v1/guilds/{guild_id}` endpoints. Responses other than exports, event streams and script validation carry an `ETag`; send it back in `If-None-Match` to get an empty `304 Not Modified` while the data is unchanged.",
+    ),
+    paths(
+        v1::get_games,
+        v1::get_game_by_id,
+        v1::get_stats_summary,
+        v1::get_stats_timeseries,
+        v1::get_player_stats,
+        v1::get_player_games,
+        v1::get_player_teammates,
+        v1::get_player_opponents,
+        v1::get_head_to_head,
+        v1::get_storyteller_stats,
+        v1::get_storyteller_games,
+        v1::get_script_stats,
+        v1::validate_script,
+        v1::get_leaderboard,
+        v1::list_role_stats,
+        v1::get_role_stats,
+        v1::export_games,
+        v1::export_game_players,
+        v1::export_players,
+        v1::get_events,
+        v1::get_guild_stats_summary,
+        v1::get_guild_stats_timeseries,
+        v1::get_guild_games,
+        v1::get_guild_player_stats,
+        v1::get_guild_script_stats,
+        v1::get_guild_leaderboard,
+        v1::get_guild_events,
+        webhooks::create_webhook,
+        webhooks::list_webhooks,
+        webhooks::delete_webhook,
+        webhooks::get_webhook_deliveries,
+    ),
+    // Enums only used by query parameters aren't collected from the paths
+    components(schemas(ErrorBody, Alignment, GameSort, ExportFormat)),
+    modifiers(&ApiKeySecurity),
+    tags(
+        (name = "games", description = "Completed games"),
+        (name = "stats", description = "Totals, trends and leaderboards"),
+        (name = "players", description = "Linked Discord players"),
+        (name = "storytellers", description = "Games by storyteller"),
+        (name = "scripts", description = "Scripts"),
+        (name = "roles", description = "Characters"),
+        (name = "exports", description = "Bulk CSV and NDJSON downloads"),
+        (name = "events", description = "Live Server-Sent Events"),
+        (name = "guilds", description = "Statistics for a single Discord guild"),
+        (name = "webhooks", description = "Game event webhooks for a guild, owned by the API key that registered them"),
+    ),
+)]
+pub struct ApiDoc;
+
+struct ApiKeySecurity;
+
+impl Modify for ApiKeySecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))),
+        );
+    }
+}
+
+/// Serve the OpenAPI document
+pub async fn get_openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
@@ -1,105 +1,1033 @@
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
-    Json,
+    http::{HeaderMap, StatusCode, Uri},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Response,
+    },
+    Extension, Json,
 };
+use futures::{Stream, StreamExt};
 use serde::Deserialize;
+use std::convert::Infallible;
+use utoipa::IntoParams;
 use crate::{
-    error::{AppError, AppResult},
-    models::{ApiKeyCreate, Game, PlayerStats, ScriptStats, StatsSummary},
+    error::{AppError, AppResult, ErrorBody},
+    handlers::game::verified_session_user,
+    middleware::AuthenticatedSession,
+    models::{
+        Alignment, ApiKeyCreate, ApiKeyResponse, ApiKeyUpdate, Game, GameDetail, GameFilter, GamePlayer, GameSort,
+        HeadToHead, Leaderboard, LeaderboardEntry, LeaderboardMetric, Pairing, PlayerGameFilter, PlayerPairing, PlayerStats, RoleStats,
+        PlayerGame, ScriptStats, ScriptValidation, StatsScope, StatsSummary, StorytellerStats, TimeBucket, Timeseries,
+    },
+    services::{
+        game::{export_game_players_query, export_games_query, game_cursor, player_game_cursor},
+        script_validation,
+        stats::leaderboard_query,
+    },
     state::AppState,
-    utils::validation,
+    utils::{
+        export::{stream_export, ExportFormat},
+        pagination::{Cursor, Page},
+        validation,
+    },
 };
 
 // ============================================================================
 // Public Read-Only Endpoints (protected by API key middleware)
 // ============================================================================
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct PaginationQuery {
     #[serde(default = "default_limit")]
     limit: i64,
     #[serde(default)]
     offset: i64,
+    cursor: Option<String>,
 }
 
 fn default_limit() -> i64 {
     50
 }
 
-/// List all games with pagination
+impl PaginationQuery {
+    /// Validate limit and offset, decoding the cursor if one was given
+    fn validate(&self) -> AppResult<Option<Cursor>> {
+        if self.limit < 1 || self.limit > 100 {
+            return Err(AppError::Validation("Limit must be between 1 and 100".to_string()));
+        }
+        if self.offset < 0 {
+            return Err(AppError::Validation("Offset must be non-negative".to_string()));
+        }
+
+        let Some(ref cursor) = self.cursor else {
+            return Ok(None);
+        };
+        if self.offset != 0 {
+            return Err(AppError::Validation("Use either cursor or offset, not both".to_string()));
+        }
+        Cursor::decode(cursor).map(Some)
+    }
+}
+
+/// List completed games with filters, sorting and cursor pagination
+#[utoipa::path(
+    get,
+    path = "/api/v1/games",
+    tag = "games",
+    params(
+        PaginationQuery,
+        GameFilter,
+    ),
+    responses(
+        (status = 200, description = "A page of completed games", body = Page<Game>, headers(("link" = String, description = "`rel=\"next\"` link to the next page"))),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
 pub async fn get_games(
     State(state): State<AppState>,
+    uri: Uri,
     Query(pagination): Query<PaginationQuery>,
-) -> AppResult<Json<Vec<Game>>> {
-    // Validate pagination
-    if pagination.limit < 1 || pagination.limit > 100 {
-        return Err(AppError::Validation("Limit must be between 1 and 100".to_string()));
+    Query(filter): Query<GameFilter>,
+) -> AppResult<Response> {
+    list_games(&state, &uri, &pagination, &filter).await
+}
+
+async fn list_games(
+    state: &AppState,
+    uri: &Uri,
+    pagination: &PaginationQuery,
+    filter: &GameFilter,
+) -> AppResult<Response> {
+    let cursor = pagination.validate()?;
+    validation::validate_game_filter(filter)?;
+
+    // Cursors follow completion order, so other sorts page by offset only
+    let keyset = matches!(filter.sort, GameSort::Newest | GameSort::Oldest);
+    if cursor.is_some() && !keyset {
+        return Err(AppError::Validation("Cursors are only supported for the newest and oldest sorts".to_string()));
     }
-    if pagination.offset < 0 {
-        return Err(AppError::Validation("Offset must be non-negative".to_string()));
+
+    // Fetch one extra game to know whether there's a next page
+    let games = state.services.game
+        .get_games(filter, pagination.limit + 1, pagination.offset, cursor)
+        .await?;
+
+    let mut page = Page::from_rows(games, pagination.limit, game_cursor);
+    if !keyset {
+        page.next_cursor = None;
     }
 
-    let games = state.services.game.get_games(pagination.limit, pagination.offset).await?;
-    Ok(Json(games))
+    Ok(page.into_response_for(uri))
 }
 
-/// Get a specific game by ID
+/// Get a specific game by ID, including its seats
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{id}",
+    tag = "games",
+    params(
+        ("id" = i32, Path, description = "Game ID"),
+    ),
+    responses(
+        (status = 200, description = "The game and its seats", body = GameDetail),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 404, description = "Game not found", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
 pub async fn get_game_by_id(
     State(state): State<AppState>,
     Path(game_id): Path<i32>,
-) -> AppResult<Json<Game>> {
-    let game = state.services.game.get_game(game_id).await?
+) -> AppResult<Json<GameDetail>> {
+    let game = state.services.game.get_game_detail(game_id).await?
         .ok_or_else(|| AppError::NotFound("Game not found".to_string()))?;
     
     Ok(Json(game))
 }
 
 /// Get overall statistics summary
+#[utoipa::path(
+    get,
+    path = "/api/v1/stats/summary",
+    tag = "stats",
+    responses(
+        (status = 200, description = "All-time totals", body = StatsSummary),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
 pub async fn get_stats_summary(
     State(state): State<AppState>,
 ) -> AppResult<Json<StatsSummary>> {
-    let total_games = state.services.game.count_total_games().await?;
-    let total_players = state.services.game.count_total_players().await?;
-    let unique_players = state.services.game.count_unique_players().await?;
-    let active_games = state.services.game.count_active_games().await?;
-
-    let stats = StatsSummary {
-        total_games,
-        total_players,
-        unique_players,
-        active_games,
-    };
-
+    let stats = state.services.aggregates.get_summary(None).await?;
     Ok(Json(stats))
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TimeseriesQuery {
+    #[serde(default)]
+    bucket: TimeBucket,
+}
+
+/// Get games played, unique players and win split over time
+#[utoipa::path(
+    get,
+    path = "/api/v1/stats/timeseries",
+    tag = "stats",
+    params(
+        TimeseriesQuery,
+        StatsScope,
+    ),
+    responses(
+        (status = 200, description = "Bucketed activity and balance", body = Timeseries),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_stats_timeseries(
+    State(state): State<AppState>,
+    Query(query): Query<TimeseriesQuery>,
+    Query(scope): Query<StatsScope>,
+) -> AppResult<Json<Timeseries>> {
+    validation::validate_stats_scope(&scope)?;
+
+    let timeseries = state.services.stats.get_timeseries(query.bucket, &scope).await?;
+    Ok(Json(timeseries))
+}
+
 /// Get player statistics by Discord ID
+#[utoipa::path(
+    get,
+    path = "/api/v1/players/{discord_id}/stats",
+    tag = "players",
+    params(
+        ("discord_id" = i64, Path, description = "Discord user ID"),
+    ),
+    responses(
+        (status = 200, description = "Player totals and ratings", body = PlayerStats),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 404, description = "Player not found", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
 pub async fn get_player_stats(
     State(state): State<AppState>,
     Path(discord_id): Path<i64>,
 ) -> AppResult<Json<PlayerStats>> {
-    let stats = state.services.game.get_player_stats(discord_id).await?
+    let stats = state.services.aggregates.get_player_stats(discord_id, None).await?
         .ok_or_else(|| AppError::NotFound("Player not found".to_string()))?;
     
     Ok(Json(stats))
 }
 
-/// Get script statistics by script name
+/// List the completed games a player sat in, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/players/{discord_id}/games",
+    tag = "players",
+    params(
+        ("discord_id" = i64, Path, description = "Discord user ID"),
+        PaginationQuery,
+        PlayerGameFilter,
+    ),
+    responses(
+        (status = 200, description = "A page of the player's games", body = Page<PlayerGame>, headers(("link" = String, description = "`rel=\"next\"` link to the next page"))),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_player_games(
+    State(state): State<AppState>,
+    Path(discord_id): Path<i64>,
+    uri: Uri,
+    Query(pagination): Query<PaginationQuery>,
+    Query(filter): Query<PlayerGameFilter>,
+) -> AppResult<Response> {
+    let cursor = pagination.validate()?;
+    validation::validate_player_game_filter(&filter)?;
+
+    // Fetch one extra game to know whether there's a next page
+    let games = state.services.game
+        .get_player_games(discord_id, &filter, pagination.limit + 1, pagination.offset, cursor)
+        .await?;
+
+    Ok(Page::from_rows(games, pagination.limit, player_game_cursor).into_response_for(&uri))
+}
+
+/// Get script statistics by script id or name
+#[utoipa::path(
+    get,
+    path = "/api/v1/scripts/{script_name}/stats",
+    tag = "scripts",
+    params(
+        ("script_name" = String, Path, description = "Script id, or a name the script was played under"),
+    ),
+    responses(
+        (status = 200, description = "Script totals", body = ScriptStats),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 404, description = "Script not found", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
 pub async fn get_script_stats(
     State(state): State<AppState>,
     Path(script_name): Path<String>,
 ) -> AppResult<Json<ScriptStats>> {
     validation::validate_script_name(&script_name)?;
-    
-    let stats = state.services.game.get_script_stats(&script_name).await?
+
+    let script_id = state.services.scripts.resolve(&script_name).await?
+        .ok_or_else(|| AppError::NotFound("Script not found".to_string()))?;
+    let stats = state.services.aggregates.get_script_stats(&script_id, None).await?
         .ok_or_else(|| AppError::NotFound("Script not found".to_string()))?;
     
     Ok(Json(stats))
 }
 
+/// Check a custom script against the character database
+#[utoipa::path(
+    post,
+    path = "/api/v1/scripts/validate",
+    tag = "scripts",
+    request_body(content = Vec<serde_json::Value>, description = "Script Tool JSON: character ids and homebrew characters, with an optional `_meta` entry"),
+    responses(
+        (status = 200, description = "Problems found, team counts and applicable jinxes. A script that can't be read still gets a report.", body = ScriptValidation),
+        (status = 400, description = "Body isn't JSON", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn validate_script(Json(payload): Json<serde_json::Value>) -> Json<ScriptValidation> {
+    Json(script_validation::validate_script(&payload))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LeaderboardQuery {
+    #[serde(default)]
+    metric: LeaderboardMetric,
+    #[serde(default = "default_min_games")]
+    min_games: i64,
+    #[serde(default = "default_leaderboard_limit")]
+    limit: i64,
+}
+
+fn default_min_games() -> i64 {
+    5
+}
+
+fn default_leaderboard_limit() -> i64 {
+    25
+}
+
+/// Rank players by a metric within an optional guild, script and date scope
+#[utoipa::path(
+    get,
+    path = "/api/v1/leaderboards",
+    tag = "stats",
+    params(
+        LeaderboardQuery,
+        StatsScope,
+    ),
+    responses(
+        (status = 200, description = "Ranked players", body = Leaderboard),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_leaderboard(
+    State(state): State<AppState>,
+    Query(query): Query<LeaderboardQuery>,
+    Query(scope): Query<StatsScope>,
+) -> AppResult<Json<Leaderboard>> {
+    leaderboard(&state, &query, &scope).await.map(Json)
+}
+
+async fn leaderboard(state: &AppState, query: &LeaderboardQuery, scope: &StatsScope) -> AppResult<Leaderboard> {
+    if query.limit < 1 || query.limit > 100 {
+        return Err(AppError::Validation("Limit must be between 1 and 100".to_string()));
+    }
+    if query.min_games < 1 {
+        return Err(AppError::Validation("min_games must be at least 1".to_string()));
+    }
+    validation::validate_stats_scope(scope)?;
+
+    state.services.stats
+        .get_leaderboard(query.metric, scope, query.min_games, query.limit)
+        .await
+}
+
+/// Get statistics for every character played in scope
+#[utoipa::path(
+    get,
+    path = "/api/v1/roles/stats",
+    tag = "roles",
+    params(
+        StatsScope,
+    ),
+    responses(
+        (status = 200, description = "Statistics for every character", body = Vec<RoleStats>),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn list_role_stats(
+    State(state): State<AppState>,
+    Query(scope): Query<StatsScope>,
+) -> AppResult<Json<Vec<RoleStats>>> {
+    validation::validate_stats_scope(&scope)?;
+
+    let stats = state.services.aggregates.get_role_stats(&scope, None).await?;
+    Ok(Json(stats))
+}
+
+/// Get statistics for a single character
+#[utoipa::path(
+    get,
+    path = "/api/v1/roles/{role_id}/stats",
+    tag = "roles",
+    params(
+        ("role_id" = String, Path, description = "Character ID"),
+        StatsScope,
+    ),
+    responses(
+        (status = 200, description = "Character statistics", body = RoleStats),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 404, description = "Role not found", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_role_stats(
+    State(state): State<AppState>,
+    Path(role_id): Path<String>,
+    Query(scope): Query<StatsScope>,
+) -> AppResult<Json<RoleStats>> {
+    validation::validate_role_id(&role_id)?;
+    validation::validate_stats_scope(&scope)?;
+
+    let stats = state.services.aggregates.get_role_stats(&scope, Some(&role_id)).await?;
+
+    let stats = stats
+        .into_iter()
+        .next()
+        .ok_or_else(|| AppError::NotFound("Role not found".to_string()))?;
+
+    Ok(Json(stats))
+}
+
+/// Get a storyteller's totals, balance and most-run scripts
+#[utoipa::path(
+    get,
+    path = "/api/v1/storytellers/{discord_id}/stats",
+    tag = "storytellers",
+    params(
+        ("discord_id" = i64, Path, description = "Discord user ID"),
+        StatsScope,
+    ),
+    responses(
+        (status = 200, description = "Storyteller totals", body = StorytellerStats),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 404, description = "Storyteller not found", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_storyteller_stats(
+    State(state): State<AppState>,
+    Path(discord_id): Path<i64>,
+    Query(scope): Query<StatsScope>,
+) -> AppResult<Json<StorytellerStats>> {
+    validation::validate_stats_scope(&scope)?;
+
+    let stats = state.services.stats.get_storyteller_stats(discord_id, &scope).await?
+        .ok_or_else(|| AppError::NotFound("Storyteller not found".to_string()))?;
+
+    Ok(Json(stats))
+}
+
+/// List the completed games a storyteller ran, with the same filters as the
+/// games list
+#[utoipa::path(
+    get,
+    path = "/api/v1/storytellers/{discord_id}/games",
+    tag = "storytellers",
+    params(
+        ("discord_id" = i64, Path, description = "Discord user ID"),
+        PaginationQuery,
+        GameFilter,
+    ),
+    responses(
+        (status = 200, description = "A page of the storyteller's games", body = Page<Game>, headers(("link" = String, description = "`rel=\"next\"` link to the next page"))),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_storyteller_games(
+    State(state): State<AppState>,
+    Path(discord_id): Path<i64>,
+    uri: Uri,
+    Query(pagination): Query<PaginationQuery>,
+    Query(mut filter): Query<GameFilter>,
+) -> AppResult<Response> {
+    filter.storyteller_id = Some(discord_id);
+    list_games(&state, &uri, &pagination, &filter).await
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PairingQuery {
+    team: Option<Alignment>,
+    #[serde(default = "default_pairing_min_games")]
+    min_games: i64,
+    #[serde(default = "default_leaderboard_limit")]
+    limit: i64,
+}
+
+fn default_pairing_min_games() -> i64 {
+    1
+}
+
+async fn list_pairings(
+    state: &AppState,
+    discord_id: i64,
+    pairing: Pairing,
+    query: &PairingQuery,
+    scope: &StatsScope,
+) -> AppResult<Vec<PlayerPairing>> {
+    if query.limit < 1 || query.limit > 100 {
+        return Err(AppError::Validation("Limit must be between 1 and 100".to_string()));
+    }
+    if query.min_games < 1 {
+        return Err(AppError::Validation("min_games must be at least 1".to_string()));
+    }
+    validation::validate_stats_scope(scope)?;
+
+    state.services.stats
+        .get_pairings(discord_id, pairing, query.team, scope, query.min_games, query.limit)
+        .await
+}
+
+/// Get a player's most frequent teammates and their win rate together
+#[utoipa::path(
+    get,
+    path = "/api/v1/players/{discord_id}/teammates",
+    tag = "players",
+    params(
+        ("discord_id" = i64, Path, description = "Discord user ID"),
+        PairingQuery,
+        StatsScope,
+    ),
+    responses(
+        (status = 200, description = "Most frequent teammates", body = Vec<PlayerPairing>),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_player_teammates(
+    State(state): State<AppState>,
+    Path(discord_id): Path<i64>,
+    Query(query): Query<PairingQuery>,
+    Query(scope): Query<StatsScope>,
+) -> AppResult<Json<Vec<PlayerPairing>>> {
+    let teammates = list_pairings(&state, discord_id, Pairing::Teammates, &query, &scope).await?;
+    Ok(Json(teammates))
+}
+
+/// Get a player's most frequent opponents and their win rate against them
+#[utoipa::path(
+    get,
+    path = "/api/v1/players/{discord_id}/opponents",
+    tag = "players",
+    params(
+        ("discord_id" = i64, Path, description = "Discord user ID"),
+        PairingQuery,
+        StatsScope,
+    ),
+    responses(
+        (status = 200, description = "Most frequent opponents", body = Vec<PlayerPairing>),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_player_opponents(
+    State(state): State<AppState>,
+    Path(discord_id): Path<i64>,
+    Query(query): Query<PairingQuery>,
+    Query(scope): Query<StatsScope>,
+) -> AppResult<Json<Vec<PlayerPairing>>> {
+    let opponents = list_pairings(&state, discord_id, Pairing::Opponents, &query, &scope).await?;
+    Ok(Json(opponents))
+}
+
+/// Compare two players' records together and against each other
+#[utoipa::path(
+    get,
+    path = "/api/v1/players/{discord_id}/head-to-head/{other_id}",
+    tag = "players",
+    params(
+        ("discord_id" = i64, Path, description = "Discord user ID"),
+        ("other_id" = i64, Path, description = "Discord user ID of the other player"),
+        StatsScope,
+    ),
+    responses(
+        (status = 200, description = "Records together and against each other", body = HeadToHead),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_head_to_head(
+    State(state): State<AppState>,
+    Path((discord_id, other_id)): Path<(i64, i64)>,
+    Query(scope): Query<StatsScope>,
+) -> AppResult<Json<HeadToHead>> {
+    if discord_id == other_id {
+        return Err(AppError::Validation("Head-to-head needs two different players".to_string()));
+    }
+    validation::validate_stats_scope(&scope)?;
+
+    let head_to_head = state.services.stats.get_head_to_head(discord_id, other_id, &scope).await?;
+    Ok(Json(head_to_head))
+}
+
+// ============================================================================
+// Bulk Exports (protected by API key middleware)
+// ============================================================================
+
+const GAME_EXPORT_COLUMNS: &[&str] = &[
+    "game_id", "guild_id", "script", "script_id", "custom_name", "start_time", "end_time", "winner",
+    "player_count", "players", "created_at", "completed_at", "storyteller_user_id", "category_id",
+];
+
+const GAME_PLAYER_EXPORT_COLUMNS: &[&str] = &[
+    "id", "game_id", "discord_id", "player_name", "seat_number",
+    "starting_role_id", "starting_role_name", "starting_team",
+    "final_role_id", "final_role_name", "final_team", "survived", "winning_team",
+];
+
+const PLAYER_EXPORT_COLUMNS: &[&str] = &[
+    "discord_id", "player_name", "games_played", "wins", "losses", "win_rate", "survival_rate",
+    "good_games", "good_win_rate", "evil_games", "evil_win_rate", "good_rating", "evil_rating",
+];
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery {
+    #[serde(default)]
+    format: ExportFormat,
+}
+
+/// Export every completed game matching the games list filters
+#[utoipa::path(
+    get,
+    path = "/api/v1/export/games",
+    tag = "exports",
+    params(
+        ExportQuery,
+        GameFilter,
+    ),
+    responses(
+        (status = 200, description = "Streamed export", content((String = "text/csv"), (String = "application/x-ndjson"))),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn export_games(
+    State(state): State<AppState>,
+    Query(export): Query<ExportQuery>,
+    Query(filter): Query<GameFilter>,
+) -> AppResult<Response> {
+    validation::validate_game_filter(&filter)?;
+
+    Ok(stream_export::<Game>(
+        state.database.pool.clone(),
+        export_games_query(&filter),
+        export.format,
+        GAME_EXPORT_COLUMNS,
+        "games",
+    ))
+}
+
+/// Export every seat in the completed games matching the games list filters
+#[utoipa::path(
+    get,
+    path = "/api/v1/export/game-players",
+    tag = "exports",
+    params(
+        ExportQuery,
+        GameFilter,
+    ),
+    responses(
+        (status = 200, description = "Streamed export", content((String = "text/csv"), (String = "application/x-ndjson"))),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn export_game_players(
+    State(state): State<AppState>,
+    Query(export): Query<ExportQuery>,
+    Query(filter): Query<GameFilter>,
+) -> AppResult<Response> {
+    validation::validate_game_filter(&filter)?;
+
+    Ok(stream_export::<GamePlayer>(
+        state.database.pool.clone(),
+        export_game_players_query(&filter),
+        export.format,
+        GAME_PLAYER_EXPORT_COLUMNS,
+        "game_players",
+    ))
+}
+
+/// Export per-player totals for every linked player in scope
+#[utoipa::path(
+    get,
+    path = "/api/v1/export/players",
+    tag = "exports",
+    params(
+        ExportQuery,
+        StatsScope,
+    ),
+    responses(
+        (status = 200, description = "Streamed export", content((String = "text/csv"), (String = "application/x-ndjson"))),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn export_players(
+    State(state): State<AppState>,
+    Query(export): Query<ExportQuery>,
+    Query(scope): Query<StatsScope>,
+) -> AppResult<Response> {
+    validation::validate_stats_scope(&scope)?;
+
+    Ok(stream_export::<LeaderboardEntry>(
+        state.database.pool.clone(),
+        leaderboard_query(LeaderboardMetric::GamesPlayed, &scope, 1),
+        export.format,
+        PLAYER_EXPORT_COLUMNS,
+        "players",
+    ))
+}
+
 // ============================================================================
-// API Key Management (TODO: should require session token, not API key)
+// Live Events (protected by API key middleware)
+// ============================================================================
+
+/// Stream game and stats events as Server-Sent Events
+#[utoipa::path(
+    get,
+    path = "/api/v1/events",
+    tag = "events",
+    params(
+        ("last-event-id" = Option<i64>, Header, description = "Resume after this event, replaying missed events from the last 7 days"),
+    ),
+    responses(
+        (status = 200, description = "Event stream of `game.started`, `game.ended`, `game.cancelled`, `game.nomination`, `game.execution` and `stats.updated` events", content_type = "text/event-stream", body = String),
+        (status = 400, description = "Invalid Last-Event-ID", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> AppResult<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
+    event_stream(&state, None, &headers).await
+}
+
+async fn event_stream(
+    state: &AppState,
+    guild_id: Option<i64>,
+    headers: &HeaderMap,
+) -> AppResult<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
+    let last_event_id = headers
+        .get("last-event-id")
+        .map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(|id| id.parse::<i64>().ok())
+                .ok_or_else(|| AppError::Validation("Invalid Last-Event-ID".to_string()))
+        })
+        .transpose()?;
+
+    let events = state.services.events.subscribe(guild_id, last_event_id).await?;
+    let stream = events.map(|event| {
+        Ok(Event::default()
+            .id(event.id.to_string())
+            .event(event.event)
+            .data(event.data))
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+// ============================================================================
+// Guild-Scoped Endpoints (protected by API key middleware)
+// ============================================================================
+
+/// Get the statistics summary for one guild
+#[utoipa::path(
+    get,
+    path = "/api/v1/guilds/{guild_id}/stats/summary",
+    tag = "guilds",
+    params(
+        ("guild_id" = i64, Path, description = "Discord guild ID"),
+    ),
+    responses(
+        (status = 200, description = "Guild totals", body = StatsSummary),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_guild_stats_summary(
+    State(state): State<AppState>,
+    Path(guild_id): Path<i64>,
+) -> AppResult<Json<StatsSummary>> {
+    let stats = state.services.aggregates.get_summary(Some(guild_id)).await?;
+    Ok(Json(stats))
+}
+
+/// Get one guild's activity and win split over time
+#[utoipa::path(
+    get,
+    path = "/api/v1/guilds/{guild_id}/stats/timeseries",
+    tag = "guilds",
+    params(
+        ("guild_id" = i64, Path, description = "Discord guild ID"),
+        TimeseriesQuery,
+        StatsScope,
+    ),
+    responses(
+        (status = 200, description = "Bucketed activity and balance", body = Timeseries),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_guild_stats_timeseries(
+    State(state): State<AppState>,
+    Path(guild_id): Path<i64>,
+    Query(query): Query<TimeseriesQuery>,
+    Query(mut scope): Query<StatsScope>,
+) -> AppResult<Json<Timeseries>> {
+    scope.guild_id = Some(guild_id);
+    validation::validate_stats_scope(&scope)?;
+
+    let timeseries = state.services.stats.get_timeseries(query.bucket, &scope).await?;
+    Ok(Json(timeseries))
+}
+
+/// List one guild's completed games, with the same filters as the games list
+#[utoipa::path(
+    get,
+    path = "/api/v1/guilds/{guild_id}/games",
+    tag = "guilds",
+    params(
+        ("guild_id" = i64, Path, description = "Discord guild ID"),
+        PaginationQuery,
+        GameFilter,
+    ),
+    responses(
+        (status = 200, description = "A page of the guild's games", body = Page<Game>, headers(("link" = String, description = "`rel=\"next\"` link to the next page"))),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_guild_games(
+    State(state): State<AppState>,
+    Path(guild_id): Path<i64>,
+    uri: Uri,
+    Query(pagination): Query<PaginationQuery>,
+    Query(mut filter): Query<GameFilter>,
+) -> AppResult<Response> {
+    filter.guild_id = Some(guild_id);
+    list_games(&state, &uri, &pagination, &filter).await
+}
+
+/// Get a player's statistics within one guild
+#[utoipa::path(
+    get,
+    path = "/api/v1/guilds/{guild_id}/players/{discord_id}/stats",
+    tag = "guilds",
+    params(
+        ("guild_id" = i64, Path, description = "Discord guild ID"),
+        ("discord_id" = i64, Path, description = "Discord user ID"),
+    ),
+    responses(
+        (status = 200, description = "Player totals within the guild", body = PlayerStats),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 404, description = "Player not found", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_guild_player_stats(
+    State(state): State<AppState>,
+    Path((guild_id, discord_id)): Path<(i64, i64)>,
+) -> AppResult<Json<PlayerStats>> {
+    let stats = state.services.aggregates.get_player_stats(discord_id, Some(guild_id)).await?
+        .ok_or_else(|| AppError::NotFound("Player not found".to_string()))?;
+
+    Ok(Json(stats))
+}
+
+/// Get a script's statistics within one guild
+#[utoipa::path(
+    get,
+    path = "/api/v1/guilds/{guild_id}/scripts/{script_name}/stats",
+    tag = "guilds",
+    params(
+        ("guild_id" = i64, Path, description = "Discord guild ID"),
+        ("script_name" = String, Path, description = "Script id, or a name the script was played under"),
+    ),
+    responses(
+        (status = 200, description = "Script totals within the guild", body = ScriptStats),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 404, description = "Script not found", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_guild_script_stats(
+    State(state): State<AppState>,
+    Path((guild_id, script_name)): Path<(i64, String)>,
+) -> AppResult<Json<ScriptStats>> {
+    validation::validate_script_name(&script_name)?;
+
+    let script_id = state.services.scripts.resolve(&script_name).await?
+        .ok_or_else(|| AppError::NotFound("Script not found".to_string()))?;
+    let stats = state.services.aggregates.get_script_stats(&script_id, Some(guild_id)).await?
+        .ok_or_else(|| AppError::NotFound("Script not found".to_string()))?;
+
+    Ok(Json(stats))
+}
+
+/// Rank one guild's players by a metric within an optional script and date
+/// scope
+#[utoipa::path(
+    get,
+    path = "/api/v1/guilds/{guild_id}/leaderboards",
+    tag = "guilds",
+    params(
+        ("guild_id" = i64, Path, description = "Discord guild ID"),
+        LeaderboardQuery,
+        StatsScope,
+    ),
+    responses(
+        (status = 200, description = "Ranked players in the guild", body = Leaderboard),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_guild_leaderboard(
+    State(state): State<AppState>,
+    Path(guild_id): Path<i64>,
+    Query(query): Query<LeaderboardQuery>,
+    Query(mut scope): Query<StatsScope>,
+) -> AppResult<Json<Leaderboard>> {
+    scope.guild_id = Some(guild_id);
+    leaderboard(&state, &query, &scope).await.map(Json)
+}
+
+/// Stream one guild's game and stats events as Server-Sent Events
+#[utoipa::path(
+    get,
+    path = "/api/v1/guilds/{guild_id}/events",
+    tag = "guilds",
+    params(
+        ("guild_id" = i64, Path, description = "Discord guild ID"),
+        ("last-event-id" = Option<i64>, Header, description = "Resume after this event, replaying missed events from the last 7 days"),
+    ),
+    responses(
+        (status = 200, description = "Event stream of the guild's events", content_type = "text/event-stream", body = String),
+        (status = 400, description = "Invalid Last-Event-ID", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_guild_events(
+    State(state): State<AppState>,
+    Path(guild_id): Path<i64>,
+    headers: HeaderMap,
+) -> AppResult<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
+    event_stream(&state, Some(guild_id), &headers).await
+}
+
+// ============================================================================
+// API Key Management (protected by session token middleware)
 // ============================================================================
 
 pub async fn list_api_keys(
@@ -110,10 +1038,15 @@ pub async fn list_api_keys(
     Ok(Json(vec![]))
 }
 
+/// Create an API key for the session's Discord user
 pub async fn create_api_key(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
+    Extension(AuthenticatedSession(session)): Extension<AuthenticatedSession>,
     Json(payload): Json<ApiKeyCreate>,
-) -> AppResult<StatusCode> {
+) -> AppResult<(StatusCode, Json<ApiKeyResponse>)> {
+    let discord_user_id = verified_session_user(&session)?;
+
+
     // Validate API key name
     validation::validate_api_key_name(&payload.name)?;
     
@@ -122,16 +1055,44 @@ pub async fn create_api_key(
         validation::validate_rate_limit(rate_limit)?;
     }
     
-    // TODO: Verify session token and implement
-    Err(AppError::Internal("Not yet implemented".to_string()))
+    // Validate guild restriction if provided
+    if let Some(ref guild_ids) = payload.guild_ids {
+        validation::validate_api_key_guilds(guild_ids)?;
+    }
+
+    let created = state.services.api_keys.create_key(discord_user_id, &payload).await?;
+    Ok((StatusCode::CREATED, Json(created)))
 }
 
+/// Update one of the session user's API keys, including its guild
+/// restriction
 pub async fn update_api_key(
-    State(_state): State<AppState>,
-    Path(_key_id): Path<i32>,
-) -> AppResult<StatusCode> {
-    // TODO: Implement
-    Err(AppError::Internal("Not yet implemented".to_string()))
+    State(state): State<AppState>,
+    Extension(AuthenticatedSession(session)): Extension<AuthenticatedSession>,
+    Path(key_id): Path<i32>,
+    Json(payload): Json<ApiKeyUpdate>,
+) -> AppResult<Json<ApiKeyResponse>> {
+    let discord_user_id = verified_session_user(&session)?;
+
+    if let Some(ref name) = payload.name {
+        validation::validate_api_key_name(name)?;
+    }
+    if let Some(rate_limit) = payload.rate_limit {
+        validation::validate_rate_limit(rate_limit)?;
+    }
+    // An empty list lifts the restriction
+    if let Some(ref guild_ids) = payload.guild_ids {
+        if !guild_ids.is_empty() {
+            validation::validate_api_key_guilds(guild_ids)?;
+        }
+    }
+
+    let updated = state.services.api_keys
+        .update_key(discord_user_id, key_id, &payload)
+        .await?
+        .ok_or_else(|| AppError::NotFound("API key not found".to_string()))?;
+
+    Ok(Json(updated))
 }
 
 pub async fn delete_api_key(
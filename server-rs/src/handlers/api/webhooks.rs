@@ -0,0 +1,157 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::{
+    error::{AppError, AppResult, ErrorBody},
+    middleware::AuthenticatedApiKey,
+    models::{Webhook, WebhookCreate, WebhookCreated, WebhookDelivery},
+    state::AppState,
+    utils::validation,
+};
+
+// ============================================================================
+// Guild Webhooks (protected by API key middleware, owned by the calling key)
+// ============================================================================
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeliveryQuery {
+    /// Deliveries to return, from 1 to 100
+    #[serde(default = "default_delivery_limit")]
+    limit: i64,
+}
+
+fn default_delivery_limit() -> i64 {
+    50
+}
+
+/// Register a webhook for a guild's game events
+#[utoipa::path(
+    post,
+    path = "/api/v1/guilds/{guild_id}/webhooks",
+    tag = "webhooks",
+    params(
+        ("guild_id" = i64, Path, description = "Discord guild ID"),
+    ),
+    request_body = WebhookCreate,
+    responses(
+        (status = 201, description = "Webhook registered, with its signing secret", body = WebhookCreated),
+        (status = 400, description = "Invalid URL or events, or a URL that resolves to a private address", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn create_webhook(
+    State(state): State<AppState>,
+    Extension(AuthenticatedApiKey(api_key)): Extension<AuthenticatedApiKey>,
+    Path(guild_id): Path<i64>,
+    Json(payload): Json<WebhookCreate>,
+) -> AppResult<(StatusCode, Json<WebhookCreated>)> {
+    // Plain HTTP is allowed outside production so a local receiver can stand in
+    validation::validate_webhook_url(&payload.url, state.config.node_env != "production")?;
+    validation::validate_webhook_events(&payload.events)?;
+    state.services.webhooks.check_target(&payload.url).await?;
+
+    let created = state.services.webhooks.create_webhook(api_key.id, guild_id, &payload).await?;
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+/// List the calling key's webhooks for a guild
+#[utoipa::path(
+    get,
+    path = "/api/v1/guilds/{guild_id}/webhooks",
+    tag = "webhooks",
+    params(
+        ("guild_id" = i64, Path, description = "Discord guild ID"),
+    ),
+    responses(
+        (status = 200, description = "The key's webhooks for the guild", body = Vec<Webhook>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn list_webhooks(
+    State(state): State<AppState>,
+    Extension(AuthenticatedApiKey(api_key)): Extension<AuthenticatedApiKey>,
+    Path(guild_id): Path<i64>,
+) -> AppResult<Json<Vec<Webhook>>> {
+    let webhooks = state.services.webhooks.list_webhooks(api_key.id, guild_id).await?;
+    Ok(Json(webhooks))
+}
+
+/// Delete a webhook and its delivery log
+#[utoipa::path(
+    delete,
+    path = "/api/v1/guilds/{guild_id}/webhooks/{webhook_id}",
+    tag = "webhooks",
+    params(
+        ("guild_id" = i64, Path, description = "Discord guild ID"),
+        ("webhook_id" = i32, Path, description = "Webhook ID"),
+    ),
+    responses(
+        (status = 204, description = "Webhook deleted"),
+        (status = 404, description = "Webhook not found", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    Extension(AuthenticatedApiKey(api_key)): Extension<AuthenticatedApiKey>,
+    Path((guild_id, webhook_id)): Path<(i64, i32)>,
+) -> AppResult<StatusCode> {
+    if !state.services.webhooks.delete_webhook(api_key.id, guild_id, webhook_id).await? {
+        return Err(AppError::NotFound("Webhook not found".to_string()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Get a webhook's most recent deliveries, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/guilds/{guild_id}/webhooks/{webhook_id}/deliveries",
+    tag = "webhooks",
+    params(
+        ("guild_id" = i64, Path, description = "Discord guild ID"),
+        ("webhook_id" = i32, Path, description = "Webhook ID"),
+        DeliveryQuery,
+    ),
+    responses(
+        (status = 200, description = "Recent deliveries, newest first", body = Vec<WebhookDelivery>),
+        (status = 400, description = "Invalid limit", body = ErrorBody),
+        (status = 404, description = "Webhook not found", body = ErrorBody),
+        (status = 401, description = "Missing or invalid API key", body = ErrorBody),
+        (status = 403, description = "API key is restricted to other guilds", body = ErrorBody),
+        (status = 429, description = "Rate limit exceeded", body = ErrorBody),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_webhook_deliveries(
+    State(state): State<AppState>,
+    Extension(AuthenticatedApiKey(api_key)): Extension<AuthenticatedApiKey>,
+    Path((guild_id, webhook_id)): Path<(i64, i32)>,
+    Query(query): Query<DeliveryQuery>,
+) -> AppResult<Json<Vec<WebhookDelivery>>> {
+    if query.limit < 1 || query.limit > 100 {
+        return Err(AppError::Validation("Limit must be between 1 and 100".to_string()));
+    }
+
+    let deliveries = state.services.webhooks
+        .get_deliveries(api_key.id, guild_id, webhook_id, query.limit)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Webhook not found".to_string()))?;
+
+    Ok(Json(deliveries))
+}
@@ -142,9 +142,9 @@ pub async fn discord_callback(
 
     println!("💾 Creating session...");
     
-    // Store session in database (expires in 30 days)
+    // Store session in database (expires in 30 days); Discord vouched for the user
     let session = state.services.session
-        .create_session(&session_token, discord_id, 30 * 24 * 60 * 60)
+        .create_session(&session_token, discord_id, true, 30 * 24 * 60 * 60)
         .await?;
 
     println!("✅ Session created: {}", session.session_id);
@@ -0,0 +1,321 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use crate::{
+    error::{AppError, AppResult},
+    middleware::AuthenticatedSession,
+    services::{game_writes::GameWrite, scripts::ScriptDescription},
+    models::{Game, GameCorrection, GameCorrectionEntry, GameDetail, NewGame, PlayerRoleUpdate, WebSession},
+    state::AppState,
+    utils::validation,
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartGameRequest {
+    pub script: String,
+    pub custom_name: Option<String>,
+    #[serde(default)]
+    pub players: Vec<String>,
+    pub session_code: String,
+    /// Character ids of a custom script, used to identify it
+    #[serde(default)]
+    pub characters: Vec<String>,
+    #[serde(default)]
+    pub bootlegger: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct StartGameResponse {
+    pub game_id: i32,
+}
+
+#[derive(Deserialize)]
+pub struct EndGameRequest {
+    #[serde(alias = "gameId")]
+    pub game_id: i32,
+    pub winner: String,
+}
+
+#[derive(Deserialize)]
+pub struct CancelGameRequest {
+    #[serde(alias = "gameId")]
+    pub game_id: i32,
+}
+
+#[derive(Serialize)]
+pub struct GameStatusResponse {
+    pub success: bool,
+    pub game_id: i32,
+}
+
+#[derive(Deserialize)]
+pub struct PlayerRoleRequest {
+    #[serde(alias = "gameId")]
+    pub game_id: i32,
+    #[serde(alias = "playerName")]
+    pub player_name: String,
+    #[serde(alias = "seatNumber", alias = "playerNumber")]
+    pub seat_number: Option<i32>,
+    #[serde(alias = "roleId")]
+    pub role_id: Option<String>,
+    #[serde(alias = "roleName")]
+    pub role_name: Option<String>,
+    #[serde(alias = "roleTeam")]
+    pub team: Option<String>,
+    #[serde(default, alias = "isFinal")]
+    pub is_final: bool,
+    #[serde(alias = "discordId")]
+    pub discord_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct AddPlayerResponse {
+    #[serde(rename = "playerId")]
+    pub player_id: i32,
+}
+
+#[derive(Serialize)]
+pub struct CorrectGameResponse {
+    pub game: GameDetail,
+    pub corrections: Vec<GameCorrectionEntry>,
+}
+
+fn now_seconds() -> f64 {
+    chrono::Utc::now().timestamp_millis() as f64 / 1000.0
+}
+
+/// Get the Discord user a web session is linked to
+pub(crate) fn session_user(session: &WebSession) -> AppResult<i64> {
+    session.discord_user_id
+        .ok_or_else(|| AppError::Forbidden("Session is not linked to a Discord user".to_string()))
+}
+
+/// Get the Discord user a web session proved through Discord OAuth. Sessions
+/// created from a bare Discord ID can't act on the authority of that user.
+pub(crate) fn verified_session_user(session: &WebSession) -> AppResult<i64> {
+    if !session.verified {
+        return Err(AppError::Forbidden("Sign in with Discord to do this".to_string()));
+    }
+    session_user(session)
+}
+
+/// Load an active game, checking that the session's user is storytelling it.
+/// Game results feed ratings and stats, so writing them needs a session
+/// verified through Discord.
+async fn authorize_active_game(state: &AppState, session: &WebSession, game_id: i32) -> AppResult<Game> {
+    let discord_user_id = verified_session_user(session)?;
+
+    let game = state.services.game.get_game(game_id).await?
+        .ok_or_else(|| AppError::NotFound("Game not found".to_string()))?;
+
+    if game.storyteller_user_id != Some(discord_user_id) {
+        return Err(AppError::Forbidden("Only the storyteller can update this game".to_string()));
+    }
+    if game.is_active != Some(true) {
+        return Err(AppError::Conflict("Game has already ended".to_string()));
+    }
+
+    Ok(game)
+}
+
+/// Load a completed game, checking that the session's user storytold it or is
+/// an admin. Rewriting history needs a session verified through Discord.
+async fn authorize_completed_game(state: &AppState, session: &WebSession, game_id: i32) -> AppResult<Game> {
+    let discord_user_id = verified_session_user(session)?;
+
+    let game = state.services.game.get_game(game_id).await?
+        .ok_or_else(|| AppError::NotFound("Game not found".to_string()))?;
+
+    let is_admin = state.config.admin_discord_ids.contains(&discord_user_id);
+    if game.storyteller_user_id != Some(discord_user_id) && !is_admin {
+        return Err(AppError::Forbidden("Only the storyteller can correct this game".to_string()));
+    }
+    if game.is_active != Some(false) || game.winner.as_deref() == Some("Cancelled") {
+        return Err(AppError::Conflict("Only completed games can be corrected".to_string()));
+    }
+
+    Ok(game)
+}
+
+impl PlayerRoleRequest {
+    fn into_update(self) -> AppResult<PlayerRoleUpdate> {
+        let discord_id = match self.discord_id.as_deref().filter(|id| !id.is_empty()) {
+            Some(id) => {
+                validation::validate_discord_id(id)?;
+                Some(id.parse().map_err(|_| AppError::Validation("Invalid Discord ID".to_string()))?)
+            }
+            None => None,
+        };
+
+        Ok(PlayerRoleUpdate {
+            player_name: self.player_name,
+            seat_number: self.seat_number,
+            discord_id,
+            role_id: self.role_id,
+            role_name: self.role_name,
+            team: self.team,
+            is_final: self.is_final,
+        })
+    }
+}
+
+/// Start a game in the Discord guild session linked to a session code
+pub async fn start_game(
+    State(state): State<AppState>,
+    Extension(AuthenticatedSession(session)): Extension<AuthenticatedSession>,
+    Json(payload): Json<StartGameRequest>,
+) -> AppResult<impl IntoResponse> {
+    let discord_user_id = verified_session_user(&session)?;
+    validation::validate_script_name(&payload.script)?;
+    validation::validate_script_contents(&payload.characters, &payload.bootlegger)?;
+
+    let guild_session = state.services.session
+        .get_guild_session_by_code(&payload.session_code)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Session code not found".to_string()))?;
+
+    if guild_session.active_game_id.is_some() {
+        return Err(AppError::Conflict("Session already has an active game".to_string()));
+    }
+
+    let custom_name = payload.custom_name.filter(|name| !name.is_empty());
+    let script_id = state.services.scripts
+        .identify(&ScriptDescription {
+            script: &payload.script,
+            custom_name: custom_name.as_deref(),
+            characters: &payload.characters,
+            bootlegger: &payload.bootlegger,
+        })
+        .await?;
+
+    let new_game = NewGame {
+        guild_id: guild_session.guild_id,
+        category_id: Some(guild_session.category_id),
+        script: payload.script,
+        script_id,
+        custom_name,
+        start_time: now_seconds(),
+        players: payload.players,
+        storyteller_user_id: Some(discord_user_id),
+        recorded_session_code: None,
+    };
+
+    let game_id = state.services.game.create_game(&new_game).await?;
+    state.services.game_writes.after_game_write(game_id, GameWrite::Started).await;
+
+    Ok((StatusCode::OK, Json(StartGameResponse { game_id })))
+}
+
+/// End a game with a winner
+pub async fn end_game(
+    State(state): State<AppState>,
+    Extension(AuthenticatedSession(session)): Extension<AuthenticatedSession>,
+    Json(payload): Json<EndGameRequest>,
+) -> AppResult<impl IntoResponse> {
+    validation::validate_winner(&payload.winner)?;
+    authorize_active_game(&state, &session, payload.game_id).await?;
+
+    state.services.game
+        .complete_game(payload.game_id, now_seconds(), Some(&payload.winner), &[])
+        .await?;
+
+    state.services.game_writes.after_game_write(payload.game_id, GameWrite::Ended).await;
+
+    Ok((StatusCode::OK, Json(GameStatusResponse { success: true, game_id: payload.game_id })))
+}
+
+/// Cancel a game without recording stats
+pub async fn cancel_game(
+    State(state): State<AppState>,
+    Extension(AuthenticatedSession(session)): Extension<AuthenticatedSession>,
+    Json(payload): Json<CancelGameRequest>,
+) -> AppResult<impl IntoResponse> {
+    authorize_active_game(&state, &session, payload.game_id).await?;
+
+    state.services.game.cancel_game(payload.game_id, now_seconds()).await?;
+    state.services.game_writes.after_game_write(payload.game_id, GameWrite::Cancelled).await;
+
+    Ok((StatusCode::OK, Json(GameStatusResponse { success: true, game_id: payload.game_id })))
+}
+
+/// Record a player's starting or final role
+pub async fn update_player_role(
+    State(state): State<AppState>,
+    Extension(AuthenticatedSession(session)): Extension<AuthenticatedSession>,
+    Json(payload): Json<PlayerRoleRequest>,
+) -> AppResult<impl IntoResponse> {
+    authorize_active_game(&state, &session, payload.game_id).await?;
+
+    let game_id = payload.game_id;
+    let update = payload.into_update()?;
+    state.services.game.record_player_role(game_id, &update).await?;
+    state.services.cache.invalidate().await;
+
+    Ok((StatusCode::OK, Json(GameStatusResponse { success: true, game_id })))
+}
+
+/// Add a player to a game
+pub async fn add_player(
+    State(state): State<AppState>,
+    Extension(AuthenticatedSession(session)): Extension<AuthenticatedSession>,
+    Json(payload): Json<PlayerRoleRequest>,
+) -> AppResult<impl IntoResponse> {
+    authorize_active_game(&state, &session, payload.game_id).await?;
+
+    let game_id = payload.game_id;
+    let update = payload.into_update()?;
+    let player_id = state.services.game.record_player_role(game_id, &update).await?;
+    state.services.cache.invalidate().await;
+
+    Ok((StatusCode::OK, Json(AddPlayerResponse { player_id })))
+}
+
+/// Correct the winner, roles, teams or survival of a completed game
+pub async fn correct_game(
+    State(state): State<AppState>,
+    Extension(AuthenticatedSession(session)): Extension<AuthenticatedSession>,
+    Path(game_id): Path<i32>,
+    Json(payload): Json<GameCorrection>,
+) -> AppResult<Json<CorrectGameResponse>> {
+    authorize_completed_game(&state, &session, game_id).await?;
+
+    if let Some(ref winner) = payload.winner {
+        validation::validate_winner(winner)?;
+    }
+    if let Some(ref reason) = payload.reason {
+        validation::validate_correction_reason(reason)?;
+    }
+
+    let corrected_by = session_user(&session)?;
+    // Roles a correction replaces no longer show up in the game afterwards
+    let keys_before = state.services.aggregates.game_keys(game_id).await?;
+    let corrections = state.services.game.correct_game(game_id, &payload, corrected_by).await?;
+
+    if !corrections.is_empty() {
+        state.services.game_writes
+            .after_game_write(game_id, GameWrite::Corrected { keys_before, corrections: &corrections })
+            .await;
+    }
+
+    let game = state.services.game.get_game_detail(game_id).await?
+        .ok_or_else(|| AppError::NotFound("Game not found".to_string()))?;
+
+    Ok(Json(CorrectGameResponse { game, corrections }))
+}
+
+/// Get the correction history of a completed game
+pub async fn get_game_corrections(
+    State(state): State<AppState>,
+    Extension(AuthenticatedSession(session)): Extension<AuthenticatedSession>,
+    Path(game_id): Path<i32>,
+) -> AppResult<Json<Vec<GameCorrectionEntry>>> {
+    authorize_completed_game(&state, &session, game_id).await?;
+
+    let corrections = state.services.game.get_game_corrections(game_id).await?;
+    Ok(Json(corrections))
+}
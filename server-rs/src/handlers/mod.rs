@@ -1,5 +1,7 @@
 pub mod health;
 pub mod auth;
 pub mod api;
+pub mod game;
+pub mod scripts;
 pub mod session;
 pub mod websocket;
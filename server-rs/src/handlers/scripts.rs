@@ -0,0 +1,145 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    error::{AppError, AppResult},
+    handlers::game::verified_session_user,
+    middleware::AuthenticatedSession,
+    models::{RegisteredScript, WebSession},
+    services::scripts::ScriptDocument,
+    state::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct ScriptListQuery {
+    /// Only scripts uploaded by this Discord user
+    owner: Option<i64>,
+    /// A script id, or any name a script was played under
+    script: Option<String>,
+    #[serde(default = "default_limit")]
+    limit: i64,
+    #[serde(default)]
+    offset: i64,
+}
+
+fn default_limit() -> i64 {
+    50
+}
+
+/// Load an uploaded script, checking that the session's user signed in with
+/// Discord and uploaded it or is an admin
+async fn authorize_registered_script(state: &AppState, session: &WebSession, id: &str) -> AppResult<RegisteredScript> {
+    let discord_user_id = verified_session_user(session)?;
+
+    let script = state.services.scripts.get_registered(id).await?
+        .ok_or_else(|| AppError::NotFound("Script not found".to_string()))?;
+
+    let is_admin = state.config.admin_discord_ids.contains(&discord_user_id);
+    if script.owner_discord_id != discord_user_id && !is_admin {
+        return Err(AppError::Forbidden("Only the uploader can change this script".to_string()));
+    }
+
+    Ok(script)
+}
+
+// ============================================================================
+// Uploads (protected by session token middleware)
+// ============================================================================
+
+/// Upload a Script Tool JSON script to the registry
+pub async fn upload_script(
+    State(state): State<AppState>,
+    Extension(AuthenticatedSession(session)): Extension<AuthenticatedSession>,
+    Json(payload): Json<Value>,
+) -> AppResult<(StatusCode, Json<RegisteredScript>)> {
+    let owner = verified_session_user(&session)?;
+    let document = ScriptDocument::parse(&payload)?;
+
+    let script = state.services.scripts.register(owner, &document).await?;
+    state.services.cache.invalidate().await;
+
+    Ok((StatusCode::CREATED, Json(script)))
+}
+
+/// Replace an uploaded script's contents. Its URL stays the same.
+pub async fn update_script(
+    State(state): State<AppState>,
+    Extension(AuthenticatedSession(session)): Extension<AuthenticatedSession>,
+    Path(id): Path<String>,
+    Json(payload): Json<Value>,
+) -> AppResult<Json<RegisteredScript>> {
+    authorize_registered_script(&state, &session, &id).await?;
+    let document = ScriptDocument::parse(&payload)?;
+
+    let script = state.services.scripts.replace_registered(&id, &document).await?
+        .ok_or_else(|| AppError::NotFound("Script not found".to_string()))?;
+    state.services.cache.invalidate().await;
+
+    Ok(Json(script))
+}
+
+/// Remove an uploaded script from the registry
+pub async fn delete_script(
+    State(state): State<AppState>,
+    Extension(AuthenticatedSession(session)): Extension<AuthenticatedSession>,
+    Path(id): Path<String>,
+) -> AppResult<StatusCode> {
+    authorize_registered_script(&state, &session, &id).await?;
+
+    if !state.services.scripts.delete_registered(&id).await? {
+        return Err(AppError::NotFound("Script not found".to_string()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// ============================================================================
+// Public registry (no authentication, so the grimoire can load scripts by URL)
+// ============================================================================
+
+/// List uploaded scripts, most recently updated first
+pub async fn list_scripts(
+    State(state): State<AppState>,
+    Query(query): Query<ScriptListQuery>,
+) -> AppResult<Json<Vec<RegisteredScript>>> {
+    if query.limit < 1 || query.limit > 100 {
+        return Err(AppError::Validation("Limit must be between 1 and 100".to_string()));
+    }
+    if query.offset < 0 {
+        return Err(AppError::Validation("Offset cannot be negative".to_string()));
+    }
+
+    let scripts = state.services.scripts
+        .list_registered(query.owner, query.script.as_deref(), query.limit, query.offset)
+        .await?;
+
+    Ok(Json(scripts))
+}
+
+/// Get an uploaded script's details
+pub async fn get_script_info(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<RegisteredScript>> {
+    let script = state.services.scripts.get_registered(&id).await?
+        .ok_or_else(|| AppError::NotFound("Script not found".to_string()))?;
+
+    Ok(Json(script))
+}
+
+/// Serve an uploaded script's JSON, as loaded by the grimoire's "Enter URL"
+pub async fn get_script_document(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let document = state.services.scripts.get_document(&id).await?
+        .ok_or_else(|| AppError::NotFound("Script not found".to_string()))?;
+
+    Ok(([(header::CONTENT_TYPE, "application/json")], document))
+}
@@ -1,11 +1,16 @@
 use axum::{
-    extract::State,
+    extract::{Path, State},
     http::StatusCode,
     response::IntoResponse,
-    Json,
+    Extension, Json,
 };
 use serde::{Deserialize, Serialize};
-use crate::{error::AppResult, state::AppState};
+use crate::{
+    error::{AppError, AppResult},
+    middleware::AuthenticatedSession,
+    models::{Game, Session},
+    state::AppState,
+};
 
 #[derive(Deserialize)]
 pub struct CreateSessionRequest {
@@ -24,14 +29,15 @@ pub async fn create_session(
     Json(payload): Json<CreateSessionRequest>,
 ) -> AppResult<impl IntoResponse> {
     let discord_user_id: i64 = payload.discord_user_id.parse()
-        .map_err(|_| crate::error::AppError::BadRequest("Invalid Discord user ID".to_string()))?;
+        .map_err(|_| AppError::BadRequest("Invalid Discord user ID".to_string()))?;
 
     // Generate session token
     let token = uuid::Uuid::new_v4().to_string();
 
-    // Create session (expires in 30 days)
+    // Create session (expires in 30 days). Nothing proves the user, so it
+    // stays unverified.
     let session = state.services.session
-        .create_session(&token, discord_user_id, 30 * 24 * 60 * 60)
+        .create_session(&token, discord_user_id, false, 30 * 24 * 60 * 60)
         .await?;
 
     Ok((
@@ -42,3 +48,88 @@ pub async fn create_session(
         })
     ))
 }
+
+#[derive(Deserialize)]
+pub struct UpdateDiscordRequest {
+    pub discord_user_id: String,
+}
+
+#[derive(Serialize)]
+pub struct UpdateDiscordResponse {
+    pub success: bool,
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    pub discord_user_id: String,
+}
+
+#[derive(Serialize)]
+pub struct SessionInfoResponse {
+    pub session_id: String,
+    pub discord_user_id: Option<String>,
+    pub created_at: Option<i64>,
+    pub expires_at: i64,
+    pub guild_sessions: Vec<Session>,
+    pub active_game: Option<Game>,
+}
+
+/// Link the authenticated web session to a Discord user, or switch it to
+/// another one. Like a session created from a bare Discord ID, a session
+/// switched this way is unverified until its user signs in with Discord.
+pub async fn update_discord_user(
+    State(state): State<AppState>,
+    Extension(AuthenticatedSession(session)): Extension<AuthenticatedSession>,
+    Json(payload): Json<UpdateDiscordRequest>,
+) -> AppResult<impl IntoResponse> {
+    let discord_user_id: i64 = payload.discord_user_id.parse()
+        .map_err(|_| AppError::BadRequest("Invalid Discord user ID".to_string()))?;
+
+    let session_id = state.services.session
+        .link_discord_user(&session.session_id, discord_user_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?
+        .session_id;
+
+    Ok((
+        StatusCode::OK,
+        Json(UpdateDiscordResponse {
+            success: true,
+            session_id,
+            discord_user_id: discord_user_id.to_string(),
+        })
+    ))
+}
+
+/// Get metadata for a web session, including the guild sessions and active
+/// game its Discord user is storytelling
+pub async fn get_session_info(
+    State(state): State<AppState>,
+    Extension(AuthenticatedSession(auth_session)): Extension<AuthenticatedSession>,
+    Path(session_id): Path<String>,
+) -> AppResult<Json<SessionInfoResponse>> {
+    // Sessions may only be inspected with their own token
+    if auth_session.session_id != session_id {
+        return Err(AppError::Forbidden("Session token does not match session".to_string()));
+    }
+
+    let session = state.services.session
+        .get_session_by_id(&session_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
+
+    let (guild_sessions, active_game) = match session.discord_user_id {
+        Some(discord_user_id) => (
+            state.services.session.get_guild_sessions_for_storyteller(discord_user_id).await?,
+            state.services.game.get_active_game_for_storyteller(discord_user_id).await?,
+        ),
+        None => (Vec::new(), None),
+    };
+
+    Ok(Json(SessionInfoResponse {
+        session_id: session.session_id,
+        discord_user_id: session.discord_user_id.map(|id| id.to_string()),
+        created_at: session.created_at,
+        expires_at: session.expires_at,
+        guild_sessions,
+        active_game,
+    }))
+}
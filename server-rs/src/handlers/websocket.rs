@@ -12,13 +12,19 @@ use tokio::sync::mpsc;
 use uuid::Uuid;
 use crate::state::{AppState, SessionClients};
 
+/// Who a client connected as: the player it sits as, or `None` for the
+/// storyteller's grimoire, and its Discord user if it signed in
+struct ClientIdentity {
+    player_id: Option<String>,
+    discord_user_id: i64,
+}
+
 /// WebSocket handler with optional path parameters
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
 ) -> Response {
-    let clients = state.websocket_clients.clone();
-    ws.on_upgrade(move |socket| handle_socket(socket, None, clients))
+    ws.on_upgrade(move |socket| handle_socket(socket, None, None, state))
 }
 
 /// WebSocket handler with channel path parameter
@@ -27,22 +33,52 @@ pub async fn websocket_handler_with_channel(
     Path(channel): Path<String>,
     State(state): State<AppState>,
 ) -> Response {
-    let clients = state.websocket_clients.clone();
-    ws.on_upgrade(move |socket| handle_socket(socket, Some(channel), clients))
+    ws.on_upgrade(move |socket| handle_socket(socket, Some(channel), None, state))
 }
 
-/// WebSocket handler with channel and client path parameters
+/// WebSocket handler with channel and client path parameters. The client is
+/// `host` for the storyteller's grimoire, or the player id of a player.
 pub async fn websocket_handler_with_client(
     ws: WebSocketUpgrade,
-    Path((channel, _client)): Path<(String, String)>,
+    Path((channel, client)): Path<(String, String)>,
     State(state): State<AppState>,
 ) -> Response {
-    let clients = state.websocket_clients.clone();
-    ws.on_upgrade(move |socket| handle_socket(socket, Some(channel), clients))
+    ws.on_upgrade(move |socket| handle_socket(socket, Some(channel), Some(client), state))
+}
+
+/// The session token of an `["auth", token]` message, which signed-in
+/// clients send first to identify themselves. These messages are never
+/// relayed.
+fn auth_token(text: &str) -> Option<String> {
+    let (command, token) = serde_json::from_str::<(String, String)>(text).ok()?;
+    (command == "auth").then_some(token)
+}
+
+/// Find the Discord user behind a session token. Only sessions created by
+/// signing in with Discord count; anything else connects anonymously.
+async fn identify_client(state: &AppState, token: &str, client: String) -> Option<ClientIdentity> {
+    let session = match state.services.session.get_session_by_token(token).await {
+        Ok(session) => session?,
+        Err(e) => {
+            tracing::error!("Failed to look up WebSocket session token: {}", e);
+            return None;
+        }
+    };
+    let discord_user_id = session.discord_user_id.filter(|_| session.verified)?;
+
+    Some(ClientIdentity {
+        player_id: (client != "host").then_some(client),
+        discord_user_id,
+    })
 }
 
 /// Handle an individual WebSocket connection
-async fn handle_socket(socket: WebSocket, session_id_from_path: Option<String>, clients: SessionClients) {
+async fn handle_socket(
+    socket: WebSocket,
+    session_id_from_path: Option<String>,
+    client: Option<String>,
+    state: AppState,
+) {
     let (mut sender, mut receiver) = socket.split();
     let client_id = Uuid::new_v4().to_string();
 
@@ -61,12 +97,12 @@ async fn handle_socket(socket: WebSocket, session_id_from_path: Option<String>,
     });
 
     // Use shared session state from AppState
-    let clients_for_recv = clients.clone();
+    let clients_for_recv = state.websocket_clients.clone();
     
     // Handle incoming messages
     let mut recv_task = tokio::spawn(async move {
         // Session ID from path or will be determined from first message
-        let mut session_id: Option<String> = session_id_from_path.clone();
+        let session_id: Option<String> = session_id_from_path.clone();
         
         // If we have session from path, register immediately
         if let Some(ref sid) = session_id {
@@ -82,11 +118,27 @@ async fn handle_socket(socket: WebSocket, session_id_from_path: Option<String>,
                 sid,
                 clients_lock.get(sid).map(|c| c.len()).unwrap_or(0)
             );
+            drop(clients_lock);
         }
 
+        let mut identified = false;
         while let Some(Ok(msg)) = receiver.next().await {
             match msg {
                 Message::Text(text) => {
+                    if let Some(token) = auth_token(&text) {
+                        if let (Some(sid), Some(client)) = (&session_id, &client) {
+                            if !identified && state.config.auto_record_games {
+                                identified = true;
+                                if let Some(identity) = identify_client(&state, &token, client.clone()).await {
+                                    state.services.recorder
+                                        .add_client(sid, &client_id, identity.player_id.as_deref(), identity.discord_user_id)
+                                        .await;
+                                }
+                            }
+                        }
+                        continue;
+                    }
+
                     tracing::debug!("Message from {} in session {:?}: {}", client_id, session_id, text);
                     
                     // Broadcast message to all clients in the session
@@ -97,6 +149,10 @@ async fn handle_socket(socket: WebSocket, session_id_from_path: Option<String>,
                             &client_id,
                             &text,
                         ).await;
+
+                        if state.config.auto_record_games {
+                            state.services.recorder.handle_message(sid, &client_id, &text).await;
+                        }
                     } else {
                         tracing::warn!("Message from {} but no session established (this shouldn't happen)", client_id);
                     }
@@ -105,6 +161,8 @@ async fn handle_socket(socket: WebSocket, session_id_from_path: Option<String>,
                     tracing::info!("Client {} disconnected", client_id);
                     break;
                 }
+                // The payload is moved into the pong, so this can't be a match guard
+                #[allow(clippy::collapsible_match)]
                 Message::Ping(data) => {
                     // Respond with pong
                     if tx.send(Message::Pong(data)).is_err() {
@@ -118,13 +176,24 @@ async fn handle_socket(socket: WebSocket, session_id_from_path: Option<String>,
         // Cleanup: remove client from session
         if let Some(sid) = session_id {
             let mut clients_lock = clients_for_recv.write().await;
+            let mut session_empty = false;
             if let Some(session_clients) = clients_lock.get_mut(&sid) {
                 session_clients.remove(&client_id);
                 if session_clients.is_empty() {
                     clients_lock.remove(&sid);
+                    session_empty = true;
                     tracing::info!("Session {} is now empty, removed", sid);
                 }
             }
+            drop(clients_lock);
+
+            if state.config.auto_record_games {
+                if session_empty {
+                    state.services.recorder.remove_session(&sid).await;
+                } else {
+                    state.services.recorder.remove_client(&sid, &client_id).await;
+                }
+            }
         }
 
         client_id
@@ -162,7 +231,7 @@ async fn broadcast_to_session(
                 continue;
             }
             
-            if let Err(_) = tx.send(Message::Text(message.to_string())) {
+            if tx.send(Message::Text(message.to_string())).is_err() {
                 failed_clients.push(client_id.clone());
             }
         }
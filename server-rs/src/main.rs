@@ -13,7 +13,7 @@ use axum::{
     http::{header, Method},
     middleware::{self as axum_middleware, Next},
     response::Response,
-    routing::{get, post},
+    routing::{get, post, put},
     Router,
 };
 use std::time::Duration;
@@ -50,22 +50,113 @@ async fn main() -> anyhow::Result<()> {
     database.run_migrations().await?;
     info!("Database connected and migrations applied");
 
+    // Maintenance commands recompute derived stats and exit
+    match std::env::args().nth(1).as_deref() {
+        Some("rebuild-ratings") => {
+            let rated = services::ratings::RatingService::new(database.clone()).rebuild().await?;
+            info!("Rebuilt player ratings from {} games", rated);
+            return Ok(());
+        }
+        Some("rebuild-aggregates") => {
+            services::aggregates::AggregateService::new(database.clone()).rebuild().await?;
+            info!("Rebuilt stats aggregates");
+            return Ok(());
+        }
+        _ => {}
+    }
+
     // Initialize services
-    let services = services::ServiceContainer::new(database.clone());
+    let services = services::ServiceContainer::new(database.clone(), &config);
     info!("Services initialized");
 
+    if services.aggregates.rebuild_if_empty().await? {
+        info!("Built stats aggregates from existing games");
+    }
+
     // Create shared application state
     let state = AppState::new(config.clone(), database, services);
 
+    // Bulk exports and event streams stream their bodies, so they skip the
+    // response cache
+    let streaming_routes = Router::new()
+        .route("/api/v1/export/games", get(api::v1::export_games))
+        .route("/api/v1/export/game-players", get(api::v1::export_game_players))
+        .route("/api/v1/export/players", get(api::v1::export_players))
+        .route("/api/v1/events", get(api::v1::get_events))
+        .route("/api/v1/guilds/:guild_id/events", get(api::v1::get_guild_events));
+
+    // Script validation answers POSTed scripts, so there's nothing to cache
+    let validation_routes = Router::new()
+        .route("/api/v1/scripts/validate", post(api::v1::validate_script));
+
+    // Webhook management writes, so it also skips the response cache
+    let webhook_routes = Router::new()
+        .route("/api/v1/guilds/:guild_id/webhooks", get(api::webhooks::list_webhooks).post(api::webhooks::create_webhook))
+        .route("/api/v1/guilds/:guild_id/webhooks/:webhook_id", axum::routing::delete(api::webhooks::delete_webhook))
+        .route("/api/v1/guilds/:guild_id/webhooks/:webhook_id/deliveries", get(api::webhooks::get_webhook_deliveries));
+
     // Protected API routes (require API key)
     let protected_routes = Router::new()
         .route("/api/v1/games", get(api::v1::get_games))
         .route("/api/v1/games/:id", get(api::v1::get_game_by_id))
         .route("/api/v1/stats/summary", get(api::v1::get_stats_summary))
+        .route("/api/v1/stats/timeseries", get(api::v1::get_stats_timeseries))
         .route("/api/v1/players/:discord_id/stats", get(api::v1::get_player_stats))
+        .route("/api/v1/players/:discord_id/games", get(api::v1::get_player_games))
+        .route("/api/v1/players/:discord_id/teammates", get(api::v1::get_player_teammates))
+        .route("/api/v1/players/:discord_id/opponents", get(api::v1::get_player_opponents))
+        .route("/api/v1/players/:discord_id/head-to-head/:other_id", get(api::v1::get_head_to_head))
+        .route("/api/v1/storytellers/:discord_id/stats", get(api::v1::get_storyteller_stats))
+        .route("/api/v1/storytellers/:discord_id/games", get(api::v1::get_storyteller_games))
         .route("/api/v1/scripts/:script_name/stats", get(api::v1::get_script_stats))
+        .route("/api/v1/leaderboards", get(api::v1::get_leaderboard))
+        .route("/api/v1/roles/stats", get(api::v1::list_role_stats))
+        .route("/api/v1/roles/:role_id/stats", get(api::v1::get_role_stats))
+        .route("/api/v1/guilds/:guild_id/stats/summary", get(api::v1::get_guild_stats_summary))
+        .route("/api/v1/guilds/:guild_id/stats/timeseries", get(api::v1::get_guild_stats_timeseries))
+        .route("/api/v1/guilds/:guild_id/games", get(api::v1::get_guild_games))
+        .route("/api/v1/guilds/:guild_id/players/:discord_id/stats", get(api::v1::get_guild_player_stats))
+        .route("/api/v1/guilds/:guild_id/scripts/:script_name/stats", get(api::v1::get_guild_script_stats))
+        .route("/api/v1/guilds/:guild_id/leaderboards", get(api::v1::get_guild_leaderboard))
+        .route_layer(axum_middleware::from_fn_with_state(state.clone(), crate::middleware::http_cache))
+        .merge(streaming_routes)
+        .merge(webhook_routes)
+        .merge(validation_routes)
+        .route_layer(axum_middleware::from_fn(crate::middleware::verify_guild_access))
         .route_layer(axum_middleware::from_fn_with_state(state.clone(), crate::middleware::verify_api_key));
 
+    // Session-authenticated routes (require Bearer session token)
+    let session_routes = Router::new()
+        .route("/api/session/update-discord", post(handlers::session::update_discord_user))
+        .route("/api/session/:session_id", get(handlers::session::get_session_info))
+        .route_layer(axum_middleware::from_fn_with_state(state.clone(), crate::middleware::verify_session_token));
+
+    // Game write routes (require Bearer session token, honour Idempotency-Key)
+    let game_routes = Router::new()
+        .route("/api/game/start", post(handlers::game::start_game))
+        .route("/api/game/end", post(handlers::game::end_game))
+        .route("/api/game/cancel", post(handlers::game::cancel_game))
+        .route("/api/game/update-role", post(handlers::game::update_player_role))
+        .route("/api/player/add", post(handlers::game::add_player))
+        .route("/api/game/:game_id/correct", post(handlers::game::correct_game))
+        .route("/api/game/:game_id/corrections", get(handlers::game::get_game_corrections))
+        .route_layer(axum_middleware::from_fn_with_state(state.clone(), crate::middleware::idempotency))
+        .route_layer(axum_middleware::from_fn_with_state(state.clone(), crate::middleware::verify_session_token));
+
+    // Script registry uploads (session auth)
+    let script_routes = Router::new()
+        .route("/api/scripts", post(handlers::scripts::upload_script))
+        .route("/api/scripts/:script_id", put(handlers::scripts::update_script).delete(handlers::scripts::delete_script))
+        .route_layer(axum_middleware::from_fn_with_state(state.clone(), crate::middleware::idempotency))
+        .route_layer(axum_middleware::from_fn_with_state(state.clone(), crate::middleware::verify_session_token));
+
+    // API key management (session auth)
+    let key_routes = Router::new()
+        .route("/api/v1/keys", get(api::v1::list_api_keys))
+        .route("/api/v1/keys/create", post(api::v1::create_api_key))
+        .route("/api/v1/keys/:key_id", post(api::v1::update_api_key).delete(api::v1::delete_api_key))
+        .route_layer(axum_middleware::from_fn_with_state(state.clone(), crate::middleware::verify_session_token));
+
     // Build main router
     let app = Router::new()
         // Health check
@@ -83,14 +174,23 @@ async fn main() -> anyhow::Result<()> {
         // Session management (no API key required)
         .route("/api/session/create", post(handlers::session::create_session))
         
+        // API description (no API key required)
+        .route("/api/v1/openapi.json", get(api::openapi::get_openapi))
+        
+        // Game stats for the frontend (no API key required)
+        .route("/api/stats/game/:id", get(api::v1::get_game_by_id))
+        
+        // Uploaded scripts, loadable by URL from the grimoire (no auth required)
+        .route("/api/scripts", get(handlers::scripts::list_scripts))
+        .route("/api/scripts/:script_id", get(handlers::scripts::get_script_document))
+        .route("/api/scripts/:script_id/info", get(handlers::scripts::get_script_info))
+        
         // Merge protected routes
         .merge(protected_routes)
-        
-        // API key management - TODO: should use session auth instead
-        .route("/api/v1/keys", get(api::v1::list_api_keys))
-        .route("/api/v1/keys/create", post(api::v1::create_api_key))
-        .route("/api/v1/keys/:key_id", post(api::v1::update_api_key))
-        .route("/api/v1/keys/:key_id", axum::routing::delete(api::v1::delete_api_key))
+        .merge(session_routes)
+        .merge(game_routes)
+        .merge(script_routes)
+        .merge(key_routes)
         
         // Middleware
         .layer(
@@ -99,8 +199,9 @@ async fn main() -> anyhow::Result<()> {
                 .layer(
                     CorsLayer::new()
                         .allow_origin(Any)
-                        .allow_methods([Method::GET, Method::POST, Method::OPTIONS, Method::DELETE, Method::PATCH])
-                        .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION, header::HeaderName::from_static("x-api-key")])
+                        .allow_methods([Method::GET, Method::POST, Method::OPTIONS, Method::PUT, Method::DELETE, Method::PATCH])
+                        .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION, header::HeaderName::from_static("x-api-key"), header::HeaderName::from_static("idempotency-key"), header::IF_NONE_MATCH, header::HeaderName::from_static("last-event-id")])
+                        .expose_headers([header::ETAG, header::LAST_MODIFIED, header::LINK])
                         .max_age(Duration::from_secs(86400)),
                 )
                 .layer(axum_middleware::from_fn(security_headers)),
@@ -1,17 +1,21 @@
-//! Authentication middleware for API key verification
+//! Authentication middleware for API key and session token verification
 
 use axum::{
-    extract::{Request, State},
+    extract::{RawPathParams, Request, State},
+    http::header,
     middleware::Next,
     response::Response,
 };
-use sha2::{Digest, Sha256};
 
-use crate::{error::AppError, models::ApiKey, state::AppState};
+use crate::{
+    error::AppError,
+    models::{ApiKey, WebSession},
+    services::api_keys::hash_api_key,
+    state::AppState,
+};
 
 /// Extension type to store authenticated API key in request
 #[derive(Clone)]
-#[allow(dead_code)]
 pub struct AuthenticatedApiKey(pub ApiKey);
 
 /// Middleware to verify API key from X-API-Key header
@@ -26,14 +30,11 @@ pub async fn verify_api_key(
         .and_then(|v| v.to_str().ok())
         .ok_or_else(|| AppError::Unauthorized("Missing X-API-Key header".to_string()))?;
 
-    // Hash the key
-    let mut hasher = Sha256::new();
-    hasher.update(api_key.as_bytes());
-    let key_hash = format!("{:x}", hasher.finalize());
+    let key_hash = hash_api_key(api_key);
 
     // Look up key in database
     let api_key_record = sqlx::query_as::<_, ApiKey>(
-        "SELECT id, key_hash, name, discord_user_id, rate_limit, created_at, last_used_at, is_active, notes 
+        "SELECT id, key_hash, name, discord_user_id, rate_limit, created_at, last_used_at, is_active, notes, guild_ids 
          FROM api_keys 
          WHERE key_hash = $1 AND is_active = true"
     )
@@ -73,3 +74,59 @@ pub async fn verify_api_key(
 
     Ok(next.run(request).await)
 }
+
+/// Middleware to keep guild-restricted API keys to their own guilds' routes.
+/// Must run after `verify_api_key`.
+pub async fn verify_guild_access(
+    params: Option<RawPathParams>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let api_key = request
+        .extensions()
+        .get::<AuthenticatedApiKey>()
+        .ok_or_else(|| AppError::Unauthorized("Missing API key".to_string()))?;
+
+    if api_key.0.guild_ids.is_some() {
+        let guild_id = params
+            .as_ref()
+            .and_then(|params| params.iter().find(|(key, _)| *key == "guild_id"))
+            .and_then(|(_, value)| value.parse::<i64>().ok())
+            .ok_or_else(|| AppError::Forbidden("This API key can only use guild-scoped endpoints".to_string()))?;
+
+        if !api_key.0.allows_guild(guild_id) {
+            return Err(AppError::Forbidden("This API key cannot access this guild".to_string()));
+        }
+    }
+
+    Ok(next.run(request).await)
+}
+
+/// Extension type to store the authenticated web session in request
+#[derive(Clone)]
+pub struct AuthenticatedSession(pub WebSession);
+
+/// Middleware to verify a web session token from the Authorization header
+pub async fn verify_session_token(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or_else(|| AppError::Unauthorized("Missing session token".to_string()))?;
+
+    let session = state
+        .services
+        .session
+        .get_session_by_token(token)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("Invalid or expired session".to_string()))?;
+
+    request.extensions_mut().insert(AuthenticatedSession(session));
+
+    Ok(next.run(request).await)
+}
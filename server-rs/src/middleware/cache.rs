@@ -0,0 +1,111 @@
+//! ETag / Last-Modified handling and response caching for read endpoints
+
+use axum::{
+    body::{self, Body},
+    extract::{Query, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    middleware::Next,
+    response::Response,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{error::AppError, services::cache::CachedResponse, state::AppState};
+
+/// Middleware that serves read responses from the in-process cache, tags them
+/// with an ETag and Last-Modified, and answers a matching If-None-Match with
+/// 304. Only successful responses are cached.
+pub async fn http_cache(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let cache = &state.services.cache;
+    let key = cache_key(request.uri());
+    let if_none_match = request.headers().get(header::IF_NONE_MATCH).cloned();
+
+    let cached = match cache.get(&key).await {
+        Some(cached) => cached,
+        None => {
+            // Taken before running the handler, so a write racing with it
+            // keeps the response out of the cache
+            let generation = cache.generation().await;
+            let response = next.run(request).await;
+            if response.status() != StatusCode::OK {
+                return Ok(response);
+            }
+
+            let (parts, body) = response.into_parts();
+            let body = body::to_bytes(body, usize::MAX)
+                .await
+                .map_err(|e| AppError::Internal(format!("Failed to read response body: {}", e)))?;
+
+            let cached = CachedResponse {
+                etag: etag_for(&body),
+                headers: parts.headers,
+                body,
+            };
+            cache.insert(key, generation, cached.clone()).await;
+            cached
+        }
+    };
+
+    let mut headers = cached.headers.clone();
+    headers.insert(header::ETAG, HeaderValue::from_str(&cached.etag).unwrap());
+    // Clients may keep the response but must check it is still current
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("private, no-cache"));
+    if let Some(last_modified) = cache.last_modified().await? {
+        let http_date = last_modified.and_utc().format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        headers.insert(header::LAST_MODIFIED, HeaderValue::from_str(&http_date).unwrap());
+    }
+
+    if if_none_match.is_some_and(|value| etag_matches(&value, &cached.etag)) {
+        headers.remove(header::CONTENT_TYPE);
+        headers.remove(header::CONTENT_LENGTH);
+        return Ok(with_headers(Response::new(Body::empty()), StatusCode::NOT_MODIFIED, headers));
+    }
+
+    Ok(with_headers(Response::new(Body::from(cached.body)), StatusCode::OK, headers))
+}
+
+/// Cache key of a request: its path and its decoded query parameters sorted
+/// by name, so the same query spelled differently shares one entry
+fn cache_key(uri: &Uri) -> String {
+    let Ok(Query(mut params)) = Query::<Vec<(String, String)>>::try_from_uri(uri) else {
+        return uri.path_and_query().map_or_else(|| uri.path().to_string(), |pq| pq.as_str().to_string());
+    };
+    if params.is_empty() {
+        return uri.path().to_string();
+    }
+
+    // Stable, so repeated parameters keep their order
+    params.sort_by(|a, b| a.0.cmp(&b.0));
+    let query: Vec<String> = params
+        .iter()
+        .map(|(name, value)| format!("{}={}", urlencoding::encode(name), urlencoding::encode(value)))
+        .collect();
+    format!("{}?{}", uri.path(), query.join("&"))
+}
+
+fn with_headers(mut response: Response, status: StatusCode, headers: HeaderMap) -> Response {
+    *response.status_mut() = status;
+    *response.headers_mut() = headers;
+    response
+}
+
+/// Strong ETag from the response body, so it only changes when the data does
+fn etag_for(body: &[u8]) -> String {
+    let digest = format!("{:x}", Sha256::digest(body));
+    format!("\"{}\"", &digest[..32])
+}
+
+/// Whether an If-None-Match header lists the ETag, using the weak comparison
+/// RFC 9110 asks for
+fn etag_matches(if_none_match: &HeaderValue, etag: &str) -> bool {
+    let Ok(value) = if_none_match.to_str() else {
+        return false;
+    };
+
+    value.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.trim_start_matches("W/") == etag
+    })
+}
@@ -0,0 +1,101 @@
+//! Idempotency-Key handling for game-write endpoints
+
+use axum::{
+    body::{self, Body},
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::AppError,
+    middleware::AuthenticatedSession,
+    services::idempotency::{IdempotencyClaim, StoredResponse},
+    state::AppState,
+};
+
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Middleware that replays the stored response when a request is retried with
+/// the same Idempotency-Key. Requests without the header pass straight through.
+pub async fn idempotency(
+    State(state): State<AppState>,
+    Extension(AuthenticatedSession(session)): Extension<AuthenticatedSession>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(key) = request
+        .headers()
+        .get("idempotency-key")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+    else {
+        return Ok(next.run(request).await);
+    };
+
+    if key.is_empty() || key.len() > 255 {
+        return Err(AppError::Validation("Idempotency-Key must be 1-255 characters".to_string()));
+    }
+
+    // Buffer the body so it can be fingerprinted and passed on
+    let (parts, body) = request.into_parts();
+    let bytes = body::to_bytes(body, MAX_BODY_BYTES)
+        .await
+        .map_err(|_| AppError::BadRequest("Request body too large".to_string()))?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(parts.method.as_str().as_bytes());
+    hasher.update(&bytes);
+    let request_hash = format!("{:x}", hasher.finalize());
+    let request_path = parts.uri.path().to_string();
+
+    let idempotency = &state.services.idempotency;
+    match idempotency.claim(&session.session_id, &key, &request_path, &request_hash).await? {
+        IdempotencyClaim::Claimed => {}
+        IdempotencyClaim::Replay(stored) => return Ok(replay(stored)),
+        IdempotencyClaim::InProgress => {
+            return Err(AppError::Conflict("A request with this Idempotency-Key is still in progress".to_string()));
+        }
+        IdempotencyClaim::Mismatch => {
+            return Err(AppError::Validation("Idempotency-Key was already used for a different request".to_string()));
+        }
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    let status = response.status();
+
+    // Server errors aren't stored, so the retry gets a real second attempt
+    if status.is_server_error() {
+        idempotency.release(&session.session_id, &key).await?;
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let bytes = body::to_bytes(body, MAX_BODY_BYTES)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to read response body: {}", e)))?;
+
+    let stored = StoredResponse {
+        status_code: status.as_u16(),
+        body: String::from_utf8_lossy(&bytes).into_owned(),
+    };
+    // The write has already happened, so the client still gets its response.
+    // A retry past the claim's lease could run the write again, but failing
+    // here would have the client retry it straight away.
+    if let Err(e) = idempotency.complete(&session.session_id, &key, &stored).await {
+        tracing::error!("Failed to store the response for Idempotency-Key {}: {}", key, e);
+    }
+
+    Ok(Response::from_parts(parts, Body::from(bytes)))
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let status = StatusCode::from_u16(stored.status_code).unwrap_or(StatusCode::OK);
+    let mut response = (status, stored.body).into_response();
+    response.headers_mut().insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+    response.headers_mut().insert("idempotent-replayed", "true".parse().unwrap());
+    response
+}
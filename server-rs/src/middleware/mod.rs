@@ -1,3 +1,7 @@
 pub mod auth;
+pub mod cache;
+pub mod idempotency;
 
-pub use auth::verify_api_key;
+pub use auth::{verify_api_key, verify_guild_access, verify_session_token, AuthenticatedApiKey, AuthenticatedSession};
+pub use cache::http_cache;
+pub use idempotency::idempotency;
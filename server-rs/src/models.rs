@@ -1,12 +1,12 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use utoipa::{IntoParams, ToSchema};
 
 // ============================================================================
 // Session Models (Discord bot sessions)
 // ============================================================================
 
-#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Session {
     pub guild_id: i64,
@@ -26,11 +26,14 @@ pub struct Session {
 // Game Models
 // ============================================================================
 
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct Game {
     pub game_id: i32,
     pub guild_id: i64,
     pub script: String,
+    /// Identity of the script, stable across renames. Missing until games
+    /// written by other clients have been identified.
+    pub script_id: Option<String>,
     pub custom_name: Option<String>,
     pub start_time: f64,
     pub end_time: Option<f64>,
@@ -46,7 +49,6 @@ pub struct Game {
     pub storyteller_user_id: Option<i64>,
 }
 
-#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct GamePlayer {
     pub id: i32,
@@ -65,6 +67,212 @@ pub struct GamePlayer {
     pub starting_team: Option<String>,
 }
 
+/// Filters for listing completed games
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GameFilter {
+    pub guild_id: Option<i64>,
+    /// A script id, or any name a script was played under
+    pub script: Option<String>,
+    pub storyteller_id: Option<i64>,
+    pub winner: Option<String>,
+    pub min_players: Option<i32>,
+    pub max_players: Option<i32>,
+    /// Completed on or after this date
+    pub from: Option<NaiveDate>,
+    /// Completed on or before this date
+    pub to: Option<NaiveDate>,
+    /// Only games this Discord user played in
+    pub discord_id: Option<i64>,
+    #[serde(default)]
+    pub sort: GameSort,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GameSort {
+    #[default]
+    Newest,
+    Oldest,
+    MostPlayers,
+    FewestPlayers,
+    Longest,
+    Shortest,
+}
+
+/// Filters for a player's match history
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PlayerGameFilter {
+    /// Matches either the starting or final role
+    pub role: Option<String>,
+    /// `good` or `evil` for the final alignment, or a character type such as
+    /// `minion`
+    pub team: Option<String>,
+    /// A script id, or any name a script was played under
+    pub script: Option<String>,
+}
+
+/// One seat in a player's match history, with the game it was in
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct PlayerGame {
+    pub game_id: i32,
+    pub guild_id: i64,
+    pub script: String,
+    pub script_id: Option<String>,
+    pub custom_name: Option<String>,
+    pub start_time: f64,
+    pub end_time: Option<f64>,
+    pub completed_at: Option<NaiveDateTime>,
+    pub winner: Option<String>,
+    pub player_count: Option<i32>,
+    pub storyteller_user_id: Option<i64>,
+    pub seat_number: i32,
+    pub player_name: String,
+    pub starting_role_id: Option<String>,
+    pub starting_role_name: Option<String>,
+    pub starting_team: Option<String>,
+    pub final_role_id: Option<String>,
+    pub final_role_name: Option<String>,
+    pub final_team: Option<String>,
+    pub survived: Option<bool>,
+    pub won: Option<bool>,
+}
+
+/// A game about to be recorded
+#[derive(Debug, Clone)]
+pub struct NewGame {
+    pub guild_id: i64,
+    pub category_id: Option<i64>,
+    pub script: String,
+    pub script_id: String,
+    pub custom_name: Option<String>,
+    pub start_time: f64,
+    pub players: Vec<String>,
+    pub storyteller_user_id: Option<i64>,
+    /// Live session the server records the game from, if it does
+    pub recorded_session_code: Option<String>,
+}
+
+/// A seat recorded when a game completes
+#[derive(Debug, Clone)]
+pub struct NewGamePlayer {
+    pub discord_id: Option<i64>,
+    pub player_name: String,
+    pub seat_number: i32,
+    pub starting_role_id: Option<String>,
+    pub starting_role_name: Option<String>,
+    pub starting_team: Option<String>,
+    pub final_role_id: Option<String>,
+    pub final_role_name: Option<String>,
+    pub final_team: Option<String>,
+    pub survived: bool,
+}
+
+/// A starting or final role recorded for a seat while a game is running
+#[derive(Debug, Clone)]
+pub struct PlayerRoleUpdate {
+    pub player_name: String,
+    pub seat_number: Option<i32>,
+    pub discord_id: Option<i64>,
+    pub role_id: Option<String>,
+    pub role_name: Option<String>,
+    pub team: Option<String>,
+    pub is_final: bool,
+}
+
+/// A single seat in a game, with the role and team the player started and
+/// ended on
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct GameSeat {
+    pub seat_number: i32,
+    pub discord_id: Option<i64>,
+    pub player_name: String,
+    pub starting_role_id: Option<String>,
+    pub starting_role_name: Option<String>,
+    pub starting_team: Option<String>,
+    pub final_role_id: Option<String>,
+    pub final_role_name: Option<String>,
+    pub final_team: Option<String>,
+    pub role_changed: bool,
+    pub team_changed: bool,
+    pub survived: Option<bool>,
+    pub won: Option<bool>,
+}
+
+impl From<GamePlayer> for GameSeat {
+    fn from(player: GamePlayer) -> Self {
+        // A missing starting role means only the final role was recorded
+        let role_changed = player.starting_role_id.is_some()
+            && player.final_role_id.is_some()
+            && player.starting_role_id != player.final_role_id;
+        let team_changed = player.starting_team.is_some()
+            && player.final_team.is_some()
+            && player.starting_team != player.final_team;
+
+        Self {
+            seat_number: player.seat_number,
+            discord_id: player.discord_id,
+            player_name: player.player_name,
+            starting_role_id: player.starting_role_id,
+            starting_role_name: player.starting_role_name,
+            starting_team: player.starting_team,
+            final_role_id: player.final_role_id,
+            final_role_name: player.final_role_name,
+            final_team: player.final_team,
+            role_changed,
+            team_changed,
+            survived: player.survived,
+            won: player.winning_team,
+        }
+    }
+}
+
+/// A game with its full seat breakdown
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GameDetail {
+    #[serde(flatten)]
+    pub game: Game,
+    pub duration_seconds: Option<f64>,
+    pub seats: Vec<GameSeat>,
+}
+
+/// Storyteller corrections to a completed game. Omitted fields are left as
+/// they are.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GameCorrection {
+    pub winner: Option<String>,
+    #[serde(default)]
+    pub seats: Vec<SeatCorrection>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SeatCorrection {
+    pub seat_number: i32,
+    pub starting_role_id: Option<String>,
+    pub starting_role_name: Option<String>,
+    pub starting_team: Option<String>,
+    pub final_role_id: Option<String>,
+    pub final_role_name: Option<String>,
+    pub final_team: Option<String>,
+    pub survived: Option<bool>,
+}
+
+/// A single audited change made by a correction
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct GameCorrectionEntry {
+    pub id: i32,
+    pub game_id: i32,
+    pub seat_number: Option<i32>,
+    pub field: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub corrected_by: i64,
+    pub reason: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
 // ============================================================================
 // API Key Models
 // ============================================================================
@@ -80,6 +288,15 @@ pub struct ApiKey {
     pub last_used_at: Option<NaiveDateTime>,
     pub is_active: Option<bool>,
     pub notes: Option<String>,
+    /// Guilds the key may read; `None` allows every guild
+    pub guild_ids: Option<Vec<i64>>,
+}
+
+impl ApiKey {
+    /// Whether the key may read a guild's statistics
+    pub fn allows_guild(&self, guild_id: i64) -> bool {
+        self.guild_ids.as_ref().is_none_or(|ids| ids.contains(&guild_id))
+    }
 }
 
 // ============================================================================
@@ -94,6 +311,8 @@ pub struct WebSession {
     pub discord_user_id: Option<i64>,
     pub created_at: Option<i64>,  // BIGINT unix timestamp
     pub expires_at: i64,  // BIGINT unix timestamp
+    /// Created through Discord OAuth, so `discord_user_id` is proven
+    pub verified: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -101,24 +320,39 @@ pub struct ApiKeyCreate {
     pub name: String,
     pub rate_limit: Option<i32>,
     pub notes: Option<String>,
+    /// Restrict the key to these guilds
+    pub guild_ids: Option<Vec<i64>>,
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Serialize, Deserialize)]
+/// Changes to an API key. Omitted fields are left as they are.
+#[derive(Debug, Deserialize)]
+pub struct ApiKeyUpdate {
+    pub name: Option<String>,
+    pub rate_limit: Option<i32>,
+    pub notes: Option<String>,
+    /// Restrict the key to these guilds; an empty list lifts the restriction
+    pub guild_ids: Option<Vec<i64>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct ApiKeyResponse {
     pub id: i32,
     pub name: String,
-    pub key: String, // Only returned on creation
+    /// Only returned on creation
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
     pub rate_limit: i32,
     pub is_active: bool,
     pub created_at: NaiveDateTime,
+    pub guild_ids: Option<Vec<i64>>,
 }
 
 // ============================================================================
 // Stats Models
 // ============================================================================
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct StatsSummary {
     pub total_games: i64,
     pub total_players: i64,
@@ -126,7 +360,7 @@ pub struct StatsSummary {
     pub active_games: i64,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct PlayerStats {
     pub discord_id: i64,
     pub player_name: String,
@@ -135,10 +369,15 @@ pub struct PlayerStats {
     pub losses: i64,
     pub survival_rate: f64,
     pub favorite_role: Option<String>,
+    /// Elo-style rating on each team, once the player has a rated game
+    pub good_rating: Option<f64>,
+    pub evil_rating: Option<f64>,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct ScriptStats {
+    pub script_id: String,
+    /// Name the script was last played under
     pub script_name: String,
     pub games_played: i64,
     pub good_wins: i64,
@@ -146,6 +385,438 @@ pub struct ScriptStats {
     pub average_player_count: f64,
 }
 
+/// Which completed games an aggregate covers
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatsScope {
+    pub guild_id: Option<i64>,
+    /// A script id, or any name a script was played under
+    pub script: Option<String>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+impl StatsScope {
+    /// Whether the scope is at most a guild, so precomputed aggregates cover it
+    pub fn is_guild_only(&self) -> bool {
+        self.script.is_none() && self.from.is_none() && self.to.is_none()
+    }
+}
+
+impl From<&StatsScope> for GameFilter {
+    fn from(scope: &StatsScope) -> Self {
+        Self {
+            guild_id: scope.guild_id,
+            script: scope.script.clone(),
+            from: scope.from,
+            to: scope.to,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardMetric {
+    #[default]
+    Wins,
+    WinRate,
+    GamesPlayed,
+    SurvivalRate,
+    GoodWinRate,
+    EvilWinRate,
+    GoodRating,
+    EvilRating,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct LeaderboardEntry {
+    pub rank: i64,
+    pub discord_id: i64,
+    pub player_name: String,
+    pub games_played: i64,
+    pub wins: i64,
+    pub losses: i64,
+    pub win_rate: f64,
+    pub survival_rate: f64,
+    pub good_games: i64,
+    pub good_win_rate: Option<f64>,
+    pub evil_games: i64,
+    pub evil_win_rate: Option<f64>,
+    /// Ratings are global and ignore the leaderboard's scope
+    pub good_rating: Option<f64>,
+    pub evil_rating: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Leaderboard {
+    pub metric: LeaderboardMetric,
+    pub min_games: i64,
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+/// Width of the buckets in a time series
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeBucket {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl TimeBucket {
+    /// The `date_trunc` unit for this bucket
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimeBucket::Day => "day",
+            TimeBucket::Week => "week",
+            TimeBucket::Month => "month",
+        }
+    }
+}
+
+/// Activity and balance over one bucket of decided games
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct TimeseriesPoint {
+    /// First day of the bucket; weeks start on Monday
+    pub bucket_start: NaiveDate,
+    pub games_played: i64,
+    pub unique_players: i64,
+    pub good_wins: i64,
+    pub evil_wins: i64,
+    pub good_win_rate: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Timeseries {
+    pub bucket: TimeBucket,
+    pub points: Vec<TimeseriesPoint>,
+}
+
+/// Totals over the games a storyteller ran
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct StorytellerTotals {
+    /// Completed games, excluding cancelled ones
+    pub games_run: i64,
+    pub cancelled_games: i64,
+    pub cancellation_rate: Option<f64>,
+    pub good_wins: i64,
+    pub evil_wins: i64,
+    /// How often evil wins the storyteller's decided games
+    pub evil_win_rate: Option<f64>,
+    pub average_player_count: Option<f64>,
+    pub average_duration_seconds: Option<f64>,
+}
+
+/// How often a storyteller has run a script
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct StorytellerScript {
+    pub script_id: String,
+    pub script: String,
+    pub games: i64,
+    pub good_wins: i64,
+    pub evil_wins: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StorytellerStats {
+    pub discord_id: i64,
+    #[serde(flatten)]
+    pub totals: StorytellerTotals,
+    pub scripts: Vec<StorytellerScript>,
+}
+
+/// The side a seat finished on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Alignment {
+    Good,
+    Evil,
+}
+
+impl Alignment {
+    /// The value stored in `games.winner` for this side
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Alignment::Good => "Good",
+            Alignment::Evil => "Evil",
+        }
+    }
+}
+
+/// Whether pairings count players on the same or the opposing side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pairing {
+    Teammates,
+    Opponents,
+}
+
+/// A player's record alongside or against another linked player. Wins and
+/// losses are from the requested player's point of view.
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct PlayerPairing {
+    pub discord_id: i64,
+    pub player_name: String,
+    pub games: i64,
+    pub wins: i64,
+    pub losses: i64,
+    pub win_rate: Option<f64>,
+}
+
+/// How two linked players fare together and against each other. Wins and
+/// losses are from the first player's point of view.
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct HeadToHead {
+    pub discord_id: i64,
+    pub other_discord_id: i64,
+    pub games_together: i64,
+    pub teammate_games: i64,
+    pub teammate_wins: i64,
+    pub teammate_win_rate: Option<f64>,
+    pub good_teammate_games: i64,
+    pub good_teammate_wins: i64,
+    pub evil_teammate_games: i64,
+    pub evil_teammate_wins: i64,
+    pub opponent_games: i64,
+    pub opponent_wins: i64,
+    pub opponent_losses: i64,
+    pub opponent_win_rate: Option<f64>,
+}
+
+/// How a character performs across seats that started or ended as it
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct RoleStats {
+    pub role_id: String,
+    pub role_name: Option<String>,
+    /// The team seats holding this role most often played for
+    pub team: Option<String>,
+    pub appearances: i64,
+    pub games_played: i64,
+    pub starting_count: i64,
+    /// Seats that ended as this role without starting as it
+    pub gained_count: i64,
+    pub starting_rate: f64,
+    /// Win rate of seats that ended as this role, in decided games
+    pub win_rate: Option<f64>,
+    pub survival_rate: Option<f64>,
+}
+
+// ============================================================================
+// Webhook Models
+// ============================================================================
+
+/// Game lifecycle events a webhook can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum WebhookEvent {
+    #[serde(rename = "game.started")]
+    Started,
+    #[serde(rename = "game.ended")]
+    Ended,
+    #[serde(rename = "game.cancelled")]
+    Cancelled,
+    #[serde(rename = "game.corrected")]
+    Corrected,
+}
+
+impl WebhookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::Started => "game.started",
+            WebhookEvent::Ended => "game.ended",
+            WebhookEvent::Cancelled => "game.cancelled",
+            WebhookEvent::Corrected => "game.corrected",
+        }
+    }
+}
+
+/// A registered webhook. The signing secret is only returned on creation.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct Webhook {
+    pub id: i32,
+    pub guild_id: i64,
+    pub url: String,
+    /// Subscribed events, such as `game.ended`
+    pub events: Vec<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct WebhookCreate {
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebhookCreated {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    /// Key for verifying the `X-Grimlive-Signature` header of deliveries
+    pub secret: String,
+}
+
+/// One event sent, or still to be sent, to a webhook
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub event: String,
+    /// `pending`, `delivered` or `failed` once retries run out
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: Option<NaiveDateTime>,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub delivered_at: Option<NaiveDateTime>,
+}
+
+/// Body POSTed to webhooks
+#[derive(Debug, Serialize)]
+pub struct WebhookPayload<'a> {
+    pub event: WebhookEvent,
+    pub occurred_at: NaiveDateTime,
+    pub game: &'a GameDetail,
+    /// Changes made by the correction, for `game.corrected`
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub corrections: &'a [GameCorrectionEntry],
+}
+
+// ============================================================================
+// Live Event Models
+// ============================================================================
+
+/// Event types streamed from `/api/v1/events`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StreamEvent {
+    #[serde(rename = "game.started")]
+    GameStarted,
+    #[serde(rename = "game.ended")]
+    GameEnded,
+    #[serde(rename = "game.cancelled")]
+    GameCancelled,
+    #[serde(rename = "game.nomination")]
+    Nomination,
+    #[serde(rename = "game.execution")]
+    Execution,
+    /// A guild's statistics changed after a game ended or was corrected
+    #[serde(rename = "stats.updated")]
+    StatsUpdated,
+}
+
+impl StreamEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StreamEvent::GameStarted => "game.started",
+            StreamEvent::GameEnded => "game.ended",
+            StreamEvent::GameCancelled => "game.cancelled",
+            StreamEvent::Nomination => "game.nomination",
+            StreamEvent::Execution => "game.execution",
+            StreamEvent::StatsUpdated => "stats.updated",
+        }
+    }
+}
+
+/// A published event as kept in the event log. `data` is the JSON sent to
+/// clients.
+#[derive(Debug, Clone, FromRow)]
+pub struct LiveEvent {
+    pub id: i64,
+    pub event: String,
+    pub guild_id: i64,
+    pub data: String,
+}
+
+// ============================================================================
+// Custom Script Models
+// ============================================================================
+
+/// An uploaded custom script. Its JSON is served from `/api/scripts/{id}`.
+#[derive(Debug, Serialize, FromRow)]
+pub struct RegisteredScript {
+    pub id: String,
+    pub owner_discord_id: i64,
+    /// Identity shared with the games played on the script
+    pub script_id: String,
+    pub name: String,
+    pub author: Option<String>,
+    /// Completed games played on the script, under any name
+    pub games_played: i64,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// The outcome of checking a custom script against the character database
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ScriptValidation {
+    /// False if any issue is an error, meaning the grimoire would not set
+    /// the script up as written
+    pub valid: bool,
+    pub name: Option<String>,
+    /// Id games played on the script are recorded under, when it has
+    /// characters
+    pub script_id: Option<String>,
+    /// Ids of the characters the grimoire would seat, normalized and in
+    /// script order
+    pub characters: Vec<String>,
+    pub team_counts: ScriptTeamCounts,
+    /// Largest player count the script has enough characters to set up
+    pub max_players: Option<i32>,
+    pub issues: Vec<ScriptIssue>,
+    /// Jinxes between characters on the script
+    pub jinxes: Vec<ScriptJinx>,
+}
+
+/// Distinct characters on a script of each team
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ScriptTeamCounts {
+    pub townsfolk: i32,
+    pub outsider: i32,
+    pub minion: i32,
+    pub demon: i32,
+    pub traveller: i32,
+    pub fabled: i32,
+    pub loric: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueSeverity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptIssueKind {
+    /// Not a JSON array, or an entry that isn't an id or an object with one
+    InvalidEntry,
+    InvalidMeta,
+    UnknownCharacter,
+    DuplicateCharacter,
+    /// A homebrew character without a name, team or ability
+    MissingField,
+    InvalidTeam,
+    /// Too few characters of a team to set up some player counts
+    TeamCount,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ScriptIssue {
+    pub severity: IssueSeverity,
+    pub kind: ScriptIssueKind,
+    pub message: String,
+    /// Position of the offending entry in the script
+    pub entry: Option<i32>,
+    pub character: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ScriptJinx {
+    pub character: String,
+    pub other: String,
+    pub reason: String,
+}
+
 // ============================================================================
 // WebSocket Messages (Legacy - kept for reference)
 // ============================================================================
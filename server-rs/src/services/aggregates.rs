@@ -0,0 +1,582 @@
+//! Precomputed statistics aggregates
+//!
+//! Per-guild totals for guilds, players, scripts and roles are stored in
+//! aggregate tables so reads don't rescan `game_players`. Guild totals are
+//! updated with each game's own contribution when it ends or its winner is
+//! corrected. Player, script and role rows are recomputed from the base
+//! tables, but only the rows of the players, script and roles the game
+//! touches, which keeps them exact without tracking every field's delta.
+
+use sqlx::{FromRow, PgConnection, Postgres, QueryBuilder};
+
+use crate::{
+    database::Database,
+    error::AppResult,
+    models::{GameFilter, PlayerStats, RoleStats, ScriptStats, StatsScope, StatsSummary},
+    services::game::push_game_filters,
+};
+
+/// Games that count towards player, guild and role aggregates
+const COUNTED_GAMES_SQL: &str = "games.is_active = false AND games.winner IS DISTINCT FROM 'Cancelled'";
+
+/// Totals for each guild, character and team the character was played on,
+/// over the seats of games. These are the columns of `role_aggregates`; see
+/// [`push_role_totals`].
+const ROLE_TOTALS_SQL: &str = "SELECT
+        games.guild_id,
+        r.role_id,
+        CASE WHEN gp.final_role_id = r.role_id THEN gp.final_team ELSE gp.starting_team END AS team,
+        MAX(CASE WHEN gp.final_role_id = r.role_id THEN gp.final_role_name ELSE gp.starting_role_name END) AS role_name,
+        COUNT(*) AS appearances,
+        COUNT(DISTINCT gp.game_id) AS games_played,
+        COUNT(*) FILTER (WHERE gp.starting_role_id = r.role_id) AS starting_count,
+        COUNT(*) FILTER (WHERE gp.final_role_id = r.role_id AND gp.starting_role_id IS DISTINCT FROM r.role_id) AS gained_count,
+        COUNT(*) FILTER (WHERE COALESCE(gp.final_role_id, gp.starting_role_id) = r.role_id AND gp.winning_team) AS final_wins,
+        COUNT(*) FILTER (WHERE COALESCE(gp.final_role_id, gp.starting_role_id) = r.role_id AND gp.winning_team IS NOT NULL) AS final_decided,
+        COUNT(*) FILTER (WHERE COALESCE(gp.final_role_id, gp.starting_role_id) = r.role_id AND gp.survived) AS final_survived,
+        COUNT(*) FILTER (WHERE COALESCE(gp.final_role_id, gp.starting_role_id) = r.role_id AND gp.survived IS NOT NULL) AS final_survival_known
+     FROM game_players gp
+     JOIN games ON games.game_id = gp.game_id
+     CROSS JOIN LATERAL (
+        SELECT DISTINCT v.role_id
+        FROM (VALUES (gp.starting_role_id), (gp.final_role_id)) AS v(role_id)
+        WHERE v.role_id IS NOT NULL
+     ) r";
+
+/// The aggregate rows a game contributes to
+#[derive(Debug, Clone, FromRow)]
+pub struct AggregateKeys {
+    guild_id: i64,
+    /// The game's winner when the keys were taken
+    winner: Option<String>,
+    scripts: Vec<String>,
+    discord_ids: Vec<i64>,
+    role_ids: Vec<String>,
+}
+
+impl AggregateKeys {
+    /// Combine the keys of a game before and after it changed
+    pub fn merge(mut self, other: AggregateKeys) -> Self {
+        self.scripts.extend(other.scripts);
+        self.discord_ids.extend(other.discord_ids);
+        self.role_ids.extend(other.role_ids);
+        self
+    }
+}
+
+#[derive(Clone)]
+pub struct AggregateService {
+    db: Database,
+}
+
+impl AggregateService {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+
+    /// Get the aggregate rows a game currently contributes to
+    pub async fn game_keys(&self, game_id: i32) -> AppResult<Option<AggregateKeys>> {
+        let keys = sqlx::query_as::<_, AggregateKeys>(
+            "SELECT
+                games.guild_id,
+                games.winner,
+                ARRAY_REMOVE(ARRAY[games.script_id], NULL) AS scripts,
+                ARRAY(
+                    SELECT DISTINCT discord_id FROM game_players
+                    WHERE game_id = $1 AND discord_id IS NOT NULL
+                ) AS discord_ids,
+                ARRAY(
+                    SELECT DISTINCT r.role_id
+                    FROM game_players gp, UNNEST(ARRAY[gp.starting_role_id, gp.final_role_id]) AS r(role_id)
+                    WHERE gp.game_id = $1 AND r.role_id IS NOT NULL
+                ) AS role_ids
+             FROM games
+             WHERE game_id = $1"
+        )
+        .bind(game_id)
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        Ok(keys)
+    }
+
+    /// Add a game that has just ended or been cancelled to the aggregates.
+    /// Must only be called once per game.
+    pub async fn add_game(&self, game_id: i32) -> AppResult<()> {
+        let Some(keys) = self.game_keys(game_id).await? else {
+            return Ok(());
+        };
+
+        let mut tx = self.db.pool.begin().await?;
+        lock_guild(&mut tx, keys.guild_id).await?;
+
+        sqlx::query(
+            "INSERT INTO guild_aggregates (guild_id, games_played, seats, good_wins, evil_wins, cancelled_games)
+             SELECT
+                guild_id,
+                COUNT(*) FILTER (WHERE winner IS DISTINCT FROM 'Cancelled'),
+                (SELECT COUNT(*) FROM game_players WHERE game_id = $1),
+                COUNT(*) FILTER (WHERE winner = 'Good'),
+                COUNT(*) FILTER (WHERE winner = 'Evil'),
+                COUNT(*) FILTER (WHERE winner = 'Cancelled')
+             FROM games
+             WHERE game_id = $1 AND is_active = false
+             GROUP BY guild_id
+             ON CONFLICT (guild_id) DO UPDATE SET
+                games_played = guild_aggregates.games_played + EXCLUDED.games_played,
+                seats = guild_aggregates.seats + EXCLUDED.seats,
+                good_wins = guild_aggregates.good_wins + EXCLUDED.good_wins,
+                evil_wins = guild_aggregates.evil_wins + EXCLUDED.evil_wins,
+                cancelled_games = guild_aggregates.cancelled_games + EXCLUDED.cancelled_games"
+        )
+        .bind(game_id)
+        .execute(&mut *tx)
+        .await?;
+
+        refresh_keys(&mut tx, &keys).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Update the aggregates after a completed game was corrected, given the
+    /// rows it contributed to before the correction
+    pub async fn correct_game(&self, game_id: i32, before: Option<AggregateKeys>) -> AppResult<()> {
+        let after = self.game_keys(game_id).await?;
+        let (before, after) = match (before, after) {
+            (Some(before), Some(after)) => (before, after),
+            (Some(keys), None) | (None, Some(keys)) => return self.refresh(&keys).await,
+            (None, None) => return Ok(()),
+        };
+
+        let mut tx = self.db.pool.begin().await?;
+        lock_guild(&mut tx, after.guild_id).await?;
+
+        // Corrections can only swap a decided winner, never cancel a game or
+        // change its seats
+        let wins = |keys: &AggregateKeys, side: &str| i64::from(keys.winner.as_deref() == Some(side));
+        sqlx::query(
+            "UPDATE guild_aggregates SET good_wins = good_wins + $2, evil_wins = evil_wins + $3
+             WHERE guild_id = $1"
+        )
+        .bind(after.guild_id)
+        .bind(wins(&after, "Good") - wins(&before, "Good"))
+        .bind(wins(&after, "Evil") - wins(&before, "Evil"))
+        .execute(&mut *tx)
+        .await?;
+
+        refresh_keys(&mut tx, &before.merge(after)).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Recompute the player, script and role rows a game contributes to,
+    /// such as once the game's script is identified
+    pub async fn refresh_game(&self, game_id: i32) -> AppResult<()> {
+        match self.game_keys(game_id).await? {
+            Some(keys) => self.refresh(&keys).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Recompute the given player, script and role rows from the base tables
+    async fn refresh(&self, keys: &AggregateKeys) -> AppResult<()> {
+        let mut tx = self.db.pool.begin().await?;
+        lock_guild(&mut tx, keys.guild_id).await?;
+        refresh_keys(&mut tx, keys).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Recompute every aggregate from the full game history
+    pub async fn rebuild(&self) -> AppResult<()> {
+        let mut tx = self.db.pool.begin().await?;
+
+        sqlx::query(
+            "LOCK TABLE guild_aggregates, player_aggregates, player_role_aggregates,
+                        script_aggregates, role_aggregates
+             IN EXCLUSIVE MODE"
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        refresh_guilds(&mut tx, None).await?;
+        refresh_players(&mut tx, None, None).await?;
+        refresh_scripts(&mut tx, None, None).await?;
+        refresh_roles(&mut tx, None, None).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Build the aggregates if they have never been built but games exist,
+    /// such as on the first start after they were added. Returns whether a
+    /// rebuild ran.
+    pub async fn rebuild_if_empty(&self) -> AppResult<bool> {
+        let (needs_rebuild,): (bool,) = sqlx::query_as(
+            "SELECT NOT EXISTS (SELECT 1 FROM guild_aggregates)
+                AND EXISTS (SELECT 1 FROM games WHERE is_active = false)"
+        )
+        .fetch_one(&self.db.pool)
+        .await?;
+
+        if needs_rebuild {
+            self.rebuild().await?;
+        }
+        Ok(needs_rebuild)
+    }
+
+    /// Get the statistics summary, optionally for one guild. Games count
+    /// once they have ended, cancelled or not, and seats and players count
+    /// as soon as they are recorded, so those of active games are counted
+    /// live.
+    pub async fn get_summary(&self, guild_id: Option<i64>) -> AppResult<StatsSummary> {
+        let (total_games, total_players, unique_players, active_games): (i64, i64, i64, i64) = sqlx::query_as(
+            "SELECT
+                COALESCE(SUM(games_played + cancelled_games), 0)::bigint,
+                (
+                    COALESCE(SUM(seats), 0) + (
+                        SELECT COUNT(*) FROM game_players gp
+                        JOIN games ON games.game_id = gp.game_id
+                        WHERE games.is_active = true AND ($1::bigint IS NULL OR games.guild_id = $1)
+                    )
+                )::bigint,
+                (
+                    SELECT COUNT(DISTINCT discord_id) FROM (
+                        SELECT discord_id FROM player_aggregates
+                        WHERE $1::bigint IS NULL OR guild_id = $1
+                        UNION
+                        SELECT gp.discord_id FROM game_players gp
+                        JOIN games ON games.game_id = gp.game_id
+                        WHERE games.is_active = true AND gp.discord_id IS NOT NULL
+                          AND ($1::bigint IS NULL OR games.guild_id = $1)
+                    ) players
+                ),
+                (
+                    SELECT COUNT(*) FROM games
+                    WHERE is_active = true AND ($1::bigint IS NULL OR guild_id = $1)
+                )
+             FROM guild_aggregates
+             WHERE $1::bigint IS NULL OR guild_id = $1"
+        )
+        .bind(guild_id)
+        .fetch_one(&self.db.pool)
+        .await?;
+
+        Ok(StatsSummary {
+            total_games,
+            total_players,
+            unique_players,
+            active_games,
+        })
+    }
+
+    /// Get player statistics by Discord ID, optionally within one guild.
+    /// Ratings are always global.
+    pub async fn get_player_stats(&self, discord_id: i64, guild_id: Option<i64>) -> AppResult<Option<PlayerStats>> {
+        let stats = sqlx::query_as::<_, PlayerStats>(
+            "SELECT
+                discord_id,
+                MAX(player_name) AS player_name,
+                SUM(games_played)::bigint AS games_played,
+                SUM(wins)::bigint AS wins,
+                SUM(losses)::bigint AS losses,
+                ROUND(SUM(survived) * 100.0 / SUM(games_played), 2)::float8 AS survival_rate,
+                (
+                    SELECT role_name FROM player_role_aggregates
+                    WHERE discord_id = $1 AND ($2::bigint IS NULL OR guild_id = $2)
+                    GROUP BY role_name
+                    ORDER BY SUM(games) DESC, role_name
+                    LIMIT 1
+                ) AS favorite_role,
+                (SELECT ROUND(good_rating::numeric, 1)::float8 FROM player_ratings WHERE discord_id = $1) AS good_rating,
+                (SELECT ROUND(evil_rating::numeric, 1)::float8 FROM player_ratings WHERE discord_id = $1) AS evil_rating
+             FROM player_aggregates
+             WHERE discord_id = $1 AND ($2::bigint IS NULL OR guild_id = $2)
+             GROUP BY discord_id"
+        )
+        .bind(discord_id)
+        .bind(guild_id)
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        Ok(stats)
+    }
+
+    /// Get script statistics by script id, optionally within one guild
+    pub async fn get_script_stats(&self, script_id: &str, guild_id: Option<i64>) -> AppResult<Option<ScriptStats>> {
+        let stats = sqlx::query_as::<_, ScriptStats>(
+            "SELECT
+                script_aggregates.script_id,
+                MAX(scripts.name) AS script_name,
+                SUM(games_played)::bigint AS games_played,
+                SUM(good_wins)::bigint AS good_wins,
+                SUM(evil_wins)::bigint AS evil_wins,
+                COALESCE(ROUND(SUM(player_count_sum)::numeric / NULLIF(SUM(player_count_games), 0), 2), 0)::float8
+                    AS average_player_count
+             FROM script_aggregates
+             JOIN scripts ON scripts.script_id = script_aggregates.script_id
+             WHERE script_aggregates.script_id = $1 AND ($2::bigint IS NULL OR guild_id = $2)
+             GROUP BY script_aggregates.script_id"
+        )
+        .bind(script_id)
+        .bind(guild_id)
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        Ok(stats)
+    }
+
+    /// Per-character statistics over completed games in scope, optionally
+    /// for a single role. Scopes narrower than a guild are computed live with
+    /// the same query the aggregates are built from.
+    pub async fn get_role_stats(&self, scope: &StatsScope, role_id: Option<&str>) -> AppResult<Vec<RoleStats>> {
+        let mut query = QueryBuilder::<Postgres>::new(
+            "SELECT
+                role_id,
+                MAX(role_name) AS role_name,
+                (ARRAY_AGG(team ORDER BY team_appearances DESC, team))[1] AS team,
+                SUM(team_appearances)::bigint AS appearances,
+                SUM(games_played)::bigint AS games_played,
+                SUM(starting_count)::bigint AS starting_count,
+                SUM(gained_count)::bigint AS gained_count,
+                ROUND(SUM(starting_count) * 100.0 / SUM(team_appearances), 2)::float8 AS starting_rate,
+                ROUND(SUM(final_wins) * 100.0 / NULLIF(SUM(final_decided), 0), 2)::float8 AS win_rate,
+                ROUND(SUM(final_survived) * 100.0 / NULLIF(SUM(final_survival_known), 0), 2)::float8 AS survival_rate
+             FROM (
+                SELECT
+                    role_id, team,
+                    MAX(role_name) AS role_name,
+                    SUM(appearances) AS team_appearances,
+                    SUM(games_played) AS games_played,
+                    SUM(starting_count) AS starting_count,
+                    SUM(gained_count) AS gained_count,
+                    SUM(final_wins) AS final_wins,
+                    SUM(final_decided) AS final_decided,
+                    SUM(final_survived) AS final_survived,
+                    SUM(final_survival_known) AS final_survival_known
+                FROM "
+        );
+
+        if scope.is_guild_only() {
+            query.push("role_aggregates WHERE true");
+            if let Some(guild_id) = scope.guild_id {
+                query.push(" AND guild_id = ").push_bind(guild_id);
+            }
+            if let Some(role_id) = role_id {
+                query.push(" AND role_id = ").push_bind(role_id.to_string());
+            }
+        } else {
+            query.push("(");
+            push_role_totals(&mut query, |query| {
+                push_game_filters(query, &GameFilter::from(scope));
+                if let Some(role_id) = role_id {
+                    query.push(" AND r.role_id = ").push_bind(role_id.to_string());
+                }
+            });
+            query.push(") totals");
+        }
+
+        query.push(
+            " GROUP BY role_id, team
+             ) teams
+             GROUP BY role_id
+             ORDER BY appearances DESC, role_id"
+        );
+
+        let stats = query
+            .build_query_as::<RoleStats>()
+            .fetch_all(&self.db.pool)
+            .await?;
+
+        Ok(stats)
+    }
+}
+
+/// Serialise aggregate writes for a guild. Concurrent refreshes would
+/// otherwise insert the same rows.
+async fn lock_guild(conn: &mut PgConnection, guild_id: i64) -> AppResult<()> {
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(guild_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Recompute the player, script and role rows for the given keys
+async fn refresh_keys(conn: &mut PgConnection, keys: &AggregateKeys) -> AppResult<()> {
+    let guild_id = Some(keys.guild_id);
+    refresh_players(conn, guild_id, Some(&keys.discord_ids)).await?;
+    refresh_scripts(conn, guild_id, Some(&keys.scripts)).await?;
+    refresh_roles(conn, guild_id, Some(&keys.role_ids)).await?;
+    Ok(())
+}
+
+/// Push [`ROLE_TOTALS_SQL`] over counted games, with `filter` adding
+/// `AND ...` conditions on `games`, `gp` and `r.role_id`
+fn push_role_totals<F>(query: &mut QueryBuilder<'_, Postgres>, filter: F)
+where
+    F: FnOnce(&mut QueryBuilder<'_, Postgres>),
+{
+    query.push(ROLE_TOTALS_SQL).push(" WHERE ").push(COUNTED_GAMES_SQL);
+    filter(query);
+    query.push(" GROUP BY games.guild_id, r.role_id, team");
+}
+
+/// Recompute guild totals, for one guild or all of them
+async fn refresh_guilds(conn: &mut PgConnection, guild_id: Option<i64>) -> AppResult<()> {
+    sqlx::query("DELETE FROM guild_aggregates WHERE $1::bigint IS NULL OR guild_id = $1")
+        .bind(guild_id)
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO guild_aggregates (guild_id, games_played, seats, good_wins, evil_wins, cancelled_games)
+         SELECT
+            games.guild_id,
+            COUNT(*) FILTER (WHERE games.winner IS DISTINCT FROM 'Cancelled'),
+            COALESCE(SUM(seats.count), 0),
+            COUNT(*) FILTER (WHERE games.winner = 'Good'),
+            COUNT(*) FILTER (WHERE games.winner = 'Evil'),
+            COUNT(*) FILTER (WHERE games.winner = 'Cancelled')
+         FROM games
+         CROSS JOIN LATERAL (SELECT COUNT(*) AS count FROM game_players WHERE game_id = games.game_id) seats
+         WHERE games.is_active = false AND ($1::bigint IS NULL OR games.guild_id = $1)
+         GROUP BY games.guild_id"
+    )
+    .bind(guild_id)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Recompute player totals and favourite roles, optionally limited to a guild
+/// and a set of players
+async fn refresh_players(conn: &mut PgConnection, guild_id: Option<i64>, discord_ids: Option<&[i64]>) -> AppResult<()> {
+    for table in ["player_aggregates", "player_role_aggregates"] {
+        sqlx::query(&format!(
+            "DELETE FROM {}
+             WHERE ($1::bigint IS NULL OR guild_id = $1) AND ($2::bigint[] IS NULL OR discord_id = ANY($2))",
+            table
+        ))
+        .bind(guild_id)
+        .bind(discord_ids)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    sqlx::query(&format!(
+        "INSERT INTO player_aggregates (guild_id, discord_id, player_name, games_played, wins, losses, survived)
+         SELECT
+            games.guild_id,
+            gp.discord_id,
+            MAX(gp.player_name),
+            COUNT(*),
+            COUNT(*) FILTER (WHERE gp.winning_team),
+            COUNT(*) FILTER (WHERE NOT gp.winning_team),
+            COUNT(*) FILTER (WHERE gp.survived)
+         FROM game_players gp
+         JOIN games ON games.game_id = gp.game_id
+         WHERE {}
+           AND gp.discord_id IS NOT NULL
+           AND ($1::bigint IS NULL OR games.guild_id = $1)
+           AND ($2::bigint[] IS NULL OR gp.discord_id = ANY($2))
+         GROUP BY games.guild_id, gp.discord_id",
+        COUNTED_GAMES_SQL
+    ))
+    .bind(guild_id)
+    .bind(discord_ids)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(&format!(
+        "INSERT INTO player_role_aggregates (guild_id, discord_id, role_name, games)
+         SELECT games.guild_id, gp.discord_id, gp.final_role_name, COUNT(*)
+         FROM game_players gp
+         JOIN games ON games.game_id = gp.game_id
+         WHERE {}
+           AND gp.discord_id IS NOT NULL
+           AND gp.final_role_name IS NOT NULL
+           AND ($1::bigint IS NULL OR games.guild_id = $1)
+           AND ($2::bigint[] IS NULL OR gp.discord_id = ANY($2))
+         GROUP BY games.guild_id, gp.discord_id, gp.final_role_name",
+        COUNTED_GAMES_SQL
+    ))
+    .bind(guild_id)
+    .bind(discord_ids)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Recompute script totals over decided games, optionally limited to a guild
+/// and a set of script ids
+async fn refresh_scripts(conn: &mut PgConnection, guild_id: Option<i64>, scripts: Option<&[String]>) -> AppResult<()> {
+    sqlx::query(
+        "DELETE FROM script_aggregates
+         WHERE ($1::bigint IS NULL OR guild_id = $1) AND ($2::text[] IS NULL OR script_id = ANY($2))"
+    )
+    .bind(guild_id)
+    .bind(scripts)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO script_aggregates
+            (guild_id, script_id, games_played, good_wins, evil_wins, player_count_sum, player_count_games)
+         SELECT
+            guild_id,
+            script_id,
+            COUNT(*),
+            COUNT(*) FILTER (WHERE winner = 'Good'),
+            COUNT(*) FILTER (WHERE winner = 'Evil'),
+            COALESCE(SUM(player_count), 0),
+            COUNT(player_count)
+         FROM games
+         WHERE is_active = false
+           AND winner IN ('Good', 'Evil')
+           AND script_id IS NOT NULL
+           AND ($1::bigint IS NULL OR guild_id = $1)
+           AND ($2::text[] IS NULL OR script_id = ANY($2))
+         GROUP BY guild_id, script_id"
+    )
+    .bind(guild_id)
+    .bind(scripts)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Recompute character totals, optionally limited to a guild and a set of
+/// roles
+async fn refresh_roles(conn: &mut PgConnection, guild_id: Option<i64>, role_ids: Option<&[String]>) -> AppResult<()> {
+    sqlx::query(
+        "DELETE FROM role_aggregates
+         WHERE ($1::bigint IS NULL OR guild_id = $1) AND ($2::text[] IS NULL OR role_id = ANY($2))"
+    )
+    .bind(guild_id)
+    .bind(role_ids)
+    .execute(&mut *conn)
+    .await?;
+
+    let mut query = QueryBuilder::<Postgres>::new(
+        "INSERT INTO role_aggregates
+            (guild_id, role_id, team, role_name, appearances, games_played, starting_count, gained_count,
+             final_wins, final_decided, final_survived, final_survival_known) "
+    );
+    push_role_totals(&mut query, |query| {
+        if let Some(guild_id) = guild_id {
+            query.push(" AND games.guild_id = ").push_bind(guild_id);
+        }
+        if let Some(role_ids) = role_ids {
+            query.push(" AND r.role_id = ANY(").push_bind(role_ids.to_vec()).push(")");
+        }
+    });
+    query.build().execute(&mut *conn).await?;
+
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use sha2::{Digest, Sha256};
+
+use crate::{
+    database::Database,
+    error::AppResult,
+    models::{ApiKeyCreate, ApiKeyResponse, ApiKeyUpdate},
+};
+
+/// Columns of [`ApiKeyResponse`], with the defaults the API key middleware
+/// applies
+const API_KEY_COLUMNS: &str = "id, name, COALESCE(rate_limit, 100) AS rate_limit, COALESCE(is_active, true) AS is_active,
+     COALESCE(created_at, CURRENT_TIMESTAMP) AS created_at, guild_ids";
+
+/// Hash an API key the way it is stored
+pub fn hash_api_key(key: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(key.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+#[derive(Clone)]
+pub struct ApiKeyService {
+    db: Database,
+}
+
+impl ApiKeyService {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+
+    /// Create an API key owned by a Discord user. The key itself is only
+    /// returned here; just its hash is stored.
+    pub async fn create_key(&self, discord_user_id: i64, key: &ApiKeyCreate) -> AppResult<ApiKeyResponse> {
+        let plain = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+
+        let mut created = sqlx::query_as::<_, ApiKeyResponse>(&format!(
+            "INSERT INTO api_keys (key_hash, name, discord_user_id, rate_limit, notes, guild_ids, is_active, created_at)
+             VALUES ($1, $2, $3, $4, $5, $6, true, CURRENT_TIMESTAMP)
+             RETURNING {}",
+            API_KEY_COLUMNS
+        ))
+        .bind(hash_api_key(&plain))
+        .bind(&key.name)
+        .bind(discord_user_id.to_string())
+        .bind(key.rate_limit)
+        .bind(&key.notes)
+        .bind(&key.guild_ids)
+        .fetch_one(&self.db.pool)
+        .await?;
+
+        created.key = Some(plain);
+        Ok(created)
+    }
+
+    /// Update a Discord user's API key. Omitted fields are left as they are,
+    /// and an empty `guild_ids` lifts the key's guild restriction. Returns
+    /// `None` if the user has no such key.
+    pub async fn update_key(&self, discord_user_id: i64, key_id: i32, update: &ApiKeyUpdate) -> AppResult<Option<ApiKeyResponse>> {
+        let guild_ids = update.guild_ids.as_ref().map(|ids| (!ids.is_empty()).then_some(ids));
+
+        let updated = sqlx::query_as::<_, ApiKeyResponse>(&format!(
+            "UPDATE api_keys SET
+                name = COALESCE($3, name),
+                rate_limit = COALESCE($4, rate_limit),
+                notes = COALESCE($5, notes),
+                guild_ids = CASE WHEN $6 THEN $7 ELSE guild_ids END
+             WHERE id = $1 AND discord_user_id = $2
+             RETURNING {}",
+            API_KEY_COLUMNS
+        ))
+        .bind(key_id)
+        .bind(discord_user_id.to_string())
+        .bind(&update.name)
+        .bind(update.rate_limit)
+        .bind(&update.notes)
+        .bind(guild_ids.is_some())
+        .bind(guild_ids.flatten())
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        Ok(updated)
+    }
+}
@@ -0,0 +1,116 @@
+use axum::{body::Bytes, http::HeaderMap};
+use chrono::NaiveDateTime;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::{database::Database, error::AppResult};
+
+/// Responses kept before new ones stop being cached until the next write
+const MAX_ENTRIES: usize = 1000;
+
+/// Larger responses are still given an ETag but not kept in memory
+const MAX_ENTRY_BYTES: usize = 256 * 1024;
+
+/// Response bytes kept in total before new ones stop being cached until the
+/// next write, so varying query strings can't fill memory with large bodies
+const MAX_TOTAL_BYTES: usize = 32 * 1024 * 1024;
+
+/// A successful read response, stored with its validators
+#[derive(Clone)]
+pub struct CachedResponse {
+    pub headers: HeaderMap,
+    pub body: Bytes,
+    pub etag: String,
+}
+
+#[derive(Default)]
+struct CacheState {
+    /// Bumped by every game write, so responses built from older data are
+    /// never stored
+    generation: u64,
+    /// Latest game completion time, loaded once per generation. The inner
+    /// `None` means no game has completed yet.
+    last_modified: Option<Option<NaiveDateTime>>,
+    entries: HashMap<String, CachedResponse>,
+    /// Body bytes held in `entries`
+    bytes: usize,
+}
+
+/// In-process cache of read API responses, cleared whenever a game is written
+#[derive(Clone)]
+pub struct ResponseCache {
+    db: Database,
+    state: Arc<RwLock<CacheState>>,
+}
+
+impl ResponseCache {
+    pub fn new(db: Database) -> Self {
+        Self {
+            db,
+            state: Arc::new(RwLock::new(CacheState::default())),
+        }
+    }
+
+    /// Current generation, to be passed back to `insert` once the response
+    /// has been built
+    pub async fn generation(&self) -> u64 {
+        self.state.read().await.generation
+    }
+
+    pub async fn get(&self, key: &str) -> Option<CachedResponse> {
+        self.state.read().await.entries.get(key).cloned()
+    }
+
+    /// Store a response unless a write happened while it was being built
+    pub async fn insert(&self, key: String, generation: u64, response: CachedResponse) {
+        if response.body.len() > MAX_ENTRY_BYTES {
+            return;
+        }
+
+        let mut state = self.state.write().await;
+        if state.generation != generation
+            || state.entries.len() >= MAX_ENTRIES
+            || state.bytes + response.body.len() > MAX_TOTAL_BYTES
+        {
+            return;
+        }
+        state.bytes += response.body.len();
+        if let Some(replaced) = state.entries.insert(key, response) {
+            state.bytes -= replaced.body.len();
+        }
+    }
+
+    /// Latest time a game was completed, used as the Last-Modified of every
+    /// read response
+    pub async fn last_modified(&self) -> AppResult<Option<NaiveDateTime>> {
+        let generation = {
+            let state = self.state.read().await;
+            if let Some(last_modified) = state.last_modified {
+                return Ok(last_modified);
+            }
+            state.generation
+        };
+
+        let (last_modified,): (Option<NaiveDateTime>,) = sqlx::query_as(
+            "SELECT MAX(completed_at) FROM games WHERE is_active = false"
+        )
+        .fetch_one(&self.db.pool)
+        .await?;
+
+        let mut state = self.state.write().await;
+        if state.generation == generation {
+            state.last_modified = Some(last_modified);
+        }
+        Ok(last_modified)
+    }
+
+    /// Drop every cached response after a game write
+    pub async fn invalidate(&self) {
+        let mut state = self.state.write().await;
+        state.generation += 1;
+        state.last_modified = None;
+        state.entries.clear();
+        state.bytes = 0;
+    }
+}
@@ -0,0 +1,191 @@
+use chrono::Utc;
+use futures::{channel::mpsc, SinkExt};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex};
+
+use crate::{
+    database::Database,
+    error::{AppError, AppResult},
+    models::{LiveEvent, StreamEvent},
+    services::game::GameService,
+};
+
+/// Live events buffered for slow subscribers before they fall back to the log
+const BROADCAST_CAPACITY: usize = 1024;
+
+/// Events buffered between a subscriber's task and its connection
+const SUBSCRIBER_BUFFER: usize = 64;
+
+/// Events read from the log per query while catching up
+const CATCH_UP_BATCH: i64 = 500;
+
+/// Events are kept in the log, and can be resumed from, this long
+const EVENT_RETENTION_DAYS: i32 = 7;
+
+/// Publishes game and stats events to the persisted event log and to live
+/// `/api/v1/events` subscribers
+#[derive(Clone)]
+pub struct EventService {
+    db: Database,
+    game: Arc<GameService>,
+    sender: broadcast::Sender<LiveEvent>,
+    /// Held while an event is logged and broadcast, so subscribers receive
+    /// events in log order
+    publish_lock: Arc<Mutex<()>>,
+}
+
+impl EventService {
+    pub fn new(db: Database) -> Self {
+        let (sender, _) = broadcast::channel(BROADCAST_CAPACITY);
+        let service = Self {
+            game: Arc::new(GameService::new(db.clone())),
+            db,
+            sender,
+            publish_lock: Arc::new(Mutex::new(())),
+        };
+
+        // Spawn cleanup task
+        let pool = service.db.pool.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(3600)); // 1 hour
+            loop {
+                interval.tick().await;
+                let result = sqlx::query(
+                    "DELETE FROM live_events WHERE created_at < CURRENT_TIMESTAMP - make_interval(days => $1)"
+                )
+                .bind(EVENT_RETENTION_DAYS)
+                .execute(&pool)
+                .await;
+
+                if let Err(e) = result {
+                    tracing::warn!("Failed to clean up live events: {}", e);
+                }
+            }
+        });
+
+        service
+    }
+
+    /// Log an event for a guild and send it to live subscribers. `data` should
+    /// be a JSON object; the guild and time are added to it.
+    pub async fn publish(&self, event: StreamEvent, guild_id: i64, mut data: Value) -> AppResult<()> {
+        if let Some(object) = data.as_object_mut() {
+            object.insert("guild_id".to_string(), json!(guild_id));
+            object.insert("occurred_at".to_string(), json!(Utc::now().naive_utc()));
+        }
+
+        let _guard = self.publish_lock.lock().await;
+        let logged = sqlx::query_as::<_, LiveEvent>(
+            "INSERT INTO live_events (event, guild_id, data)
+             VALUES ($1, $2, $3)
+             RETURNING id, event, guild_id, data"
+        )
+        .bind(event.as_str())
+        .bind(guild_id)
+        .bind(data.to_string())
+        .fetch_one(&self.db.pool)
+        .await?;
+
+        // Nobody listening isn't an error
+        let _ = self.sender.send(logged);
+        Ok(())
+    }
+
+    /// Publish an event about a game, describing it from its current row
+    pub async fn publish_game(&self, event: StreamEvent, game_id: i32) -> AppResult<()> {
+        let game = self.game.get_game(game_id).await?
+            .ok_or_else(|| AppError::NotFound("Game not found".to_string()))?;
+
+        let data = json!({
+            "game_id": game.game_id,
+            "script": game.script,
+            "custom_name": game.custom_name,
+            "player_count": game.player_count,
+            "winner": game.winner,
+        });
+        self.publish(event, game.guild_id, data).await
+    }
+
+    /// Follow the event stream, optionally for one guild. Events after
+    /// `last_event_id` are replayed from the log first; without it only new
+    /// events are sent. The stream ends if the log can't be read.
+    pub async fn subscribe(&self, guild_id: Option<i64>, last_event_id: Option<i64>) -> AppResult<mpsc::Receiver<LiveEvent>> {
+        // Subscribe before reading the log so nothing published in between is lost
+        let mut live = self.sender.subscribe();
+        let mut last_id = match last_event_id {
+            Some(id) => id,
+            None => {
+                let (latest,): (i64,) = sqlx::query_as("SELECT COALESCE(MAX(id), 0) FROM live_events")
+                    .fetch_one(&self.db.pool)
+                    .await?;
+                latest
+            }
+        };
+
+        let (mut sender, receiver) = mpsc::channel(SUBSCRIBER_BUFFER);
+        let pool = self.db.pool.clone();
+        tokio::spawn(async move {
+            loop {
+                // Catch up from the log, then follow live events until this
+                // subscriber falls too far behind
+                loop {
+                    let events = match events_after(&pool, guild_id, last_id).await {
+                        Ok(events) => events,
+                        Err(e) => {
+                            tracing::warn!("Failed to read live events: {}", e);
+                            return;
+                        }
+                    };
+                    let caught_up = (events.len() as i64) < CATCH_UP_BATCH;
+                    for event in events {
+                        last_id = event.id;
+                        if sender.send(event).await.is_err() {
+                            return;
+                        }
+                    }
+                    if caught_up {
+                        break;
+                    }
+                }
+
+                loop {
+                    match live.recv().await {
+                        Ok(event) => {
+                            if event.id <= last_id || guild_id.is_some_and(|id| id != event.guild_id) {
+                                continue;
+                            }
+                            last_id = event.id;
+                            // Stop once the client has gone away
+                            if sender.send(event).await.is_err() {
+                                return;
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(_)) => break,
+                        Err(broadcast::error::RecvError::Closed) => return,
+                    }
+                }
+            }
+        });
+
+        Ok(receiver)
+    }
+}
+
+async fn events_after(pool: &sqlx::PgPool, guild_id: Option<i64>, after: i64) -> AppResult<Vec<LiveEvent>> {
+    let events = sqlx::query_as::<_, LiveEvent>(
+        "SELECT id, event, guild_id, data
+         FROM live_events
+         WHERE id > $1 AND ($2::bigint IS NULL OR guild_id = $2)
+         ORDER BY id
+         LIMIT $3"
+    )
+    .bind(after)
+    .bind(guild_id)
+    .bind(CATCH_UP_BATCH)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(events)
+}
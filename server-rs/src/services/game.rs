@@ -1,6 +1,25 @@
-use crate::{database::Database, error::AppResult, models::{Game, GamePlayer, PlayerStats, ScriptStats}};
-use sqlx::Row;
+use crate::{
+    database::Database,
+    error::{AppError, AppResult},
+    models::{
+        Game, GameCorrection, GameCorrectionEntry, GameDetail, GameFilter, GamePlayer, GameSort,
+        NewGame, NewGamePlayer, PlayerGame, PlayerGameFilter, PlayerRoleUpdate,
+    },
+    services::scripts::push_script_filter,
+    utils::pagination::Cursor,
+};
+use chrono::{DateTime, Duration, NaiveTime};
+use sqlx::{PgConnection, Postgres, QueryBuilder, Row};
 
+/// A field changed by a game correction, before it's written to the audit trail
+struct FieldChange {
+    seat_number: Option<i32>,
+    field: &'static str,
+    old_value: Option<String>,
+    new_value: Option<String>,
+}
+
+#[derive(Clone)]
 pub struct GameService {
     db: Database,
 }
@@ -12,7 +31,7 @@ impl GameService {
 
     pub async fn get_game(&self, game_id: i32) -> AppResult<Option<Game>> {
         let game = sqlx::query_as::<_, Game>(
-            "SELECT game_id, guild_id, script, custom_name, start_time, end_time, winner, 
+            "SELECT game_id, guild_id, script, script_id, custom_name, start_time, end_time, winner, 
                     player_count, players, is_active, created_at, completed_at, 
                     storyteller_id, category_id, storyteller_user_id 
              FROM games WHERE game_id = $1"
@@ -24,25 +43,62 @@ impl GameService {
         Ok(game)
     }
 
-    pub async fn get_games(&self, limit: i64, offset: i64) -> AppResult<Vec<Game>> {
-        let games = sqlx::query_as::<_, Game>(
-            "SELECT game_id, guild_id, script, custom_name, start_time, end_time, winner, 
+    /// Get the active game a storyteller is currently running, if any
+    pub async fn get_active_game_for_storyteller(&self, discord_user_id: i64) -> AppResult<Option<Game>> {
+        let game = sqlx::query_as::<_, Game>(
+            "SELECT game_id, guild_id, script, script_id, custom_name, start_time, end_time, winner, 
                     player_count, players, is_active, created_at, completed_at, 
                     storyteller_id, category_id, storyteller_user_id 
              FROM games 
-             WHERE is_active = false 
-             ORDER BY completed_at DESC NULLS LAST, created_at DESC 
-             LIMIT $1 OFFSET $2"
+             WHERE is_active = true AND storyteller_user_id = $1 
+             ORDER BY start_time DESC 
+             LIMIT 1"
+        )
+        .bind(discord_user_id)
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        Ok(game)
+    }
+
+    /// Active games the server is recording from live sessions, with the
+    /// code of the session each is recorded from
+    pub async fn get_recorded_active_games(&self) -> AppResult<Vec<(i32, String)>> {
+        let games = sqlx::query_as::<_, (i32, String)>(
+            "SELECT game_id, recorded_session_code FROM games 
+             WHERE is_active = true AND recorded_session_code IS NOT NULL"
         )
-        .bind(limit)
-        .bind(offset)
         .fetch_all(&self.db.pool)
         .await?;
 
         Ok(games)
     }
 
-    #[allow(dead_code)]
+    /// List completed games. With a cursor, returns the games after it in
+    /// completion order; cursors are only valid for the newest/oldest sorts.
+    pub async fn get_games(
+        &self,
+        filter: &GameFilter,
+        limit: i64,
+        offset: i64,
+        cursor: Option<Cursor>,
+    ) -> AppResult<Vec<Game>> {
+        let mut query = games_query(filter);
+        if let Some(cursor) = cursor {
+            push_completed_cursor(&mut query, cursor, filter.sort == GameSort::Oldest)?;
+        }
+        query.push(game_sort_clause(filter.sort));
+        query.push(" LIMIT ").push_bind(limit);
+        query.push(" OFFSET ").push_bind(offset);
+
+        let games = query
+            .build_query_as::<Game>()
+            .fetch_all(&self.db.pool)
+            .await?;
+
+        Ok(games)
+    }
+
     pub async fn get_game_players(&self, game_id: i32) -> AppResult<Vec<GamePlayer>> {
         let players = sqlx::query_as::<_, GamePlayer>(
             "SELECT id, game_id, discord_id, player_name, seat_number, 
@@ -60,88 +116,595 @@ impl GameService {
         Ok(players)
     }
 
-    pub async fn count_total_games(&self) -> AppResult<i64> {
-        let row = sqlx::query("SELECT COUNT(*) FROM games WHERE is_active = false")
-            .fetch_one(&self.db.pool)
-            .await?;
+    /// Get a game together with its ordered seats
+    pub async fn get_game_detail(&self, game_id: i32) -> AppResult<Option<GameDetail>> {
+        let Some(game) = self.get_game(game_id).await? else {
+            return Ok(None);
+        };
 
-        Ok(row.get(0))
+        let seats = self.get_game_players(game_id).await?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        let duration_seconds = game.end_time.map(|end| end - game.start_time);
+
+        Ok(Some(GameDetail {
+            game,
+            duration_seconds,
+            seats,
+        }))
     }
 
-    pub async fn count_total_players(&self) -> AppResult<i64> {
-        let row = sqlx::query("SELECT COUNT(*) FROM game_players")
-            .fetch_one(&self.db.pool)
+    /// Create an active game and mark it as the guild session's active game.
+    /// Fails with a conflict if the session already has one.
+    pub async fn create_game(&self, game: &NewGame) -> AppResult<i32> {
+        let mut tx = self.db.pool.begin().await?;
+
+        let row = sqlx::query(
+            "INSERT INTO games (guild_id, category_id, script, script_id, custom_name, start_time, 
+                                player_count, players, is_active, created_at, storyteller_user_id, 
+                                recorded_session_code) 
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, true, CURRENT_TIMESTAMP, $9, $10) 
+             RETURNING game_id"
+        )
+        .bind(game.guild_id)
+        .bind(game.category_id)
+        .bind(&game.script)
+        .bind(&game.script_id)
+        .bind(&game.custom_name)
+        .bind(game.start_time)
+        .bind(game.players.len() as i32)
+        .bind(serde_json::json!(game.players))
+        .bind(game.storyteller_user_id)
+        .bind(&game.recorded_session_code)
+        .fetch_one(&mut *tx)
+        .await?;
+        let game_id: i32 = row.get(0);
+
+        if let Some(category_id) = game.category_id {
+            let result = sqlx::query(
+                "UPDATE sessions SET active_game_id = $1 
+                 WHERE guild_id = $2 AND category_id = $3 AND active_game_id IS NULL"
+            )
+            .bind(game_id)
+            .bind(game.guild_id)
+            .bind(category_id)
+            .execute(&mut *tx)
             .await?;
 
-        Ok(row.get(0))
+            if result.rows_affected() == 0 {
+                return Err(AppError::Conflict("Session already has an active game".to_string()));
+            }
+        }
+
+        tx.commit().await?;
+        Ok(game_id)
     }
 
-    pub async fn count_unique_players(&self) -> AppResult<i64> {
-        let row = sqlx::query("SELECT COUNT(DISTINCT discord_id) FROM game_players WHERE discord_id IS NOT NULL")
-            .fetch_one(&self.db.pool)
+    /// Complete an active game, recording any remaining seats and the winner.
+    /// Each seat's result is derived from the team it finished on. Fails with
+    /// a conflict if the game has already ended.
+    pub async fn complete_game(
+        &self,
+        game_id: i32,
+        end_time: f64,
+        winner: Option<&str>,
+        players: &[NewGamePlayer],
+    ) -> AppResult<()> {
+        let mut tx = self.db.pool.begin().await?;
+
+        for player in players {
+            sqlx::query(
+                "INSERT INTO game_players (game_id, discord_id, player_name, seat_number, 
+                                           starting_role_id, starting_role_name, starting_team, 
+                                           final_role_id, final_role_name, final_team, survived) 
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)"
+            )
+            .bind(game_id)
+            .bind(player.discord_id)
+            .bind(&player.player_name)
+            .bind(player.seat_number)
+            .bind(&player.starting_role_id)
+            .bind(&player.starting_role_name)
+            .bind(&player.starting_team)
+            .bind(&player.final_role_id)
+            .bind(&player.final_role_name)
+            .bind(&player.final_team)
+            .bind(player.survived)
+            .execute(&mut *tx)
             .await?;
+        }
 
-        Ok(row.get(0))
+        let result = sqlx::query(
+            "UPDATE games 
+             SET end_time = $2, winner = $3, is_active = false, completed_at = CURRENT_TIMESTAMP, 
+                 player_count = (SELECT COUNT(*) FROM game_players WHERE game_id = $1) 
+             WHERE game_id = $1 AND is_active = true"
+        )
+        .bind(game_id)
+        .bind(end_time)
+        .bind(winner)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::Conflict("Game has already ended".to_string()));
+        }
+
+        update_winning_teams(&mut tx, game_id, winner).await?;
+
+        sqlx::query("UPDATE sessions SET active_game_id = NULL WHERE active_game_id = $1")
+            .bind(game_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
     }
 
-    pub async fn count_active_games(&self) -> AppResult<i64> {
-        let row = sqlx::query("SELECT COUNT(*) FROM games WHERE is_active = true")
-            .fetch_one(&self.db.pool)
+    /// Cancel an active game. Its seats are discarded so the game never
+    /// counts towards player stats. Fails with a conflict if the game has
+    /// already ended.
+    pub async fn cancel_game(&self, game_id: i32, end_time: f64) -> AppResult<()> {
+        let mut tx = self.db.pool.begin().await?;
+
+        let result = sqlx::query(
+            "UPDATE games 
+             SET end_time = $2, winner = 'Cancelled', is_active = false, completed_at = CURRENT_TIMESTAMP 
+             WHERE game_id = $1 AND is_active = true"
+        )
+        .bind(game_id)
+        .bind(end_time)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::Conflict("Game has already ended".to_string()));
+        }
+
+        sqlx::query("DELETE FROM game_players WHERE game_id = $1")
+            .bind(game_id)
+            .execute(&mut *tx)
             .await?;
 
-        Ok(row.get(0))
+        sqlx::query("UPDATE sessions SET active_game_id = NULL WHERE active_game_id = $1")
+            .bind(game_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
     }
 
-    /// Get player statistics by Discord ID
-    pub async fn get_player_stats(&self, discord_id: i64) -> AppResult<Option<PlayerStats>> {
-        let stats = sqlx::query_as::<_, PlayerStats>(
-            r#"
-            SELECT 
-                discord_id,
-                MAX(player_name) as player_name,
-                COUNT(*) as games_played,
-                SUM(CASE WHEN winning_team THEN 1 ELSE 0 END) as wins,
-                SUM(CASE WHEN NOT winning_team THEN 1 ELSE 0 END) as losses,
-                ROUND(AVG(CASE WHEN survived THEN 1.0 ELSE 0.0 END) * 100, 2) as survival_rate,
-                (
-                    SELECT final_role_name 
-                    FROM game_players 
-                    WHERE discord_id = $1 AND final_role_name IS NOT NULL
-                    GROUP BY final_role_name 
-                    ORDER BY COUNT(*) DESC 
-                    LIMIT 1
-                ) as favorite_role
-            FROM game_players
-            WHERE discord_id = $1
-            GROUP BY discord_id
-            "#
+    /// Record a seat's starting or final role in an active game, creating the
+    /// seat if it doesn't exist yet. Returns the seat's row id.
+    pub async fn record_player_role(&self, game_id: i32, update: &PlayerRoleUpdate) -> AppResult<i32> {
+        let existing = sqlx::query(
+            "SELECT id FROM game_players 
+             WHERE game_id = $1 AND (seat_number = $2 OR ($2 IS NULL AND player_name = $3)) 
+             LIMIT 1"
         )
-        .bind(discord_id)
+        .bind(game_id)
+        .bind(update.seat_number)
+        .bind(&update.player_name)
         .fetch_optional(&self.db.pool)
         .await?;
 
-        Ok(stats)
-    }
-
-    /// Get script statistics by script name
-    pub async fn get_script_stats(&self, script_name: &str) -> AppResult<Option<ScriptStats>> {
-        let stats = sqlx::query_as::<_, ScriptStats>(
-            r#"
-            SELECT 
-                script as script_name,
-                COUNT(*) as games_played,
-                SUM(CASE WHEN winner = 'Good' THEN 1 ELSE 0 END) as good_wins,
-                SUM(CASE WHEN winner = 'Evil' THEN 1 ELSE 0 END) as evil_wins,
-                ROUND(AVG(player_count::numeric), 2) as average_player_count
-            FROM games
-            WHERE script = $1 AND is_active = false AND winner IS NOT NULL
-            GROUP BY script
-            "#
+        if let Some(row) = existing {
+            let id: i32 = row.get(0);
+            // A starting role also fills in the final role until one is recorded
+            let query = if update.is_final {
+                "UPDATE game_players 
+                 SET final_role_id = $2, final_role_name = $3, final_team = $4, 
+                     discord_id = COALESCE($5, discord_id), player_name = $6 
+                 WHERE id = $1"
+            } else {
+                "UPDATE game_players 
+                 SET starting_role_id = $2, starting_role_name = $3, starting_team = $4, 
+                     final_role_id = COALESCE(final_role_id, $2), 
+                     final_role_name = COALESCE(final_role_name, $3), 
+                     final_team = COALESCE(final_team, $4), 
+                     discord_id = COALESCE($5, discord_id), player_name = $6 
+                 WHERE id = $1"
+            };
+
+            sqlx::query(query)
+                .bind(id)
+                .bind(&update.role_id)
+                .bind(&update.role_name)
+                .bind(&update.team)
+                .bind(update.discord_id)
+                .bind(&update.player_name)
+                .execute(&self.db.pool)
+                .await?;
+
+            return Ok(id);
+        }
+
+        let seat_number = match update.seat_number {
+            Some(seat_number) => seat_number,
+            None => {
+                let row = sqlx::query("SELECT COALESCE(MAX(seat_number), 0) + 1 FROM game_players WHERE game_id = $1")
+                    .bind(game_id)
+                    .fetch_one(&self.db.pool)
+                    .await?;
+                row.get(0)
+            }
+        };
+        let starting = (!update.is_final).then_some(update);
+
+        let row = sqlx::query(
+            "INSERT INTO game_players (game_id, discord_id, player_name, seat_number, 
+                                       starting_role_id, starting_role_name, starting_team, 
+                                       final_role_id, final_role_name, final_team) 
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) 
+             RETURNING id"
         )
-        .bind(script_name)
-        .fetch_optional(&self.db.pool)
+        .bind(game_id)
+        .bind(update.discord_id)
+        .bind(&update.player_name)
+        .bind(seat_number)
+        .bind(starting.and_then(|u| u.role_id.as_ref()))
+        .bind(starting.and_then(|u| u.role_name.as_ref()))
+        .bind(starting.and_then(|u| u.team.as_ref()))
+        .bind(&update.role_id)
+        .bind(&update.role_name)
+        .bind(&update.team)
+        .fetch_one(&self.db.pool)
         .await?;
 
-        Ok(stats)
+        Ok(row.get(0))
     }
+
+    /// Apply storyteller corrections to a completed game, recording every
+    /// changed field in the audit trail
+    pub async fn correct_game(
+        &self,
+        game_id: i32,
+        correction: &GameCorrection,
+        corrected_by: i64,
+    ) -> AppResult<Vec<GameCorrectionEntry>> {
+        let mut tx = self.db.pool.begin().await?;
+
+        let game = sqlx::query_as::<_, Game>(
+            "SELECT game_id, guild_id, script, script_id, custom_name, start_time, end_time, winner, 
+                    player_count, players, is_active, created_at, completed_at, 
+                    storyteller_id, category_id, storyteller_user_id 
+             FROM games WHERE game_id = $1 FOR UPDATE"
+        )
+        .bind(game_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Game not found".to_string()))?;
+
+        let mut changes: Vec<FieldChange> = Vec::new();
+
+        let winner = match correction.winner {
+            Some(ref winner) if game.winner.as_ref() != Some(winner) => {
+                changes.push(FieldChange {
+                    seat_number: None,
+                    field: "winner",
+                    old_value: game.winner.clone(),
+                    new_value: Some(winner.clone()),
+                });
+                Some(winner.as_str())
+            }
+            _ => game.winner.as_deref(),
+        };
+
+        for seat in &correction.seats {
+            let mut player = sqlx::query_as::<_, GamePlayer>(
+                "SELECT id, game_id, discord_id, player_name, seat_number, 
+                        final_role_id, final_role_name, final_team, 
+                        survived, winning_team, created_at,
+                        starting_role_id, starting_role_name, starting_team 
+                 FROM game_players 
+                 WHERE game_id = $1 AND seat_number = $2 FOR UPDATE"
+            )
+            .bind(game_id)
+            .bind(seat.seat_number)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Seat {} not found", seat.seat_number)))?;
+
+            let changes_before = changes.len();
+            let seat_number = Some(seat.seat_number);
+            let mut apply = |field: &'static str, current: &mut Option<String>, new: &Option<String>| {
+                if new.is_some() && current != new {
+                    changes.push(FieldChange {
+                        seat_number,
+                        field,
+                        old_value: current.clone(),
+                        new_value: new.clone(),
+                    });
+                    *current = new.clone();
+                }
+            };
+            apply("starting_role_id", &mut player.starting_role_id, &seat.starting_role_id);
+            apply("starting_role_name", &mut player.starting_role_name, &seat.starting_role_name);
+            apply("starting_team", &mut player.starting_team, &seat.starting_team);
+            apply("final_role_id", &mut player.final_role_id, &seat.final_role_id);
+            apply("final_role_name", &mut player.final_role_name, &seat.final_role_name);
+            apply("final_team", &mut player.final_team, &seat.final_team);
+
+            if seat.survived.is_some() && seat.survived != player.survived {
+                changes.push(FieldChange {
+                    seat_number,
+                    field: "survived",
+                    old_value: player.survived.map(|v| v.to_string()),
+                    new_value: seat.survived.map(|v| v.to_string()),
+                });
+                player.survived = seat.survived;
+            }
+
+            if changes.len() == changes_before {
+                continue;
+            }
+
+            sqlx::query(
+                "UPDATE game_players 
+                 SET starting_role_id = $2, starting_role_name = $3, starting_team = $4, 
+                     final_role_id = $5, final_role_name = $6, final_team = $7, survived = $8 
+                 WHERE id = $1"
+            )
+            .bind(player.id)
+            .bind(&player.starting_role_id)
+            .bind(&player.starting_role_name)
+            .bind(&player.starting_team)
+            .bind(&player.final_role_id)
+            .bind(&player.final_role_name)
+            .bind(&player.final_team)
+            .bind(player.survived)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        if changes.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        sqlx::query("UPDATE games SET winner = $2 WHERE game_id = $1")
+            .bind(game_id)
+            .bind(winner)
+            .execute(&mut *tx)
+            .await?;
+        update_winning_teams(&mut tx, game_id, winner).await?;
+
+        let mut entries = Vec::with_capacity(changes.len());
+        for change in changes {
+            let entry = sqlx::query_as::<_, GameCorrectionEntry>(
+                "INSERT INTO game_corrections (game_id, seat_number, field, old_value, new_value, corrected_by, reason) 
+                 VALUES ($1, $2, $3, $4, $5, $6, $7) 
+                 RETURNING id, game_id, seat_number, field, old_value, new_value, corrected_by, reason, created_at"
+            )
+            .bind(game_id)
+            .bind(change.seat_number)
+            .bind(change.field)
+            .bind(change.old_value)
+            .bind(change.new_value)
+            .bind(corrected_by)
+            .bind(&correction.reason)
+            .fetch_one(&mut *tx)
+            .await?;
+            entries.push(entry);
+        }
+
+        tx.commit().await?;
+        Ok(entries)
+    }
+
+    /// Get the correction history of a game, oldest first
+    pub async fn get_game_corrections(&self, game_id: i32) -> AppResult<Vec<GameCorrectionEntry>> {
+        let entries = sqlx::query_as::<_, GameCorrectionEntry>(
+            "SELECT id, game_id, seat_number, field, old_value, new_value, corrected_by, reason, created_at 
+             FROM game_corrections 
+             WHERE game_id = $1 
+             ORDER BY created_at, id"
+        )
+        .bind(game_id)
+        .fetch_all(&self.db.pool)
+        .await?;
+
+        Ok(entries)
+    }
+
+    /// List the completed games a player sat in, newest first
+    pub async fn get_player_games(
+        &self,
+        discord_id: i64,
+        filter: &PlayerGameFilter,
+        limit: i64,
+        offset: i64,
+        cursor: Option<Cursor>,
+    ) -> AppResult<Vec<PlayerGame>> {
+        let mut query = QueryBuilder::<Postgres>::new(
+            "SELECT games.game_id, games.guild_id, games.script, games.script_id, games.custom_name,
+                    games.start_time, games.end_time, games.completed_at, games.winner,
+                    games.player_count, games.storyteller_user_id,
+                    gp.seat_number, gp.player_name,
+                    gp.starting_role_id, gp.starting_role_name, gp.starting_team,
+                    gp.final_role_id, gp.final_role_name, gp.final_team,
+                    gp.survived, gp.winning_team AS won
+             FROM game_players gp
+             JOIN games ON games.game_id = gp.game_id
+             WHERE games.is_active = false
+               AND games.winner IS DISTINCT FROM 'Cancelled'
+               AND gp.discord_id = "
+        );
+        query.push_bind(discord_id);
+
+        if let Some(ref role) = filter.role {
+            query.push(" AND (gp.starting_role_id = ").push_bind(role.clone());
+            query.push(" OR gp.final_role_id = ").push_bind(role.clone()).push(")");
+        }
+        if let Some(ref team) = filter.team {
+            match team.to_lowercase().as_str() {
+                "good" => query.push(format!(" AND {} = 'Good'", SEAT_ALIGNMENT_SQL)),
+                "evil" => query.push(format!(" AND {} = 'Evil'", SEAT_ALIGNMENT_SQL)),
+                team => query
+                    .push(" AND LOWER(COALESCE(gp.final_team, gp.starting_team)) = ")
+                    .push_bind(team.to_string()),
+            };
+        }
+        if let Some(ref script) = filter.script {
+            push_script_filter(&mut query, script);
+        }
+        if let Some(cursor) = cursor {
+            push_completed_cursor(&mut query, cursor, false)?;
+        }
+        query.push(game_sort_clause(GameSort::Newest));
+        query.push(" LIMIT ").push_bind(limit);
+        query.push(" OFFSET ").push_bind(offset);
+
+        let games = query
+            .build_query_as::<PlayerGame>()
+            .fetch_all(&self.db.pool)
+            .await?;
+
+        Ok(games)
+    }
+}
+
+/// The side ("Good" or "Evil") a seat aliased as `gp` finished on
+pub const SEAT_ALIGNMENT_SQL: &str = "CASE \
+    WHEN LOWER(COALESCE(gp.final_team, gp.starting_team)) IN ('townsfolk', 'outsider', 'good') THEN 'Good' \
+    WHEN LOWER(COALESCE(gp.final_team, gp.starting_team)) IN ('minion', 'demon', 'evil') THEN 'Evil' \
+    END";
+
+/// Keyset sort key for completed games; games without a completion time sort
+/// as if completed at the epoch
+const COMPLETED_KEY: &str = "COALESCE(games.completed_at, TIMESTAMP '1970-01-01 00:00:00')";
+
+/// Cursor pointing just after a game in completion order
+pub fn game_cursor(game: &Game) -> Cursor {
+    let completed_at = game.completed_at.unwrap_or_default();
+    Cursor {
+        sort_key: completed_at.and_utc().timestamp_micros(),
+        id: game.game_id as i64,
+    }
+}
+
+/// Cursor pointing just after a seat in a player's match history
+pub fn player_game_cursor(game: &PlayerGame) -> Cursor {
+    let completed_at = game.completed_at.unwrap_or_default();
+    Cursor {
+        sort_key: completed_at.and_utc().timestamp_micros(),
+        id: game.game_id as i64,
+    }
+}
+
+/// Completed games matching a filter, before sorting and paging
+fn games_query(filter: &GameFilter) -> QueryBuilder<'static, Postgres> {
+    let mut query = QueryBuilder::<Postgres>::new(
+        "SELECT game_id, guild_id, script, script_id, custom_name, start_time, end_time, winner, 
+                player_count, players, is_active, created_at, completed_at, 
+                storyteller_id, category_id, storyteller_user_id 
+         FROM games 
+         WHERE is_active = false"
+    );
+    push_game_filters(&mut query, filter);
+    query
+}
+
+/// Every completed game matching a filter, in the filter's sort order
+pub fn export_games_query(filter: &GameFilter) -> QueryBuilder<'static, Postgres> {
+    let mut query = games_query(filter);
+    query.push(game_sort_clause(filter.sort));
+    query
+}
+
+/// Every seat in the completed games matching a filter, in the filter's sort
+/// order and then by seat
+pub fn export_game_players_query(filter: &GameFilter) -> QueryBuilder<'static, Postgres> {
+    let mut query = QueryBuilder::<Postgres>::new(
+        "SELECT gp.id, gp.game_id, gp.discord_id, gp.player_name, gp.seat_number, 
+                gp.final_role_id, gp.final_role_name, gp.final_team, 
+                gp.survived, gp.winning_team, gp.created_at,
+                gp.starting_role_id, gp.starting_role_name, gp.starting_team 
+         FROM game_players gp 
+         JOIN games ON games.game_id = gp.game_id 
+         WHERE games.is_active = false"
+    );
+    push_game_filters(&mut query, filter);
+    query.push(game_sort_clause(filter.sort)).push(", gp.seat_number");
+    query
+}
+
+/// Append a keyset condition continuing after a cursor in completion order
+fn push_completed_cursor(query: &mut QueryBuilder<'_, Postgres>, cursor: Cursor, ascending: bool) -> AppResult<()> {
+    let completed_at = DateTime::from_timestamp_micros(cursor.sort_key)
+        .ok_or_else(|| AppError::Validation("Invalid cursor".to_string()))?
+        .naive_utc();
+    let comparison = if ascending { " > " } else { " < " };
+
+    query.push(format!(" AND ({}, games.game_id){}(", COMPLETED_KEY, comparison));
+    query.push_bind(completed_at).push(", ").push_bind(cursor.id as i32).push(")");
+    Ok(())
+}
+
+/// Append `AND ...` conditions for a game filter to a query over `games`
+pub fn push_game_filters(query: &mut QueryBuilder<'_, Postgres>, filter: &GameFilter) {
+    if let Some(guild_id) = filter.guild_id {
+        query.push(" AND games.guild_id = ").push_bind(guild_id);
+    }
+    if let Some(ref script) = filter.script {
+        push_script_filter(query, script);
+    }
+    if let Some(storyteller_id) = filter.storyteller_id {
+        query.push(" AND games.storyteller_user_id = ").push_bind(storyteller_id);
+    }
+    if let Some(ref winner) = filter.winner {
+        query.push(" AND games.winner = ").push_bind(winner.clone());
+    }
+    if let Some(min_players) = filter.min_players {
+        query.push(" AND games.player_count >= ").push_bind(min_players);
+    }
+    if let Some(max_players) = filter.max_players {
+        query.push(" AND games.player_count <= ").push_bind(max_players);
+    }
+    if let Some(from) = filter.from {
+        query.push(" AND games.completed_at >= ").push_bind(from.and_time(NaiveTime::MIN));
+    }
+    if let Some(to) = filter.to {
+        // Inclusive of the whole end day
+        query.push(" AND games.completed_at < ").push_bind((to + Duration::days(1)).and_time(NaiveTime::MIN));
+    }
+    if let Some(discord_id) = filter.discord_id {
+        query.push(" AND EXISTS (SELECT 1 FROM game_players gp WHERE gp.game_id = games.game_id AND gp.discord_id = ");
+        query.push_bind(discord_id).push(")");
+    }
+}
+
+fn game_sort_clause(sort: GameSort) -> &'static str {
+    match sort {
+        GameSort::Newest => " ORDER BY COALESCE(games.completed_at, TIMESTAMP '1970-01-01 00:00:00') DESC, games.game_id DESC",
+        GameSort::Oldest => " ORDER BY COALESCE(games.completed_at, TIMESTAMP '1970-01-01 00:00:00') ASC, games.game_id ASC",
+        GameSort::MostPlayers => " ORDER BY games.player_count DESC NULLS LAST, games.game_id DESC",
+        GameSort::FewestPlayers => " ORDER BY games.player_count ASC NULLS LAST, games.game_id DESC",
+        GameSort::Longest => " ORDER BY (games.end_time - games.start_time) DESC NULLS LAST, games.game_id DESC",
+        GameSort::Shortest => " ORDER BY (games.end_time - games.start_time) ASC NULLS LAST, games.game_id DESC",
+    }
+}
+
+/// Derive each seat's result from the team it finished on and the winner
+async fn update_winning_teams(
+    conn: &mut PgConnection,
+    game_id: i32,
+    winner: Option<&str>,
+) -> AppResult<()> {
+    sqlx::query(
+        "UPDATE game_players SET winning_team = CASE 
+             WHEN $2::text IS NULL THEN NULL 
+             WHEN LOWER(COALESCE(final_team, starting_team)) IN ('townsfolk', 'outsider', 'good') THEN $2 = 'Good' 
+             WHEN LOWER(COALESCE(final_team, starting_team)) IN ('minion', 'demon', 'evil') THEN $2 = 'Evil' 
+             ELSE NULL 
+         END 
+         WHERE game_id = $1"
+    )
+    .bind(game_id)
+    .bind(winner)
+    .execute(conn)
+    .await?;
+
+    Ok(())
 }
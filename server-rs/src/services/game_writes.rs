@@ -0,0 +1,103 @@
+//! Follow-up work after a game is written
+//!
+//! Starting, ending, cancelling or correcting a game leaves stats aggregates,
+//! ratings and cached responses out of date, and webhooks and live
+//! subscribers waiting to hear about it. Request handlers and the game
+//! recorder both go through [`GameWriteService::after_game_write`] so every
+//! write path does the same follow-up.
+
+use crate::{
+    database::Database,
+    models::{GameCorrectionEntry, StreamEvent, WebhookEvent},
+    services::{
+        aggregates::{AggregateKeys, AggregateService},
+        cache::ResponseCache,
+        events::EventService,
+        ratings::{RatingRebuilder, RatingService},
+        webhooks::WebhookService,
+    },
+};
+
+/// A game write that has just been saved
+pub enum GameWrite<'a> {
+    Started,
+    Ended,
+    Cancelled,
+    Corrected {
+        /// Aggregate rows the game contributed to before the correction
+        keys_before: Option<AggregateKeys>,
+        corrections: &'a [GameCorrectionEntry],
+    },
+}
+
+#[derive(Clone)]
+pub struct GameWriteService {
+    aggregates: AggregateService,
+    ratings: RatingService,
+    rating_rebuilds: RatingRebuilder,
+    cache: ResponseCache,
+    webhooks: WebhookService,
+    events: EventService,
+}
+
+impl GameWriteService {
+    pub fn new(
+        db: Database,
+        cache: ResponseCache,
+        webhooks: WebhookService,
+        events: EventService,
+        rating_rebuilds: RatingRebuilder,
+    ) -> Self {
+        Self {
+            aggregates: AggregateService::new(db.clone()),
+            ratings: RatingService::new(db),
+            rating_rebuilds,
+            cache,
+            webhooks,
+            events,
+        }
+    }
+
+    /// Bring everything derived from a game up to date and announce the
+    /// write. The game itself is already saved, so failures here are logged
+    /// rather than returned: failing the write would only make the caller
+    /// retry something that succeeded.
+    pub async fn after_game_write(&self, game_id: i32, write: GameWrite<'_>) {
+        let (webhook, events, corrections): (_, &[StreamEvent], _) = match write {
+            GameWrite::Started => (WebhookEvent::Started, &[StreamEvent::GameStarted], &[][..]),
+            GameWrite::Ended => {
+                if let Err(e) = self.ratings.rate_game(game_id).await {
+                    tracing::error!("Failed to rate game {}: {}", game_id, e);
+                }
+                if let Err(e) = self.aggregates.add_game(game_id).await {
+                    tracing::error!("Failed to update aggregates for game {}: {}", game_id, e);
+                }
+                (WebhookEvent::Ended, &[StreamEvent::GameEnded, StreamEvent::StatsUpdated], &[][..])
+            }
+            GameWrite::Cancelled => {
+                if let Err(e) = self.aggregates.add_game(game_id).await {
+                    tracing::error!("Failed to update aggregates for game {}: {}", game_id, e);
+                }
+                (WebhookEvent::Cancelled, &[StreamEvent::GameCancelled], &[][..])
+            }
+            GameWrite::Corrected { keys_before, corrections } => {
+                if let Err(e) = self.aggregates.correct_game(game_id, keys_before).await {
+                    tracing::error!("Failed to update aggregates after correcting game {}: {}", game_id, e);
+                }
+                // Later games were rated against the old result, so replay the history
+                self.rating_rebuilds.request();
+                (WebhookEvent::Corrected, &[StreamEvent::StatsUpdated], corrections)
+            }
+        };
+
+        self.cache.invalidate().await;
+        if let Err(e) = self.webhooks.notify(webhook, game_id, corrections).await {
+            tracing::error!("Failed to queue {} webhooks for game {}: {}", webhook.as_str(), game_id, e);
+        }
+        for &event in events {
+            if let Err(e) = self.events.publish_game(event, game_id).await {
+                tracing::error!("Failed to publish {} event for game {}: {}", event.as_str(), game_id, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::time::Duration;
+use sqlx::Row;
+
+use crate::{database::Database, error::AppResult};
+
+/// How long a claimed key stays locked without a response before a retry may
+/// take it over, in case the request holding it never finished
+const CLAIM_LEASE_SECONDS: f64 = 60.0;
+
+/// A response stored against an idempotency key
+pub struct StoredResponse {
+    pub status_code: u16,
+    pub body: String,
+}
+
+/// Outcome of claiming an idempotency key for a request
+pub enum IdempotencyClaim {
+    /// First use of the key, or a takeover of a claim whose request never
+    /// finished; the request should be executed
+    Claimed,
+    /// The request already completed; replay its response
+    Replay(StoredResponse),
+    /// The original request is still being processed, within its lease
+    InProgress,
+    /// The key was already used for a different request
+    Mismatch,
+}
+
+pub struct IdempotencyService {
+    db: Database,
+    retention_hours: i64,
+}
+
+impl IdempotencyService {
+    pub fn new(db: Database, retention_hours: u64) -> Self {
+        let service = Self {
+            db,
+            retention_hours: retention_hours as i64,
+        };
+
+        // Spawn cleanup task
+        let pool = service.db.pool.clone();
+        let retention_hours = service.retention_hours;
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(3600)); // 1 hour
+            loop {
+                interval.tick().await;
+                let result = sqlx::query(
+                    "DELETE FROM idempotency_keys WHERE created_at < CURRENT_TIMESTAMP - make_interval(hours => $1)"
+                )
+                .bind(retention_hours as i32)
+                .execute(&pool)
+                .await;
+
+                if let Err(e) = result {
+                    tracing::warn!("Failed to clean up idempotency keys: {}", e);
+                }
+            }
+        });
+
+        service
+    }
+
+    /// Claim a key for a request, or find the response it already produced
+    pub async fn claim(
+        &self,
+        session_id: &str,
+        key: &str,
+        request_path: &str,
+        request_hash: &str,
+    ) -> AppResult<IdempotencyClaim> {
+        // Keys past the retention window may be reused
+        sqlx::query(
+            "DELETE FROM idempotency_keys
+             WHERE session_id = $1 AND idempotency_key = $2
+               AND created_at < CURRENT_TIMESTAMP - make_interval(hours => $3)"
+        )
+        .bind(session_id)
+        .bind(key)
+        .bind(self.retention_hours as i32)
+        .execute(&self.db.pool)
+        .await?;
+
+        // A claim past its lease without a response belonged to a request
+        // that died, so the same request may take it over
+        let claimed = sqlx::query(
+            "INSERT INTO idempotency_keys (idempotency_key, session_id, request_path, request_hash)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (session_id, idempotency_key) DO UPDATE SET locked_at = CURRENT_TIMESTAMP
+             WHERE idempotency_keys.status_code IS NULL
+               AND idempotency_keys.locked_at < CURRENT_TIMESTAMP - make_interval(secs => $5)
+               AND idempotency_keys.request_path = EXCLUDED.request_path
+               AND idempotency_keys.request_hash = EXCLUDED.request_hash"
+        )
+        .bind(key)
+        .bind(session_id)
+        .bind(request_path)
+        .bind(request_hash)
+        .bind(CLAIM_LEASE_SECONDS)
+        .execute(&self.db.pool)
+        .await?
+        .rows_affected()
+            > 0;
+
+        if claimed {
+            return Ok(IdempotencyClaim::Claimed);
+        }
+
+        let row = sqlx::query(
+            "SELECT request_path, request_hash, status_code, response_body
+             FROM idempotency_keys
+             WHERE session_id = $1 AND idempotency_key = $2"
+        )
+        .bind(session_id)
+        .bind(key)
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        // The key was released between the insert and the select
+        let Some(row) = row else {
+            return Ok(IdempotencyClaim::InProgress);
+        };
+
+        let stored_path: String = row.get("request_path");
+        let stored_hash: String = row.get("request_hash");
+        if stored_path != request_path || stored_hash != request_hash {
+            return Ok(IdempotencyClaim::Mismatch);
+        }
+
+        let status_code: Option<i32> = row.get("status_code");
+        let body: Option<String> = row.get("response_body");
+        match (status_code, body) {
+            (Some(status_code), Some(body)) => Ok(IdempotencyClaim::Replay(StoredResponse {
+                status_code: status_code as u16,
+                body,
+            })),
+            _ => Ok(IdempotencyClaim::InProgress),
+        }
+    }
+
+    /// Store the response produced for a claimed key
+    pub async fn complete(&self, session_id: &str, key: &str, response: &StoredResponse) -> AppResult<()> {
+        sqlx::query(
+            "UPDATE idempotency_keys SET status_code = $3, response_body = $4
+             WHERE session_id = $1 AND idempotency_key = $2"
+        )
+        .bind(session_id)
+        .bind(key)
+        .bind(response.status_code as i32)
+        .bind(&response.body)
+        .execute(&self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Release a claimed key so the request can be retried
+    pub async fn release(&self, session_id: &str, key: &str) -> AppResult<()> {
+        sqlx::query("DELETE FROM idempotency_keys WHERE session_id = $1 AND idempotency_key = $2")
+            .bind(session_id)
+            .bind(key)
+            .execute(&self.db.pool)
+            .await?;
+
+        Ok(())
+    }
+}
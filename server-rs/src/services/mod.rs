@@ -1,22 +1,66 @@
+pub mod aggregates;
+pub mod api_keys;
+pub mod cache;
+pub mod events;
 pub mod session;
 pub mod game;
+pub mod game_writes;
+pub mod idempotency;
 pub mod rate_limit;
+pub mod ratings;
+pub mod recorder;
+pub mod script_validation;
+pub mod scripts;
+pub mod stats;
+pub mod webhooks;
 
-use crate::database::Database;
+use crate::{config::Config, database::Database};
 
 pub struct ServiceContainer {
-    #[allow(dead_code)]
     pub session: session::SessionService,
     pub game: game::GameService,
+    pub game_writes: game_writes::GameWriteService,
+    pub stats: stats::StatsService,
+    pub aggregates: aggregates::AggregateService,
+    pub api_keys: api_keys::ApiKeyService,
+    pub cache: cache::ResponseCache,
     pub rate_limit: rate_limit::RateLimitService,
+    pub recorder: recorder::GameRecorderService,
+    pub scripts: scripts::ScriptService,
+    pub idempotency: idempotency::IdempotencyService,
+    pub webhooks: webhooks::WebhookService,
+    pub events: events::EventService,
 }
 
 impl ServiceContainer {
-    pub fn new(database: Database) -> Self {
+    pub fn new(database: Database, config: &Config) -> Self {
+        let cache = cache::ResponseCache::new(database.clone());
+        // Local webhook receivers are allowed outside production, for development
+        let webhooks = webhooks::WebhookService::new(database.clone(), config.node_env != "production");
+        let events = events::EventService::new(database.clone());
+        let scripts = scripts::ScriptService::new(database.clone());
+        let game_writes = game_writes::GameWriteService::new(
+            database.clone(),
+            cache.clone(),
+            webhooks.clone(),
+            events.clone(),
+            ratings::RatingRebuilder::new(database.clone(), cache.clone()),
+        );
+
         Self {
             session: session::SessionService::new(database.clone()),
             game: game::GameService::new(database.clone()),
+            game_writes: game_writes.clone(),
+            stats: stats::StatsService::new(database.clone()),
+            aggregates: aggregates::AggregateService::new(database.clone()),
+            api_keys: api_keys::ApiKeyService::new(database.clone()),
+            cache,
             rate_limit: rate_limit::RateLimitService::new(),
+            recorder: recorder::GameRecorderService::new(database.clone(), scripts.clone(), events.clone(), game_writes),
+            scripts,
+            idempotency: idempotency::IdempotencyService::new(database.clone(), config.idempotency_retention_hours),
+            webhooks,
+            events,
         }
     }
 }
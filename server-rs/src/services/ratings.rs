@@ -0,0 +1,370 @@
+//! Elo-style player ratings, tracked separately for the good and evil teams
+//!
+//! Each linked player's rating for the team they finished on moves towards
+//! their result, weighted by how their rating compares to the average rating
+//! of the linked players on the opposing team.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use sqlx::{FromRow, PgConnection};
+use tokio::sync::Notify;
+
+use crate::{
+    database::Database,
+    error::AppResult,
+    services::{cache::ResponseCache, game::SEAT_ALIGNMENT_SQL},
+};
+
+/// How long a requested rebuild waits for further requests to join it
+const REBUILD_DELAY: Duration = Duration::from_secs(10);
+
+const INITIAL_RATING: f64 = 1500.0;
+const K_FACTOR: f64 = 32.0;
+
+#[derive(Debug, Clone, Copy)]
+struct Rating {
+    good: f64,
+    evil: f64,
+    good_games: i32,
+    evil_games: i32,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Self {
+            good: INITIAL_RATING,
+            evil: INITIAL_RATING,
+            good_games: 0,
+            evil_games: 0,
+        }
+    }
+}
+
+/// A linked seat in a decided game
+#[derive(Debug, FromRow)]
+struct RatedSeat {
+    game_id: i32,
+    discord_id: i64,
+    alignment: Option<String>,
+    winning_team: Option<bool>,
+}
+
+/// Rebuilds ratings in the background after games are corrected. Requests
+/// made while a rebuild is waiting or running are served by one more
+/// rebuild, so a burst of corrections locks the ratings tables only twice.
+#[derive(Clone)]
+pub struct RatingRebuilder {
+    requested: Arc<Notify>,
+}
+
+impl RatingRebuilder {
+    pub fn new(db: Database, cache: ResponseCache) -> Self {
+        let requested = Arc::new(Notify::new());
+
+        // Spawn rebuild task
+        let ratings = RatingService::new(db);
+        let pending = requested.clone();
+        tokio::spawn(async move {
+            loop {
+                pending.notified().await;
+                tokio::time::sleep(REBUILD_DELAY).await;
+                if let Err(e) = ratings.rebuild().await {
+                    tracing::error!("Failed to rebuild ratings: {}", e);
+                }
+                // Leaderboards served before the rebuild finished show old ratings
+                cache.invalidate().await;
+            }
+        });
+
+        Self { requested }
+    }
+
+    /// Ask for ratings to be replayed from the full game history
+    pub fn request(&self) {
+        self.requested.notify_one();
+    }
+}
+
+#[derive(Clone)]
+pub struct RatingService {
+    db: Database,
+}
+
+impl RatingService {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+
+    /// Apply a completed game to the ratings of its players. Games that are
+    /// undecided or already rated are skipped.
+    pub async fn rate_game(&self, game_id: i32) -> AppResult<()> {
+        let mut tx = self.db.pool.begin().await?;
+
+        let newly_rated = sqlx::query(
+            "INSERT INTO rated_games (game_id)
+             SELECT game_id FROM games
+             WHERE game_id = $1 AND is_active = false AND winner IN ('Good', 'Evil')
+             ON CONFLICT (game_id) DO NOTHING"
+        )
+        .bind(game_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+
+        if !newly_rated {
+            return Ok(());
+        }
+
+        let seats = fetch_rated_seats(&mut tx, Some(game_id)).await?;
+        let mut discord_ids: Vec<i64> = seats.iter().map(|seat| seat.discord_id).collect();
+        // Lock in a fixed order so games sharing players can't deadlock
+        discord_ids.sort_unstable();
+        discord_ids.dedup();
+
+        // FOR UPDATE only locks existing rows, so give first-time players one
+        // before two games can both read them as new
+        sqlx::query(
+            "INSERT INTO player_ratings (discord_id, good_rating, evil_rating, good_games, evil_games, updated_at)
+             SELECT UNNEST($1::bigint[]), $2, $2, 0, 0, CURRENT_TIMESTAMP
+             ON CONFLICT (discord_id) DO NOTHING"
+        )
+        .bind(&discord_ids)
+        .bind(INITIAL_RATING)
+        .execute(&mut *tx)
+        .await?;
+
+        let rows: Vec<(i64, f64, f64, i32, i32)> = sqlx::query_as(
+            "SELECT discord_id, good_rating, evil_rating, good_games, evil_games
+             FROM player_ratings
+             WHERE discord_id = ANY($1)
+             ORDER BY discord_id
+             FOR UPDATE"
+        )
+        .bind(&discord_ids)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let mut ratings: HashMap<i64, Rating> = rows
+            .into_iter()
+            .map(|(discord_id, good, evil, good_games, evil_games)| {
+                (discord_id, Rating { good, evil, good_games, evil_games })
+            })
+            .collect();
+
+        apply_game(&mut ratings, &seats);
+        save_ratings(&mut tx, &ratings).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Recompute every rating from the full game history, in completion
+    /// order. Returns the number of games rated.
+    pub async fn rebuild(&self) -> AppResult<usize> {
+        let mut tx = self.db.pool.begin().await?;
+
+        sqlx::query("LOCK TABLE player_ratings, rated_games IN EXCLUSIVE MODE")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM rated_games").execute(&mut *tx).await?;
+        sqlx::query("DELETE FROM player_ratings").execute(&mut *tx).await?;
+
+        let seats = fetch_rated_seats(&mut tx, None).await?;
+
+        let mut ratings = HashMap::new();
+        let mut game_ids = Vec::new();
+        for game in seats.chunk_by(|a, b| a.game_id == b.game_id) {
+            apply_game(&mut ratings, game);
+            game_ids.push(game[0].game_id);
+        }
+
+        save_ratings(&mut tx, &ratings).await?;
+        sqlx::query("INSERT INTO rated_games (game_id) SELECT UNNEST($1::int[])")
+            .bind(&game_ids)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(game_ids.len())
+    }
+}
+
+/// Load linked seats from decided games in completion order, optionally for a
+/// single game
+async fn fetch_rated_seats(conn: &mut PgConnection, game_id: Option<i32>) -> AppResult<Vec<RatedSeat>> {
+    let seats = sqlx::query_as::<_, RatedSeat>(&format!(
+        "SELECT gp.game_id, gp.discord_id, {} AS alignment, gp.winning_team
+         FROM game_players gp
+         JOIN games ON games.game_id = gp.game_id
+         WHERE games.is_active = false
+           AND games.winner IN ('Good', 'Evil')
+           AND gp.discord_id IS NOT NULL
+           AND ($1::int IS NULL OR games.game_id = $1)
+         ORDER BY COALESCE(games.completed_at, TIMESTAMP '1970-01-01 00:00:00'), games.game_id, gp.seat_number",
+        SEAT_ALIGNMENT_SQL
+    ))
+    .bind(game_id)
+    .fetch_all(conn)
+    .await?;
+
+    Ok(seats)
+}
+
+async fn save_ratings(conn: &mut PgConnection, ratings: &HashMap<i64, Rating>) -> AppResult<()> {
+    for (discord_id, rating) in ratings {
+        sqlx::query(
+            "INSERT INTO player_ratings (discord_id, good_rating, evil_rating, good_games, evil_games, updated_at)
+             VALUES ($1, $2, $3, $4, $5, CURRENT_TIMESTAMP)
+             ON CONFLICT (discord_id) DO UPDATE SET
+                 good_rating = EXCLUDED.good_rating,
+                 evil_rating = EXCLUDED.evil_rating,
+                 good_games = EXCLUDED.good_games,
+                 evil_games = EXCLUDED.evil_games,
+                 updated_at = EXCLUDED.updated_at"
+        )
+        .bind(discord_id)
+        .bind(rating.good)
+        .bind(rating.evil)
+        .bind(rating.good_games)
+        .bind(rating.evil_games)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Update ratings for the seats of a single game
+fn apply_game(ratings: &mut HashMap<i64, Rating>, seats: &[RatedSeat]) {
+    let team_rating = |ratings: &HashMap<i64, Rating>, alignment: &str| {
+        let team: Vec<f64> = seats
+            .iter()
+            .filter(|seat| seat.alignment.as_deref() == Some(alignment))
+            .map(|seat| {
+                let rating = ratings.get(&seat.discord_id).copied().unwrap_or_default();
+                if alignment == "Good" { rating.good } else { rating.evil }
+            })
+            .collect();
+
+        if team.is_empty() {
+            INITIAL_RATING
+        } else {
+            team.iter().sum::<f64>() / team.len() as f64
+        }
+    };
+
+    // Both averages are taken before any rating in this game changes
+    let good_average = team_rating(ratings, "Good");
+    let evil_average = team_rating(ratings, "Evil");
+
+    for seat in seats {
+        let Some(won) = seat.winning_team else { continue };
+        let rating = ratings.entry(seat.discord_id).or_default();
+        let score = if won { 1.0 } else { 0.0 };
+
+        match seat.alignment.as_deref() {
+            Some("Good") => {
+                rating.good += K_FACTOR * (score - expected_score(rating.good, evil_average));
+                rating.good_games += 1;
+            }
+            Some("Evil") => {
+                rating.evil += K_FACTOR * (score - expected_score(rating.evil, good_average));
+                rating.evil_games += 1;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn expected_score(rating: f64, opponent_rating: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent_rating - rating) / 400.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seat(discord_id: i64, alignment: Option<&str>, winning_team: Option<bool>) -> RatedSeat {
+        RatedSeat { game_id: 1, discord_id, alignment: alignment.map(str::to_string), winning_team }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn expected_score_follows_the_rating_gap() {
+        assert!(close(expected_score(1500.0, 1500.0), 0.5));
+        assert!(close(expected_score(1900.0, 1500.0), 10.0 / 11.0));
+        assert!(close(expected_score(1500.0, 1900.0) + expected_score(1900.0, 1500.0), 1.0));
+    }
+
+    #[test]
+    fn even_teams_move_by_half_the_k_factor() {
+        let mut ratings = HashMap::new();
+        let seats = [
+            seat(1, Some("Good"), Some(true)),
+            seat(2, Some("Good"), Some(true)),
+            seat(3, Some("Evil"), Some(false)),
+        ];
+        apply_game(&mut ratings, &seats);
+
+        for winner in [1, 2] {
+            assert!(close(ratings[&winner].good, INITIAL_RATING + K_FACTOR / 2.0));
+            assert_eq!((ratings[&winner].good_games, ratings[&winner].evil_games), (1, 0));
+            assert!(close(ratings[&winner].evil, INITIAL_RATING));
+        }
+        assert!(close(ratings[&3].evil, INITIAL_RATING - K_FACTOR / 2.0));
+        assert_eq!((ratings[&3].good_games, ratings[&3].evil_games), (0, 1));
+    }
+
+    #[test]
+    fn players_are_rated_against_the_opposing_average() {
+        let mut ratings = HashMap::new();
+        ratings.insert(1, Rating { good: 1700.0, ..Rating::default() });
+        ratings.insert(2, Rating { evil: 1300.0, ..Rating::default() });
+        ratings.insert(3, Rating { evil: 1500.0, ..Rating::default() });
+        let seats = [
+            seat(1, Some("Good"), Some(false)),
+            seat(2, Some("Evil"), Some(true)),
+            seat(3, Some("Evil"), Some(true)),
+        ];
+        apply_game(&mut ratings, &seats);
+
+        // Good faced an evil average of 1400; each evil player faced 1700
+        assert!(close(ratings[&1].good, 1700.0 - K_FACTOR * expected_score(1700.0, 1400.0)));
+        assert!(close(ratings[&2].evil, 1300.0 + K_FACTOR * (1.0 - expected_score(1300.0, 1700.0))));
+        assert!(close(ratings[&3].evil, 1500.0 + K_FACTOR * (1.0 - expected_score(1500.0, 1700.0))));
+    }
+
+    #[test]
+    fn travellers_and_undecided_seats_are_not_rated() {
+        let mut ratings = HashMap::new();
+        ratings.insert(9, Rating { good: 2000.0, evil: 2000.0, ..Rating::default() });
+        let seats = [
+            seat(1, Some("Good"), Some(true)),
+            seat(2, Some("Evil"), Some(false)),
+            // A traveller has no team to be rated on, nor does it count towards either average
+            seat(9, None, Some(true)),
+            seat(4, Some("Good"), None),
+        ];
+        apply_game(&mut ratings, &seats);
+
+        assert!(close(ratings[&1].good, INITIAL_RATING + K_FACTOR / 2.0));
+        assert!(close(ratings[&2].evil, INITIAL_RATING - K_FACTOR / 2.0));
+        assert!(close(ratings[&9].good, 2000.0) && close(ratings[&9].evil, 2000.0));
+        assert_eq!((ratings[&9].good_games, ratings[&9].evil_games), (0, 0));
+        assert!(!ratings.contains_key(&4));
+    }
+
+    #[test]
+    fn one_sided_games_use_the_initial_rating_for_the_empty_side() {
+        let mut ratings = HashMap::new();
+        ratings.insert(1, Rating { good: 1600.0, ..Rating::default() });
+        apply_game(&mut ratings, &[seat(1, Some("Good"), Some(true))]);
+
+        assert!(close(ratings[&1].good, 1600.0 + K_FACTOR * (1.0 - expected_score(1600.0, INITIAL_RATING))));
+    }
+}
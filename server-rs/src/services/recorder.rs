@@ -0,0 +1,931 @@
+//! Server-side game recording from live session traffic
+//!
+//! The host's grimoire already relays everything needed to reconstruct a game:
+//! seats arrive in `gs`, role assignments in directed `player` updates, deaths
+//! in `player` `isDead` changes, and the end-of-game reveal in `grimReveal`.
+//! Games are only recorded for live sessions linked to a Discord guild session,
+//! and only from the grimoire of that session's storyteller, who must connect
+//! signed in with Discord. Players who connect signed in are linked to their
+//! seats.
+//!
+//! Nominations of recorded games are published as live events, as are
+//! executions, taken to be a daytime death of the player on the block.
+//!
+//! A session recording a game is kept for a while after its last client
+//! leaves, so the storyteller can reload the page and carry on. Recorded
+//! games nobody comes back to, or that a server restart left behind, are
+//! cancelled so they don't keep their guild session busy.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use serde_json::{json, Value};
+use tokio::sync::RwLock;
+
+use crate::{
+    database::Database,
+    error::{AppError, AppResult},
+    models::{NewGame, NewGamePlayer, StreamEvent},
+    services::{
+        events::EventService, game::GameService, game_writes::{GameWrite, GameWriteService},
+        scripts::{ScriptDescription, ScriptService}, session::SessionService,
+    },
+};
+
+/// How long to wait before checking again whether a signed-in client is the
+/// storyteller, in case the session is linked to Discord after it connected
+const HOST_RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long a recorded game can go without a session following it before
+/// it is cancelled, and how often to look for such games
+const ORPHAN_GRACE: Duration = Duration::from_secs(15 * 60);
+const ORPHAN_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Custom characters are sent stripped: fields are keyed by their index in
+/// the grimoire's custom role template, and left out when they hold the
+/// template's default
+const STRIPPED_ID_KEY: &str = "0";
+const STRIPPED_TEAM_KEY: &str = "12";
+const STRIPPED_DEFAULT_TEAM: &str = "townsfolk";
+
+/// A client signed in with Discord
+struct TrackedClient {
+    discord_user_id: i64,
+    /// Player the client connected as, or `None` for a grimoire
+    player_id: Option<String>,
+    /// When the client was last checked against the session's storyteller
+    host_checked_at: Option<Instant>,
+}
+
+#[derive(Clone, Default)]
+struct TrackedSeat {
+    player_id: String,
+    name: String,
+    is_dead: bool,
+    starting_role: Option<String>,
+    final_role: Option<String>,
+}
+
+#[derive(Clone)]
+struct TrackedGame {
+    game_id: Option<i32>,
+    guild_id: Option<i64>,
+    /// The storyteller's client records this game itself, so it is only
+    /// followed for live events
+    external: bool,
+    has_deaths: bool,
+}
+
+#[derive(Default)]
+struct TrackedSession {
+    host_client_id: Option<String>,
+    /// Clients signed in with Discord, by client id
+    clients: HashMap<String, TrackedClient>,
+    /// Discord users of signed-in players, by player id. `None` when more
+    /// than one user claimed the same player.
+    player_users: HashMap<String, Option<i64>>,
+    script: Option<String>,
+    custom_name: Option<String>,
+    /// Teams of custom characters, as sent with custom editions
+    role_teams: HashMap<String, String>,
+    /// Character ids and bootlegger rules of a custom edition
+    characters: Vec<String>,
+    bootlegger: Vec<String>,
+    seats: Vec<TrackedSeat>,
+    game: Option<TrackedGame>,
+    is_night: bool,
+    /// Seat of the player on the block
+    marked: Option<usize>,
+    /// When the last client left a session that is kept for its game
+    closed_at: Option<Instant>,
+}
+
+impl TrackedSession {
+    fn is_host(&self, client_id: &str) -> bool {
+        self.host_client_id.as_deref() == Some(client_id)
+    }
+
+    /// Whether a client may rename a seat: the host may rename anyone, and
+    /// signed-in players only themselves
+    fn may_rename(&self, client_id: &str, index: usize) -> bool {
+        if self.is_host(client_id) {
+            return true;
+        }
+        let player_id = self.clients.get(client_id).and_then(|client| client.player_id.as_deref());
+        player_id.is_some() && self.seats.get(index).map(|seat| seat.player_id.as_str()) == player_id
+    }
+
+    /// Game and guild of the game being recorded, once it has been saved
+    fn recorded_game(&self) -> Option<(i32, i64)> {
+        let game = self.game.as_ref()?;
+        Some((game.game_id?, game.guild_id?))
+    }
+
+    /// The saved game this session records itself, rather than follows
+    fn own_game_id(&self) -> Option<i32> {
+        self.game.as_ref().filter(|game| !game.external)?.game_id
+    }
+
+    /// Seat number and name of a seat, for live events
+    fn seat_json(&self, index: usize) -> Value {
+        json!({
+            "seat_number": index + 1,
+            "name": self.seats.get(index).map(|seat| seat.name.as_str()),
+        })
+    }
+
+    /// Team of a character, from the official character list or the custom
+    /// edition it came with
+    fn team_of(&self, role_id: &Option<String>) -> Option<String> {
+        let id = role_id.as_deref()?;
+        // The grimoire uses its own definition of official characters, even
+        // when a script redefines them
+        match grimlive_rules::team_of(id) {
+            Some(team) => Some(team.as_str().to_string()),
+            None => self.role_teams.get(id).cloned(),
+        }
+    }
+
+    /// Discord user of the player in a seat, if they connected signed in
+    fn discord_id(&self, seat: &TrackedSeat) -> Option<i64> {
+        self.player_users.get(&seat.player_id).copied().flatten()
+    }
+
+    /// Apply a role assignment to a seat. Roles assigned before the first death
+    /// count as starting roles; later ones only change the final role.
+    fn assign_role(&mut self, index: usize, role_id: Option<String>) {
+        let setup_phase = self.game.as_ref().is_none_or(|g| !g.has_deaths);
+        if let Some(seat) = self.seats.get_mut(index) {
+            if setup_phase || seat.starting_role.is_none() {
+                seat.starting_role = role_id.clone();
+            }
+            seat.final_role = role_id;
+        }
+    }
+}
+
+/// What to write to the database once the session lock is released
+enum RecorderAction {
+    Start,
+    Finish(i32, Option<&'static str>, Vec<NewGamePlayer>),
+    Publish(StreamEvent, i64, Value),
+}
+
+#[derive(Clone)]
+pub struct GameRecorderService {
+    game: GameService,
+    session: SessionService,
+    events: EventService,
+    game_writes: GameWriteService,
+    scripts: ScriptService,
+    sessions: Arc<RwLock<HashMap<String, TrackedSession>>>,
+}
+
+impl GameRecorderService {
+    pub fn new(db: Database, scripts: ScriptService, events: EventService, game_writes: GameWriteService) -> Self {
+        let service = Self {
+            game: GameService::new(db.clone()),
+            session: SessionService::new(db),
+            scripts,
+            events,
+            game_writes,
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+        };
+
+        // Spawn orphaned game cleanup task
+        let worker = service.clone();
+        tokio::spawn(async move {
+            let mut untracked_since = HashMap::new();
+            let mut interval = tokio::time::interval(ORPHAN_SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = worker.cancel_orphaned_games(&mut untracked_since).await {
+                    tracing::warn!("Failed to clean up orphaned recorded games: {}", e);
+                }
+            }
+        });
+
+        service
+    }
+
+    /// Register a client that connected signed in with Discord. `player_id`
+    /// is the player it connected as, or `None` for the storyteller's
+    /// grimoire.
+    pub async fn add_client(&self, session_id: &str, client_id: &str, player_id: Option<&str>, discord_user_id: i64) {
+        let mut sessions = self.sessions.write().await;
+        let tracked = sessions.entry(session_id.to_string()).or_default();
+        tracked.closed_at = None;
+        tracked.clients.insert(
+            client_id.to_string(),
+            TrackedClient {
+                discord_user_id,
+                player_id: player_id.map(str::to_string),
+                host_checked_at: None,
+            },
+        );
+
+        if let Some(player_id) = player_id {
+            // Anyone can connect as any player, so a player claimed by two
+            // users is linked to neither
+            tracked
+                .player_users
+                .entry(player_id.to_string())
+                .and_modify(|user| {
+                    if *user != Some(discord_user_id) {
+                        *user = None;
+                    }
+                })
+                .or_insert(Some(discord_user_id));
+        }
+    }
+
+    /// Inspect a relayed message and update the tracked game for its session.
+    /// Sessions are only tracked once a signed-in client joins them.
+    pub async fn handle_message(&self, session_id: &str, client_id: &str, text: &str) {
+        if !self.sessions.read().await.contains_key(session_id) {
+            return;
+        }
+        let Ok((command, params)) = serde_json::from_str::<(String, Value)>(text) else {
+            return;
+        };
+
+        // Only the host publishes the gamestate
+        if command == "gs" {
+            self.check_host(session_id, client_id).await;
+        }
+
+        let action = {
+            let mut sessions = self.sessions.write().await;
+            let Some(tracked) = sessions.get_mut(session_id) else {
+                return;
+            };
+            apply_message(tracked, client_id, &command, &params)
+        };
+
+        let result = match action {
+            Some(RecorderAction::Start) => self.start_game(session_id).await,
+            Some(RecorderAction::Finish(game_id, winner, players)) => {
+                self.finish_game(session_id, game_id, winner, players).await
+            }
+            Some(RecorderAction::Publish(event, guild_id, data)) => {
+                self.events.publish(event, guild_id, data).await
+            }
+            None => Ok(()),
+        };
+
+        if let Err(e) = result {
+            tracing::error!("Failed to record game for session {}: {}", session_id, e);
+        }
+    }
+
+    /// Make a client publishing the gamestate the host if it is signed in as
+    /// the storyteller of the Discord guild session linked to the session
+    async fn check_host(&self, session_id: &str, client_id: &str) {
+        let discord_user_id = {
+            let mut sessions = self.sessions.write().await;
+            let Some(tracked) = sessions.get_mut(session_id) else {
+                return;
+            };
+            if tracked.is_host(client_id) {
+                return;
+            }
+            let Some(client) = tracked.clients.get_mut(client_id) else {
+                return;
+            };
+            if client.host_checked_at.is_some_and(|at| at.elapsed() < HOST_RECHECK_INTERVAL) {
+                return;
+            }
+            client.host_checked_at = Some(Instant::now());
+            client.discord_user_id
+        };
+
+        let guild_session = match self.session.get_guild_session_by_code(session_id).await {
+            Ok(guild_session) => guild_session,
+            Err(e) => {
+                tracing::error!("Failed to look up the storyteller of session {}: {}", session_id, e);
+                return;
+            }
+        };
+        if guild_session.and_then(|guild_session| guild_session.storyteller_user_id) != Some(discord_user_id) {
+            return;
+        }
+
+        let mut sessions = self.sessions.write().await;
+        if let Some(tracked) = sessions.get_mut(session_id) {
+            tracing::debug!("Client {} is the storyteller of session {}", client_id, session_id);
+            tracked.host_client_id = Some(client_id.to_string());
+        }
+    }
+
+    /// Forget a client when it disconnects, and the host with it so its
+    /// reconnecting client can take over
+    pub async fn remove_client(&self, session_id: &str, client_id: &str) {
+        let mut sessions = self.sessions.write().await;
+        if let Some(tracked) = sessions.get_mut(session_id) {
+            tracked.clients.remove(client_id);
+            if tracked.is_host(client_id) {
+                tracked.host_client_id = None;
+            }
+        }
+    }
+
+    /// Forget a session once every client has left. A session recording a
+    /// game is kept so the storyteller can reconnect and carry on with it.
+    pub async fn remove_session(&self, session_id: &str) {
+        let mut sessions = self.sessions.write().await;
+        let Some(tracked) = sessions.get_mut(session_id) else {
+            return;
+        };
+
+        match tracked.own_game_id() {
+            Some(game_id) => {
+                tracing::info!("Session {} closed while recording game {}, keeping it", session_id, game_id);
+                tracked.clients.clear();
+                tracked.host_client_id = None;
+                tracked.closed_at = Some(Instant::now());
+            }
+            None => {
+                sessions.remove(session_id);
+            }
+        }
+    }
+
+    /// Cancel recorded games no open session has followed for the grace
+    /// period, whether their session closed or the server restarted while
+    /// recording them. `untracked_since` carries when each game was first
+    /// seen without a session between sweeps.
+    async fn cancel_orphaned_games(&self, untracked_since: &mut HashMap<i32, Instant>) -> AppResult<()> {
+        let tracked: HashSet<i32> = {
+            let mut sessions = self.sessions.write().await;
+            sessions.retain(|_, tracked| tracked.closed_at.is_none_or(|at| at.elapsed() < ORPHAN_GRACE));
+            sessions
+                .values()
+                .filter(|tracked| tracked.closed_at.is_none())
+                .filter_map(TrackedSession::own_game_id)
+                .collect()
+        };
+
+        let recorded = self.game.get_recorded_active_games().await?;
+        let now = Instant::now();
+        untracked_since.retain(|game_id, _| {
+            !tracked.contains(game_id) && recorded.iter().any(|(recorded_id, _)| recorded_id == game_id)
+        });
+
+        for (game_id, session_code) in recorded {
+            if tracked.contains(&game_id) {
+                continue;
+            }
+            let since = *untracked_since.entry(game_id).or_insert(now);
+            if since.elapsed() < ORPHAN_GRACE {
+                continue;
+            }
+
+            untracked_since.remove(&game_id);
+            tracing::warn!("Cancelling game {} left behind by session {}", game_id, session_code);
+            self.cancel_game(game_id).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Cancel a recorded game that can't be finished, unless it has ended
+    /// in the meantime
+    async fn cancel_game(&self, game_id: i32) -> AppResult<()> {
+        let end_time = chrono::Utc::now().timestamp_millis() as f64 / 1000.0;
+        match self.game.cancel_game(game_id, end_time).await {
+            Ok(()) => {}
+            Err(AppError::Conflict(_)) => return Ok(()),
+            Err(e) => return Err(e),
+        }
+
+        self.game_writes.after_game_write(game_id, GameWrite::Cancelled).await;
+        Ok(())
+    }
+
+    async fn start_game(&self, session_id: &str) -> AppResult<()> {
+        let mut guild_session = self.session.get_guild_session_by_code(session_id).await?;
+
+        // A game this session was recording before a server restart can't
+        // be finished, and its storyteller has moved on to a new one
+        if let Some(active_game_id) = guild_session.as_ref().and_then(|guild_session| guild_session.active_game_id) {
+            let recorded = self.game.get_recorded_active_games().await?;
+            if recorded.iter().any(|(game_id, code)| *game_id == active_game_id && code == session_id) {
+                tracing::info!("Cancelling game {} that session {} can no longer finish", active_game_id, session_id);
+                self.cancel_game(active_game_id).await?;
+                guild_session = self.session.get_guild_session_by_code(session_id).await?;
+            }
+        }
+
+        let mut sessions = self.sessions.write().await;
+        let Some(tracked) = sessions.get_mut(session_id) else {
+            return Ok(());
+        };
+
+        let Some(guild_session) = guild_session else {
+            tracing::debug!("Session {} is not linked to Discord, not recording", session_id);
+            tracked.game = None;
+            return Ok(());
+        };
+
+        // The storyteller's client is already recording this game itself
+        if let Some(active_game_id) = guild_session.active_game_id {
+            tracing::debug!("Session {} already has an active game, not recording", session_id);
+            if let Some(game) = tracked.game.as_mut() {
+                game.game_id = Some(active_game_id);
+                game.guild_id = Some(guild_session.guild_id);
+                game.external = true;
+            }
+            return Ok(());
+        }
+
+        let script = tracked.script.clone().unwrap_or_else(|| "Custom Script".to_string());
+        let script_id = self.scripts
+            .identify(&ScriptDescription {
+                script: &script,
+                custom_name: tracked.custom_name.as_deref(),
+                characters: &tracked.characters,
+                bootlegger: &tracked.bootlegger,
+            })
+            .await?;
+
+        let new_game = NewGame {
+            guild_id: guild_session.guild_id,
+            category_id: Some(guild_session.category_id),
+            script,
+            script_id,
+            custom_name: tracked.custom_name.clone(),
+            start_time: chrono::Utc::now().timestamp_millis() as f64 / 1000.0,
+            players: tracked.seats.iter().map(|seat| seat.name.clone()).collect(),
+            storyteller_user_id: guild_session.storyteller_user_id,
+            recorded_session_code: Some(session_id.to_string()),
+        };
+
+        let game_id = match self.game.create_game(&new_game).await {
+            Ok(game_id) => game_id,
+            Err(e) => {
+                // Try again on the next role distribution
+                tracked.game = None;
+                return Err(e);
+            }
+        };
+        // Track the game before anything else can fail, or it would never
+        // be finished
+        if let Some(game) = tracked.game.as_mut() {
+            game.game_id = Some(game_id);
+            game.guild_id = Some(guild_session.guild_id);
+        }
+        drop(sessions);
+        self.game_writes.after_game_write(game_id, GameWrite::Started).await;
+
+        tracing::info!("Recording game {} for session {}", game_id, session_id);
+        Ok(())
+    }
+
+    async fn finish_game(
+        &self,
+        session_id: &str,
+        game_id: i32,
+        winner: Option<&str>,
+        players: Vec<NewGamePlayer>,
+    ) -> AppResult<()> {
+        let end_time = chrono::Utc::now().timestamp_millis() as f64 / 1000.0;
+        self.game.complete_game(game_id, end_time, winner, &players).await?;
+        self.game_writes.after_game_write(game_id, GameWrite::Ended).await;
+
+        tracing::info!("Recorded game {} for session {}", game_id, session_id);
+        Ok(())
+    }
+}
+
+/// Update the tracked session from a single message, returning any database
+/// write the message triggers
+fn apply_message(
+    tracked: &mut TrackedSession,
+    client_id: &str,
+    command: &str,
+    params: &Value,
+) -> Option<RecorderAction> {
+    // Players may rename themselves; everything else must come from the host
+    if command == "name" {
+        let index = params.get(0)?.as_u64()? as usize;
+        let name = params.get(1)?.as_str()?;
+        if !tracked.may_rename(client_id, index) {
+            return None;
+        }
+        if let Some(seat) = tracked.seats.get_mut(index) {
+            seat.name = name.to_string();
+        }
+        return None;
+    }
+
+    if !tracked.is_host(client_id) {
+        return None;
+    }
+
+    match command {
+        "gs" => {
+            let gamestate = params.get("gamestate")?.as_array()?;
+            let seats = gamestate
+                .iter()
+                .enumerate()
+                .map(|(index, player)| {
+                    let player_id = player.get("id").and_then(Value::as_str).unwrap_or_default();
+                    // Keep known roles for players who are still in the same seat
+                    let previous = tracked
+                        .seats
+                        .get(index)
+                        .filter(|seat| seat.player_id == player_id)
+                        .cloned()
+                        .unwrap_or_default();
+
+                    TrackedSeat {
+                        player_id: player_id.to_string(),
+                        name: player.get("name").and_then(Value::as_str).unwrap_or_default().to_string(),
+                        is_dead: player.get("isDead").and_then(Value::as_bool).unwrap_or(false),
+                        ..previous
+                    }
+                })
+                .collect();
+            tracked.seats = seats;
+            if let Some(is_night) = params.get("isNight").and_then(Value::as_bool) {
+                tracked.is_night = is_night;
+            }
+            if let Some(marked) = params.get("markedPlayer").and_then(Value::as_i64) {
+                tracked.marked = usize::try_from(marked).ok();
+            }
+            None
+        }
+        "isNight" => {
+            tracked.is_night = params.as_bool()?;
+            None
+        }
+        "marked" => {
+            // -1 clears the block
+            tracked.marked = usize::try_from(params.as_i64()?).ok();
+            None
+        }
+        "nomination" => {
+            // A null nomination ends the vote
+            let nomination = params.as_array()?;
+            let nominator = nomination.first()?.as_u64()? as usize;
+            let nominee = nomination.get(1)?.as_u64()? as usize;
+            let (game_id, guild_id) = tracked.recorded_game()?;
+            let data = json!({
+                "game_id": game_id,
+                "nominator": tracked.seat_json(nominator),
+                "nominee": tracked.seat_json(nominee),
+            });
+            Some(RecorderAction::Publish(StreamEvent::Nomination, guild_id, data))
+        }
+        "edition" => {
+            let edition = params.get("edition")?;
+            let id = edition.get("id").and_then(Value::as_str);
+            let name = edition.get("name").and_then(Value::as_str);
+            if edition.get("isOfficial").and_then(Value::as_bool) == Some(false) || name.is_some() {
+                tracked.script = Some("Custom Script".to_string());
+                tracked.custom_name = name.or(id).map(str::to_string);
+            } else {
+                tracked.script = id.map(str::to_string);
+                tracked.custom_name = None;
+            }
+
+            tracked.characters = params
+                .get("roles")
+                .and_then(Value::as_array)
+                .map(|roles| {
+                    roles
+                        .iter()
+                        .filter_map(|role| role.get("id").or_else(|| role.get(STRIPPED_ID_KEY))?.as_str())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default();
+            tracked.bootlegger = edition
+                .get("bootlegger")
+                .and_then(Value::as_array)
+                .map(|rules| rules.iter().filter_map(Value::as_str).map(str::to_string).collect())
+                .unwrap_or_default();
+
+            tracked.role_teams = params
+                .get("roles")
+                .and_then(Value::as_array)
+                .map(|roles| {
+                    roles
+                        .iter()
+                        .filter_map(|role| {
+                            let (id, team) = match role.get(STRIPPED_ID_KEY) {
+                                Some(id) => (
+                                    id.as_str()?,
+                                    role.get(STRIPPED_TEAM_KEY).and_then(Value::as_str).unwrap_or(STRIPPED_DEFAULT_TEAM),
+                                ),
+                                // Official characters are sent as just their id
+                                None => (role.get("id")?.as_str()?, role.get("team")?.as_str()?),
+                            };
+                            Some((id.to_string(), team.to_string()))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            None
+        }
+        "direct" => {
+            // Role distribution: { playerId: ["player", { index, property: "role", value }] }
+            let mut assigned = false;
+            for message in params.as_object()?.values() {
+                if message.get(0).and_then(Value::as_str) != Some("player") {
+                    continue;
+                }
+                let Some(update) = message.get(1) else { continue };
+                if update.get("property").and_then(Value::as_str) != Some("role") {
+                    continue;
+                }
+                let Some(index) = update.get("index").and_then(Value::as_u64) else { continue };
+                let role_id = update.get("value").and_then(Value::as_str).map(str::to_string);
+                tracked.assign_role(index as usize, role_id);
+                assigned = true;
+            }
+
+            if assigned && tracked.game.is_none() {
+                tracked.game = Some(TrackedGame {
+                    game_id: None,
+                    guild_id: None,
+                    external: false,
+                    has_deaths: false,
+                });
+                return Some(RecorderAction::Start);
+            }
+            None
+        }
+        "player" => {
+            let index = params.get("index")?.as_u64()? as usize;
+            let value = params.get("value")?;
+            match params.get("property")?.as_str()? {
+                "role" => {
+                    // An empty role means a traveller went back to an unknown role
+                    let role_id = value.as_str().filter(|id| !id.is_empty()).map(str::to_string);
+                    if role_id.is_some() {
+                        tracked.assign_role(index, role_id);
+                    }
+                }
+                "isDead" => {
+                    let is_dead = value.as_bool().unwrap_or(false);
+                    let was_dead = tracked.seats.get(index).is_some_and(|seat| seat.is_dead);
+                    if let Some(seat) = tracked.seats.get_mut(index) {
+                        seat.is_dead = is_dead;
+                    }
+                    if is_dead {
+                        if let Some(game) = tracked.game.as_mut() {
+                            game.has_deaths = true;
+                        }
+                    }
+
+                    if is_dead && !was_dead && !tracked.is_night && tracked.marked == Some(index) {
+                        let (game_id, guild_id) = tracked.recorded_game()?;
+                        let data = json!({
+                            "game_id": game_id,
+                            "player": tracked.seat_json(index),
+                        });
+                        return Some(RecorderAction::Publish(StreamEvent::Execution, guild_id, data));
+                    }
+                }
+                _ => {}
+            }
+            None
+        }
+        "swap" => {
+            let from = params.get(0)?.as_u64()? as usize;
+            let to = params.get(1)?.as_u64()? as usize;
+            if from < tracked.seats.len() && to < tracked.seats.len() {
+                tracked.seats.swap(from, to);
+            }
+            None
+        }
+        "move" => {
+            let from = params.get(0)?.as_u64()? as usize;
+            let to = params.get(1)?.as_u64()? as usize;
+            if from < tracked.seats.len() && to < tracked.seats.len() {
+                let seat = tracked.seats.remove(from);
+                tracked.seats.insert(to, seat);
+            }
+            None
+        }
+        "remove" => {
+            let index = params.as_u64()? as usize;
+            if index < tracked.seats.len() {
+                tracked.seats.remove(index);
+            }
+            None
+        }
+        "grimReveal" => {
+            if params.get("active").and_then(Value::as_bool) != Some(true) {
+                return None;
+            }
+            let game = tracked.game.take()?;
+            let game_id = game.game_id.filter(|_| !game.external)?;
+
+            let players: Vec<NewGamePlayer> = tracked
+                .seats
+                .iter()
+                .enumerate()
+                .map(|(index, seat)| NewGamePlayer {
+                    discord_id: tracked.discord_id(seat),
+                    player_name: seat.name.clone(),
+                    seat_number: index as i32 + 1,
+                    starting_role_id: seat.starting_role.clone(),
+                    starting_role_name: None,
+                    starting_team: tracked.team_of(&seat.starting_role),
+                    final_role_id: seat.final_role.clone(),
+                    final_role_name: None,
+                    final_team: tracked.team_of(&seat.final_role),
+                    survived: !seat.is_dead,
+                })
+                .collect();
+
+            // Roles are redistributed for the next game
+            for seat in tracked.seats.iter_mut() {
+                seat.starting_role = None;
+                seat.final_role = None;
+            }
+
+            let winner = winner(&players);
+            Some(RecorderAction::Finish(game_id, winner, players))
+        }
+        _ => None,
+    }
+}
+
+/// The winner as far as the revealed grimoire shows it: good once every demon
+/// is dead, evil once a demon lives with only two players alive. Games ending
+/// any other way, such as the Saint being executed, are left undecided for
+/// the storyteller to correct.
+fn winner(players: &[NewGamePlayer]) -> Option<&'static str> {
+    let is_demon = |player: &&NewGamePlayer| player.final_team.as_deref() == Some("demon");
+    let demons: Vec<&NewGamePlayer> = players.iter().filter(is_demon).collect();
+    if demons.is_empty() {
+        return None;
+    }
+    if demons.iter().all(|demon| !demon.survived) {
+        return Some("Good");
+    }
+
+    // Travellers don't count towards the players left alive
+    let alive = players
+        .iter()
+        .filter(|player| player.survived && player.final_team.as_deref() != Some("traveller"))
+        .count();
+    (alive <= 2).then_some("Evil")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A session hosted by `host`, with `alice` signed in as player `a`
+    fn session() -> TrackedSession {
+        let mut tracked = TrackedSession { host_client_id: Some("host".to_string()), ..Default::default() };
+        tracked.clients.insert(
+            "alice".to_string(),
+            TrackedClient { discord_user_id: 11, player_id: Some("a".to_string()), host_checked_at: None },
+        );
+        tracked.player_users.insert("a".to_string(), Some(11));
+        apply_message(
+            &mut tracked,
+            "host",
+            "gs",
+            &json!({ "gamestate": [
+                { "id": "a", "name": "Alice" },
+                { "id": "b", "name": "Bob" },
+                { "id": "c", "name": "Cat" },
+            ] }),
+        );
+        tracked
+    }
+
+    fn assign(index: usize, role: &str) -> Value {
+        json!({ "x": ["player", { "index": index, "property": "role", "value": role }] })
+    }
+
+    fn kill(tracked: &mut TrackedSession, index: usize) {
+        apply_message(tracked, "host", "player", &json!({ "index": index, "property": "isDead", "value": true }));
+    }
+
+    fn player(final_team: &str, survived: bool) -> NewGamePlayer {
+        NewGamePlayer {
+            discord_id: None,
+            player_name: String::new(),
+            seat_number: 1,
+            starting_role_id: None,
+            starting_role_name: None,
+            starting_team: None,
+            final_role_id: None,
+            final_role_name: None,
+            final_team: Some(final_team.to_string()),
+            survived,
+        }
+    }
+
+    #[test]
+    fn only_the_host_changes_the_grimoire() {
+        let mut tracked = session();
+        assert!(apply_message(&mut tracked, "alice", "direct", &assign(0, "imp")).is_none());
+        assert!(apply_message(&mut tracked, "alice", "remove", &json!(0)).is_none());
+        assert_eq!(tracked.seats.len(), 3);
+        assert!(tracked.game.is_none());
+    }
+
+    #[test]
+    fn players_only_rename_their_own_seat() {
+        let mut tracked = session();
+        apply_message(&mut tracked, "alice", "name", &json!([0, "Ally"]));
+        apply_message(&mut tracked, "alice", "name", &json!([1, "Not Bob"]));
+        apply_message(&mut tracked, "stranger", "name", &json!([2, "Not Cat"]));
+        apply_message(&mut tracked, "host", "name", &json!([2, "Kat"]));
+        // Out of range seats are ignored
+        apply_message(&mut tracked, "host", "name", &json!([9, "Nobody"]));
+
+        let names: Vec<&str> = tracked.seats.iter().map(|seat| seat.name.as_str()).collect();
+        assert_eq!(names, ["Ally", "Bob", "Kat"]);
+    }
+
+    #[test]
+    fn roles_after_the_first_death_only_change_the_final_role() {
+        let mut tracked = session();
+        let started = apply_message(&mut tracked, "host", "direct", &assign(0, "imp"));
+        assert!(matches!(started, Some(RecorderAction::Start)));
+        apply_message(&mut tracked, "host", "direct", &assign(1, "chef"));
+        // Starting the game only happens once
+        assert!(apply_message(&mut tracked, "host", "direct", &assign(2, "saint")).is_none());
+
+        kill(&mut tracked, 0);
+        apply_message(&mut tracked, "host", "player", &json!({ "index": 1, "property": "role", "value": "imp" }));
+
+        let roles: Vec<(Option<&str>, Option<&str>)> = tracked
+            .seats
+            .iter()
+            .map(|seat| (seat.starting_role.as_deref(), seat.final_role.as_deref()))
+            .collect();
+        assert_eq!(roles, [(Some("imp"), Some("imp")), (Some("chef"), Some("imp")), (Some("saint"), Some("saint"))]);
+    }
+
+    #[test]
+    fn reveal_finishes_the_recorded_game() {
+        let mut tracked = session();
+        apply_message(&mut tracked, "host", "direct", &assign(0, "imp"));
+        apply_message(&mut tracked, "host", "direct", &assign(1, "chef"));
+        apply_message(&mut tracked, "host", "direct", &assign(2, "saint"));
+        // Not saved yet, so there is nothing to finish
+        assert!(apply_message(&mut tracked, "host", "grimReveal", &json!({ "active": true })).is_none());
+
+        apply_message(&mut tracked, "host", "direct", &assign(0, "imp"));
+        tracked.game.as_mut().unwrap().game_id = Some(5);
+        kill(&mut tracked, 0);
+        assert!(apply_message(&mut tracked, "host", "grimReveal", &json!({ "active": false })).is_none());
+
+        let Some(RecorderAction::Finish(game_id, winner, players)) =
+            apply_message(&mut tracked, "host", "grimReveal", &json!({ "active": true }))
+        else {
+            panic!("reveal should finish the game");
+        };
+        assert_eq!((game_id, winner), (5, Some("Good")));
+        assert_eq!(players.len(), 3);
+        assert_eq!(players[0].discord_id, Some(11));
+        assert_eq!(players[0].final_team.as_deref(), Some("demon"));
+        assert!(!players[0].survived);
+        assert_eq!(players[2].starting_team.as_deref(), Some("outsider"));
+        assert!(tracked.game.is_none());
+        assert!(tracked.seats.iter().all(|seat| seat.starting_role.is_none()));
+    }
+
+    #[test]
+    fn externally_recorded_games_are_not_finished() {
+        let mut tracked = session();
+        tracked.game = Some(TrackedGame { game_id: Some(5), guild_id: Some(1), external: true, has_deaths: false });
+        assert!(apply_message(&mut tracked, "host", "grimReveal", &json!({ "active": true })).is_none());
+    }
+
+    #[test]
+    fn good_wins_once_every_demon_is_dead() {
+        let players = [player("demon", false), player("townsfolk", true), player("minion", true)];
+        assert_eq!(winner(&players), Some("Good"));
+    }
+
+    #[test]
+    fn evil_wins_with_two_players_left_not_counting_travellers() {
+        let players = [
+            player("demon", true),
+            player("townsfolk", true),
+            player("traveller", true),
+            player("outsider", false),
+        ];
+        assert_eq!(winner(&players), Some("Evil"));
+
+        let players = [player("demon", true), player("townsfolk", true), player("minion", true)];
+        assert_eq!(winner(&players), None);
+    }
+
+    #[test]
+    fn games_without_a_demon_are_undecided() {
+        assert_eq!(winner(&[player("townsfolk", true), player("minion", false)]), None);
+        assert_eq!(winner(&[]), None);
+    }
+}
@@ -0,0 +1,378 @@
+//! Checks custom scripts against the character database the grimoire uses,
+//! from the shared `grimlive-rules` crate.
+//!
+//! Ids are matched the way the grimoire matches them, ignoring case and
+//! anything but letters and digits. Problems the grimoire would trip over
+//! while setting the script up are errors; anything it copes with is a
+//! warning.
+
+use grimlive_rules::{Team, MAX_PLAYERS, MIN_PLAYERS};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+
+use crate::{
+    models::{IssueSeverity, ScriptIssue, ScriptIssueKind, ScriptJinx, ScriptTeamCounts, ScriptValidation},
+    services::scripts::custom_script_id,
+};
+
+/// Issues found so far
+struct Report {
+    issues: Vec<ScriptIssue>,
+}
+
+impl Report {
+    fn push(&mut self, severity: IssueSeverity, kind: ScriptIssueKind, message: String, entry: Option<usize>, character: Option<&str>) {
+        self.issues.push(ScriptIssue {
+            severity,
+            kind,
+            message,
+            entry: entry.map(|index| index as i32),
+            character: character.map(str::to_string),
+        });
+    }
+
+    fn error(&mut self, kind: ScriptIssueKind, message: String, entry: Option<usize>, character: Option<&str>) {
+        self.push(IssueSeverity::Error, kind, message, entry, character);
+    }
+
+    fn warning(&mut self, kind: ScriptIssueKind, message: String, entry: Option<usize>, character: Option<&str>) {
+        self.push(IssueSeverity::Warning, kind, message, entry, character);
+    }
+}
+
+/// Check a Script Tool script: an array of character ids and homebrew
+/// character objects, with an optional `_meta` entry
+pub fn validate_script(document: &Value) -> ScriptValidation {
+    let mut report = Report { issues: Vec::new() };
+    let mut name = None;
+    let mut bootlegger = Vec::new();
+    // Characters in script order, with their team
+    let mut characters: Vec<(String, Team)> = Vec::new();
+    // Jinxes homebrew characters declare, by character
+    let mut homebrew_jinxes: Vec<(String, String, String)> = Vec::new();
+
+    let Some(entries) = document.as_array() else {
+        report.error(ScriptIssueKind::InvalidEntry, "A script must be a JSON array".to_string(), None, None);
+        return finish(report, name, &characters, &bootlegger, Vec::new());
+    };
+
+    let mut seen_meta = false;
+    let mut seen = HashSet::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let (raw_id, object) = match entry {
+            Value::String(id) => (id.as_str(), None),
+            Value::Object(object) => match object.get("id").and_then(Value::as_str) {
+                Some(id) => (id, Some(object)),
+                None => {
+                    report.error(ScriptIssueKind::InvalidEntry, format!("Entry {} has no id", index), Some(index), None);
+                    continue;
+                }
+            },
+            _ => {
+                report.error(
+                    ScriptIssueKind::InvalidEntry,
+                    format!("Entry {} must be a character id or an object with an id", index),
+                    Some(index),
+                    None,
+                );
+                continue;
+            }
+        };
+
+        if raw_id == "_meta" {
+            if seen_meta {
+                report.error(ScriptIssueKind::InvalidMeta, "A script can only have one _meta entry".to_string(), Some(index), None);
+                continue;
+            }
+            seen_meta = true;
+            if let Some(meta) = object {
+                name = check_meta(&mut report, meta, index, &mut bootlegger);
+            }
+            continue;
+        }
+
+        let id = grimlive_rules::clean_id(raw_id);
+        if id.is_empty() {
+            report.error(ScriptIssueKind::InvalidEntry, format!("Entry {} has an empty id", index), Some(index), None);
+            continue;
+        }
+        if !seen.insert(id.clone()) {
+            report.warning(
+                ScriptIssueKind::DuplicateCharacter,
+                format!("{} is on the script more than once", raw_id),
+                Some(index),
+                Some(&id),
+            );
+            continue;
+        }
+
+        // The grimoire uses its own definition of official characters, even
+        // when a script redefines them
+        if let Some(team) = grimlive_rules::team_of(&id) {
+            characters.push((id, team));
+            continue;
+        }
+
+        let Some(object) = object.filter(|object| object.keys().any(|key| key != "id")) else {
+            report.error(
+                ScriptIssueKind::UnknownCharacter,
+                format!("{} is not a known character", raw_id),
+                Some(index),
+                Some(&id),
+            );
+            continue;
+        };
+
+        if let Some(team) = check_homebrew(&mut report, object, index, &id) {
+            characters.push((id.clone(), team));
+        }
+        if let Some(Value::Array(jinxes)) = object.get("jinxes") {
+            for jinx in jinxes {
+                let other = jinx.get("id").and_then(Value::as_str).map(grimlive_rules::clean_id);
+                let reason = jinx.get("reason").and_then(Value::as_str);
+                if let (Some(other), Some(reason)) = (other, reason) {
+                    homebrew_jinxes.push((id.clone(), other, reason.to_string()));
+                }
+            }
+        }
+    }
+
+    let on_script: HashSet<&str> = characters.iter().map(|(id, _)| id.as_str()).collect();
+    let mut jinxes = Vec::new();
+    for (id, _) in &characters {
+        for jinx in grimlive_rules::jinxes(id) {
+            if on_script.contains(jinx.id.as_str()) {
+                jinxes.push(ScriptJinx { character: id.clone(), other: jinx.id.clone(), reason: jinx.reason.clone() });
+            }
+        }
+    }
+    for (id, other, reason) in homebrew_jinxes {
+        if on_script.contains(id.as_str()) && on_script.contains(other.as_str()) {
+            jinxes.push(ScriptJinx { character: id, other, reason });
+        }
+    }
+
+    finish(report, name, &characters, &bootlegger, jinxes)
+}
+
+/// Check the `_meta` entry, returning the script's name and collecting its
+/// bootlegger rules
+fn check_meta(report: &mut Report, meta: &Map<String, Value>, index: usize, bootlegger: &mut Vec<String>) -> Option<String> {
+    for field in ["name", "author", "logo"] {
+        if meta.get(field).is_some_and(|value| !value.is_string() && !value.is_null()) {
+            report.error(ScriptIssueKind::InvalidMeta, format!("_meta.{} must be a string", field), Some(index), None);
+        }
+    }
+    for field in ["bootlegger", "firstNight", "otherNight"] {
+        match meta.get(field) {
+            None | Some(Value::Null) => {}
+            Some(Value::Array(values)) if values.iter().all(Value::is_string) => {
+                if field == "bootlegger" {
+                    bootlegger.extend(values.iter().filter_map(Value::as_str).map(str::to_string));
+                }
+            }
+            Some(_) => {
+                report.error(ScriptIssueKind::InvalidMeta, format!("_meta.{} must be a list of strings", field), Some(index), None);
+            }
+        }
+    }
+
+    let name = meta.get("name").and_then(Value::as_str).map(str::trim).filter(|name| !name.is_empty());
+    if name.is_none() {
+        report.warning(ScriptIssueKind::InvalidMeta, "_meta has no name".to_string(), Some(index), None);
+    }
+    name.map(str::to_string)
+}
+
+/// Check the fields the grimoire needs to show a homebrew character,
+/// returning its team if it has everything
+fn check_homebrew(report: &mut Report, object: &Map<String, Value>, index: usize, id: &str) -> Option<Team> {
+    for field in ["name", "team", "ability"] {
+        let present = object.get(field).and_then(Value::as_str).is_some_and(|value| !value.trim().is_empty());
+        if !present {
+            report.error(
+                ScriptIssueKind::MissingField,
+                format!("Homebrew character {} has no {}", id, field),
+                Some(index),
+                Some(id),
+            );
+        }
+    }
+
+    let missing = report.issues.iter().any(|issue| issue.entry == Some(index as i32));
+    let raw_team = object.get("team").and_then(Value::as_str)?;
+    let Ok(team) = raw_team.parse::<Team>() else {
+        report.error(
+            ScriptIssueKind::InvalidTeam,
+            format!("Homebrew character {} has an unknown team \"{}\"", id, raw_team),
+            Some(index),
+            Some(id),
+        );
+        return None;
+    };
+
+    // The grimoire leaves out characters it can't show
+    if missing {
+        return None;
+    }
+    Some(team)
+}
+
+/// Count teams, check them against the player counts and assemble the result
+fn finish(
+    mut report: Report,
+    name: Option<String>,
+    characters: &[(String, Team)],
+    bootlegger: &[String],
+    jinxes: Vec<ScriptJinx>,
+) -> ScriptValidation {
+    let mut counts = ScriptTeamCounts::default();
+    for (_, team) in characters {
+        match team {
+            Team::Townsfolk => counts.townsfolk += 1,
+            Team::Outsider => counts.outsider += 1,
+            Team::Minion => counts.minion += 1,
+            Team::Demon => counts.demon += 1,
+            Team::Traveller => counts.traveller += 1,
+            Team::Fabled => counts.fabled += 1,
+            Team::Loric => counts.loric += 1,
+        }
+    }
+
+    let max_players = (MIN_PLAYERS..=MAX_PLAYERS)
+        .take_while(|&players| {
+            grimlive_rules::team_counts(players).is_some_and(|needed| {
+                counts.townsfolk >= needed.townsfolk as i32
+                    && counts.outsider >= needed.outsider as i32
+                    && counts.minion >= needed.minion as i32
+                    && counts.demon >= needed.demon as i32
+            })
+        })
+        .last()
+        .map(|players| players as i32);
+
+    if !characters.is_empty() {
+        match max_players {
+            None => report.error(
+                ScriptIssueKind::TeamCount,
+                format!("The script doesn't have enough characters to set up a {} player game", MIN_PLAYERS),
+                None,
+                None,
+            ),
+            Some(max_players) if max_players < MAX_PLAYERS as i32 => report.warning(
+                ScriptIssueKind::TeamCount,
+                format!("The script only has enough characters for games of up to {} players", max_players),
+                None,
+                None,
+            ),
+            Some(_) => {}
+        }
+    } else if report.issues.iter().all(|issue| issue.kind != ScriptIssueKind::InvalidEntry) {
+        report.error(ScriptIssueKind::TeamCount, "The script has no characters".to_string(), None, None);
+    }
+
+    // Fabled and loric sit beside the grimoire rather than in it, so they
+    // aren't part of the character list games send
+    let ids: Vec<String> = characters
+        .iter()
+        .filter(|(_, team)| team.is_seated())
+        .map(|(id, _)| id.clone())
+        .collect();
+    ScriptValidation {
+        valid: report.issues.iter().all(|issue| issue.severity != IssueSeverity::Error),
+        name,
+        script_id: (!ids.is_empty()).then(|| custom_script_id(&ids, bootlegger)),
+        characters: ids,
+        team_counts: counts,
+        max_players,
+        issues: report.issues,
+        jinxes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const TROUBLE_BREWING: [&str; 22] = [
+        "washerwoman", "librarian", "investigator", "chef", "empath", "fortuneteller", "undertaker", "monk",
+        "ravenkeeper", "virgin", "slayer", "soldier", "mayor", "butler", "drunk", "recluse", "saint", "poisoner",
+        "spy", "scarletwoman", "baron", "imp",
+    ];
+
+    /// Trouble Brewing with a `_meta` entry, followed by `extra` entries
+    fn script(extra: &[Value]) -> Value {
+        let mut entries = vec![json!({ "id": "_meta", "name": "Brewing Again", "author": "Tester" })];
+        entries.extend(TROUBLE_BREWING.iter().map(|id| json!(id)));
+        entries.extend_from_slice(extra);
+        Value::Array(entries)
+    }
+
+    fn kinds(validation: &ScriptValidation, severity: IssueSeverity) -> Vec<ScriptIssueKind> {
+        validation.issues.iter().filter(|issue| issue.severity == severity).map(|issue| issue.kind).collect()
+    }
+
+    #[test]
+    fn valid_script_has_no_issues() {
+        let validation = validate_script(&script(&[]));
+        assert!(validation.valid);
+        assert!(validation.issues.is_empty(), "{:?}", validation.issues);
+        assert_eq!(validation.name.as_deref(), Some("Brewing Again"));
+        assert_eq!(validation.characters.len(), TROUBLE_BREWING.len());
+        assert_eq!(validation.max_players, Some(MAX_PLAYERS as i32));
+        assert_eq!(validation.script_id, Some(custom_script_id(&validation.characters, &[])));
+    }
+
+    #[test]
+    fn unknown_ids_are_errors() {
+        let validation = validate_script(&script(&[json!("notacharacter")]));
+        assert!(!validation.valid);
+        assert_eq!(kinds(&validation, IssueSeverity::Error), vec![ScriptIssueKind::UnknownCharacter]);
+        assert_eq!(validation.issues[0].character.as_deref(), Some("notacharacter"));
+        assert_eq!(validation.issues[0].entry, Some(TROUBLE_BREWING.len() as i32 + 1));
+    }
+
+    #[test]
+    fn missing_meta_only_leaves_the_name_out() {
+        let entries: Vec<Value> = TROUBLE_BREWING.iter().map(|id| json!(id)).collect();
+        let validation = validate_script(&Value::Array(entries));
+        assert!(validation.valid);
+        assert_eq!(validation.name, None);
+
+        let validation = validate_script(&json!([{ "id": "_meta", "name": 5 }, "imp"]));
+        assert!(kinds(&validation, IssueSeverity::Error).contains(&ScriptIssueKind::InvalidMeta));
+    }
+
+    #[test]
+    fn duplicate_characters_are_warnings() {
+        let validation = validate_script(&script(&[json!("Fortune_Teller")]));
+        assert!(validation.valid);
+        assert_eq!(kinds(&validation, IssueSeverity::Warning), vec![ScriptIssueKind::DuplicateCharacter]);
+        assert_eq!(validation.characters.len(), TROUBLE_BREWING.len());
+    }
+
+    #[test]
+    fn malformed_homebrew_is_left_out() {
+        let validation = validate_script(&script(&[
+            json!({ "id": "bard", "name": "Bard", "team": "townsfolk" }),
+            json!({ "id": "jester", "name": "Jester", "team": "clown", "ability": "Jokes" }),
+            json!({ "id": "mime", "name": "Mime", "team": "outsider", "ability": "Says nothing" }),
+        ]));
+        assert!(!validation.valid);
+        assert_eq!(
+            kinds(&validation, IssueSeverity::Error),
+            vec![ScriptIssueKind::MissingField, ScriptIssueKind::InvalidTeam]
+        );
+        assert!(!validation.characters.contains(&"bard".to_string()));
+        assert!(!validation.characters.contains(&"jester".to_string()));
+        assert!(validation.characters.contains(&"mime".to_string()));
+    }
+
+    #[test]
+    fn non_arrays_are_rejected() {
+        let validation = validate_script(&json!({ "name": "Not a script" }));
+        assert!(!validation.valid);
+        assert_eq!(kinds(&validation, IssueSeverity::Error), vec![ScriptIssueKind::InvalidEntry]);
+    }
+}
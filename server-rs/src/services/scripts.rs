@@ -0,0 +1,554 @@
+//! Script identity
+//!
+//! Games record a script id alongside their display name so statistics
+//! follow the script rather than whatever it was called:
+//! - official editions use their edition id (`tb`, `bmr`, `snv`)
+//! - custom scripts with a known character list use `custom-` and a hash of
+//!   their sorted character ids and bootlegger rules, so renaming a script
+//!   keeps its id and two scripts sharing a name stay apart
+//! - anything else falls back to `name-` and its normalized name
+//!
+//! Every name a script is played under is kept as an alias, so lookups by
+//! name resolve to the script last played under it. Official edition names
+//! always resolve to the edition, whatever custom scripts are called.
+//!
+//! Custom scripts can also be uploaded as Script Tool JSON to the script
+//! registry, which serves them back from a stable id and links them to the
+//! script id their games are recorded under. Uploading never renames a
+//! script or adds an alias; only games played on it do.
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::{Postgres, QueryBuilder};
+use std::time::Duration;
+
+use crate::{
+    database::Database,
+    error::{AppError, AppResult},
+    models::{IssueSeverity, RegisteredScript},
+    services::{aggregates::AggregateService, script_validation::validate_script},
+    utils::validation,
+};
+
+/// Official editions and the normalized names they go by
+const OFFICIAL_SCRIPTS: &[(&str, &[&str])] = &[
+    ("tb", &["tb", "troublebrewing"]),
+    ("bmr", &["bmr", "badmoonrising"]),
+    ("snv", &["snv", "sectsviolets", "sectsandviolets"]),
+];
+
+/// Placeholder name the client sends for every custom script
+const CUSTOM_SCRIPT: &str = "Custom Script";
+
+/// Largest script upload accepted, in bytes of JSON
+const MAX_DOCUMENT_BYTES: usize = 256 * 1024;
+
+/// Scripts one Discord user can keep in the registry
+const MAX_SCRIPTS_PER_OWNER: i64 = 100;
+
+/// Games written by other clients without a script id are identified in
+/// batches of this many
+const IDENTIFY_BATCH: i64 = 100;
+const IDENTIFY_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Columns of a `RegisteredScript`, selected from `registered_scripts`
+const REGISTERED_SCRIPT_COLUMNS: &str = "registered_scripts.id, registered_scripts.owner_discord_id,
+    registered_scripts.script_id, registered_scripts.name, registered_scripts.author,
+    (SELECT COUNT(*) FROM games
+     WHERE games.script_id = registered_scripts.script_id
+       AND games.is_active = false AND games.winner IS DISTINCT FROM 'Cancelled') AS games_played,
+    registered_scripts.created_at, registered_scripts.updated_at";
+
+/// Append a condition matching games on a script id, or on any script
+/// played under a name
+pub fn push_script_filter(query: &mut QueryBuilder<'_, Postgres>, script: &str) {
+    push_script_match(query, "games.script_id", script);
+}
+
+/// Append a condition matching a script id column on a script id, or on any
+/// script played under a name
+fn push_script_match(query: &mut QueryBuilder<'_, Postgres>, column: &str, script: &str) {
+    if let Some(official) = official_script_id(script) {
+        query.push(format!(" AND {} = ", column)).push_bind(official);
+        return;
+    }
+
+    query.push(format!(" AND {} IN (SELECT script_id FROM scripts WHERE script_id = ", column));
+    query.push_bind(script.to_string());
+    query.push(" UNION SELECT script_id FROM script_aliases WHERE alias = ");
+    query.push_bind(normalize_name(script)).push(")");
+}
+
+/// Lowercase letters and digits only, so "Sects & Violets", "sects-violets"
+/// and "SECTS AND VIOLETS" can be matched. Letters and digits of any script
+/// are kept, so names written in other alphabets stay apart.
+pub fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Id of a script known only by its name. Names without any letters or
+/// digits, such as one made only of emoji, use a hash of the name instead.
+fn name_script_id(name: &str) -> String {
+    let normalized = normalize_name(name);
+    if !normalized.is_empty() {
+        return format!("name-{}", normalized);
+    }
+
+    let digest = format!("{:x}", Sha256::digest(name.trim().as_bytes()));
+    format!("name-{}", &digest[..16])
+}
+
+/// Edition id of an official script name or id
+pub fn official_script_id(name: &str) -> Option<&'static str> {
+    let normalized = normalize_name(name);
+    OFFICIAL_SCRIPTS
+        .iter()
+        .find(|(_, aliases)| aliases.contains(&normalized.as_str()))
+        .map(|(id, _)| *id)
+}
+
+/// Normalized, sorted and deduplicated character ids of a script
+pub fn normalize_characters(characters: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = characters
+        .iter()
+        .map(|id| grimlive_rules::clean_id(id))
+        .filter(|id| !id.is_empty())
+        .collect();
+    normalized.sort_unstable();
+    normalized.dedup();
+    normalized
+}
+
+/// Content id of a custom script. Display metadata such as the name, author
+/// and logo is left out; bootlegger rules change how the script plays, so
+/// they are part of it.
+pub fn custom_script_id(characters: &[String], bootlegger: &[String]) -> String {
+    let mut rules: Vec<&str> = bootlegger.iter().map(|rule| rule.trim()).filter(|rule| !rule.is_empty()).collect();
+    rules.sort_unstable();
+
+    let mut hasher = Sha256::new();
+    hasher.update(normalize_characters(characters).join(","));
+    hasher.update("\n");
+    hasher.update(rules.join("\n"));
+    let digest = format!("{:x}", hasher.finalize());
+    format!("custom-{}", &digest[..32])
+}
+
+/// How a game described its script
+pub struct ScriptDescription<'a> {
+    pub script: &'a str,
+    pub custom_name: Option<&'a str>,
+    /// Character ids, when the client sent the script's contents
+    pub characters: &'a [String],
+    pub bootlegger: &'a [String],
+}
+
+impl ScriptDescription<'_> {
+    /// Name the script is shown under
+    fn display_name(&self) -> &str {
+        match self.custom_name.filter(|name| !name.is_empty()) {
+            Some(name) if self.script == CUSTOM_SCRIPT => name,
+            _ => self.script,
+        }
+    }
+}
+
+/// An uploaded Script Tool script: an array of character ids and homebrew
+/// character objects, with a `_meta` entry naming it
+pub struct ScriptDocument {
+    pub name: String,
+    pub author: Option<String>,
+    /// Characters the grimoire seats, as games played on the script send them
+    pub characters: Vec<String>,
+    pub bootlegger: Vec<String>,
+    /// The document as stored and served back
+    pub json: String,
+}
+
+impl ScriptDocument {
+    /// Check a document the way `POST /api/v1/scripts/validate` does,
+    /// rejecting it on the first error, and pull out what identifies it
+    pub fn parse(document: &Value) -> AppResult<Self> {
+        let json = document.to_string();
+        if json.len() > MAX_DOCUMENT_BYTES {
+            return Err(AppError::Validation(format!(
+                "Scripts can be at most {} KB", MAX_DOCUMENT_BYTES / 1024
+            )));
+        }
+
+        let validation = validate_script(document);
+        if let Some(issue) = validation.issues.iter().find(|issue| issue.severity == IssueSeverity::Error) {
+            return Err(AppError::Validation(issue.message.clone()));
+        }
+
+        let meta = document
+            .as_array()
+            .and_then(|entries| entries.iter().find(|entry| entry.get("id").and_then(Value::as_str) == Some("_meta")));
+        let name = validation.name
+            .ok_or_else(|| AppError::Validation("A script needs a _meta entry with its name".to_string()))?;
+        let author = meta
+            .and_then(|meta| meta.get("author"))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        let bootlegger: Vec<String> = meta
+            .and_then(|meta| meta.get("bootlegger"))
+            .and_then(Value::as_array)
+            .map(|rules| rules.iter().filter_map(Value::as_str).map(str::to_string).collect())
+            .unwrap_or_default();
+
+        if name.len() > 100 {
+            return Err(AppError::Validation("Script names must be 1-100 characters".to_string()));
+        }
+        if author.as_ref().is_some_and(|author| author.len() > 100) {
+            return Err(AppError::Validation("Script authors must be at most 100 characters".to_string()));
+        }
+        validation::validate_script_contents(&validation.characters, &bootlegger)?;
+
+        Ok(Self { name, author, characters: validation.characters, bootlegger, json })
+    }
+}
+
+#[derive(Clone)]
+pub struct ScriptService {
+    db: Database,
+}
+
+impl ScriptService {
+    pub fn new(db: Database) -> Self {
+        let service = Self { db };
+
+        // Spawn identification task
+        let worker = service.clone();
+        tokio::spawn(async move {
+            let aggregates = AggregateService::new(worker.db.clone());
+            let mut interval = tokio::time::interval(IDENTIFY_INTERVAL);
+            loop {
+                interval.tick().await;
+                loop {
+                    match worker.identify_unknown_games(&aggregates).await {
+                        // A full batch means more may be waiting
+                        Ok(identified) if identified as i64 == IDENTIFY_BATCH => continue,
+                        Ok(_) => {}
+                        Err(e) => tracing::warn!("Failed to identify game scripts: {}", e),
+                    }
+                    break;
+                }
+            }
+        });
+
+        service
+    }
+
+    /// Identify a batch of games written without a script id, such as those
+    /// of the Grimkeeper bot, from the name they were played under. Returns
+    /// how many were identified.
+    async fn identify_unknown_games(&self, aggregates: &AggregateService) -> AppResult<usize> {
+        let games: Vec<(i32, String, Option<String>, bool)> = sqlx::query_as(
+            "SELECT game_id, script, custom_name, is_active FROM games
+             WHERE script_id IS NULL
+             ORDER BY game_id
+             LIMIT $1"
+        )
+        .bind(IDENTIFY_BATCH)
+        .fetch_all(&self.db.pool)
+        .await?;
+
+        for (game_id, script, custom_name, is_active) in &games {
+            let script_id = self
+                .identify(&ScriptDescription {
+                    script,
+                    custom_name: custom_name.as_deref(),
+                    characters: &[],
+                    bootlegger: &[],
+                })
+                .await?;
+            sqlx::query("UPDATE games SET script_id = $2 WHERE game_id = $1 AND script_id IS NULL")
+                .bind(game_id)
+                .bind(&script_id)
+                .execute(&self.db.pool)
+                .await?;
+
+            // Script stats only count games once they are identified
+            if !is_active {
+                aggregates.refresh_game(*game_id).await?;
+            }
+        }
+
+        Ok(games.len())
+    }
+
+    /// Work out the id of the script a game is played on, registering the
+    /// script and the name it was played under
+    pub async fn identify(&self, description: &ScriptDescription<'_>) -> AppResult<String> {
+        let name = description.display_name();
+        let official = official_script_id(description.script);
+
+        let (script_id, characters) = match official {
+            Some(id) => (id.to_string(), None),
+            None if !description.characters.is_empty() => (
+                custom_script_id(description.characters, description.bootlegger),
+                Some(normalize_characters(description.characters)),
+            ),
+            None => (name_script_id(name), None),
+        };
+
+        let mut tx = self.db.pool.begin().await?;
+
+        // Official scripts keep their canonical name
+        sqlx::query(
+            "INSERT INTO scripts (script_id, name, characters)
+             VALUES ($1, $2, $3)
+             ON CONFLICT (script_id) DO UPDATE SET
+                name = CASE WHEN scripts.is_official THEN scripts.name ELSE EXCLUDED.name END,
+                last_seen_at = CURRENT_TIMESTAMP"
+        )
+        .bind(&script_id)
+        .bind(name)
+        .bind(&characters)
+        .execute(&mut *tx)
+        .await?;
+
+        // Custom scripts named after an official edition don't get to answer
+        // to its name
+        let alias = normalize_name(name);
+        if !alias.is_empty() && official_script_id(name).is_none_or(|official| official == script_id) {
+            sqlx::query(
+                "INSERT INTO script_aliases (alias, script_id)
+                 VALUES ($1, $2)
+                 ON CONFLICT (alias, script_id) DO UPDATE SET last_seen_at = CURRENT_TIMESTAMP"
+            )
+            .bind(&alias)
+            .bind(&script_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(script_id)
+    }
+
+    /// Resolve a script id or a name it was played under to a script id. A
+    /// name shared by several scripts resolves to the one played most
+    /// recently.
+    pub async fn resolve(&self, script: &str) -> AppResult<Option<String>> {
+        if let Some(official) = official_script_id(script) {
+            return Ok(Some(official.to_string()));
+        }
+
+        let script_id = sqlx::query_scalar::<_, String>(
+            "SELECT script_id FROM (
+                SELECT script_id, 0 AS priority, last_seen_at FROM scripts WHERE script_id = $1
+                UNION ALL
+                SELECT script_id, 1, last_seen_at FROM script_aliases WHERE alias = $2
+             ) candidates
+             ORDER BY priority, last_seen_at DESC
+             LIMIT 1"
+        )
+        .bind(script)
+        .bind(normalize_name(script))
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        Ok(script_id)
+    }
+
+    /// Store an uploaded script for a Discord user under a new registry id
+    pub async fn register(&self, owner_discord_id: i64, document: &ScriptDocument) -> AppResult<RegisteredScript> {
+        let (count,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM registered_scripts WHERE owner_discord_id = $1"
+        )
+        .bind(owner_discord_id)
+        .fetch_one(&self.db.pool)
+        .await?;
+
+        if count >= MAX_SCRIPTS_PER_OWNER {
+            return Err(AppError::Conflict(format!(
+                "At most {} scripts can be uploaded per user", MAX_SCRIPTS_PER_OWNER
+            )));
+        }
+
+        let script_id = self.identify_document(document).await?;
+        let id = uuid::Uuid::new_v4().simple().to_string()[..16].to_string();
+        sqlx::query(
+            "INSERT INTO registered_scripts (id, owner_discord_id, script_id, name, author, document)
+             VALUES ($1, $2, $3, $4, $5, $6)"
+        )
+        .bind(&id)
+        .bind(owner_discord_id)
+        .bind(&script_id)
+        .bind(&document.name)
+        .bind(&document.author)
+        .bind(&document.json)
+        .execute(&self.db.pool)
+        .await?;
+
+        self.get_registered(&id).await?
+            .ok_or_else(|| AppError::Internal("Uploaded script disappeared".to_string()))
+    }
+
+    /// Replace an uploaded script's contents, keeping its registry id.
+    /// Returns `None` if there is no such script.
+    pub async fn replace_registered(&self, id: &str, document: &ScriptDocument) -> AppResult<Option<RegisteredScript>> {
+        let script_id = self.identify_document(document).await?;
+        let result = sqlx::query(
+            "UPDATE registered_scripts
+             SET script_id = $2, name = $3, author = $4, document = $5, updated_at = CURRENT_TIMESTAMP
+             WHERE id = $1"
+        )
+        .bind(id)
+        .bind(&script_id)
+        .bind(&document.name)
+        .bind(&document.author)
+        .bind(&document.json)
+        .execute(&self.db.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        self.get_registered(id).await
+    }
+
+    /// Remove an uploaded script. Games played on it keep their script id.
+    /// Returns false if there is no such script.
+    pub async fn delete_registered(&self, id: &str) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM registered_scripts WHERE id = $1")
+            .bind(id)
+            .execute(&self.db.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_registered(&self, id: &str) -> AppResult<Option<RegisteredScript>> {
+        let script = sqlx::query_as::<_, RegisteredScript>(&format!(
+            "SELECT {} FROM registered_scripts WHERE id = $1",
+            REGISTERED_SCRIPT_COLUMNS
+        ))
+        .bind(id)
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        Ok(script)
+    }
+
+    /// The JSON of an uploaded script
+    pub async fn get_document(&self, id: &str) -> AppResult<Option<String>> {
+        let document = sqlx::query_scalar::<_, String>("SELECT document FROM registered_scripts WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.db.pool)
+            .await?;
+
+        Ok(document)
+    }
+
+    /// List uploaded scripts, most recently updated first, optionally only
+    /// one user's or those of one script
+    pub async fn list_registered(
+        &self,
+        owner_discord_id: Option<i64>,
+        script: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> AppResult<Vec<RegisteredScript>> {
+        let mut query = QueryBuilder::<Postgres>::new("SELECT ");
+        query.push(REGISTERED_SCRIPT_COLUMNS);
+        query.push(" FROM registered_scripts WHERE true");
+        if let Some(owner_discord_id) = owner_discord_id {
+            query.push(" AND registered_scripts.owner_discord_id = ").push_bind(owner_discord_id);
+        }
+        if let Some(script) = script {
+            push_script_match(&mut query, "registered_scripts.script_id", script);
+        }
+        query.push(" ORDER BY registered_scripts.updated_at DESC, registered_scripts.id");
+        query.push(" LIMIT ").push_bind(limit);
+        query.push(" OFFSET ").push_bind(offset);
+
+        let scripts = query.build_query_as::<RegisteredScript>()
+            .fetch_all(&self.db.pool)
+            .await?;
+
+        Ok(scripts)
+    }
+
+    /// Work out the script id games played on an uploaded script are
+    /// recorded under. The script is added if nothing has been played on it
+    /// yet, but an existing script keeps its name and aliases: whoever
+    /// uploads a script doesn't get to decide what it's looked up by.
+    async fn identify_document(&self, document: &ScriptDocument) -> AppResult<String> {
+        let script_id = custom_script_id(&document.characters, &document.bootlegger);
+
+        sqlx::query(
+            "INSERT INTO scripts (script_id, name, characters)
+             VALUES ($1, $2, $3)
+             ON CONFLICT (script_id) DO NOTHING"
+        )
+        .bind(&script_id)
+        .bind(&document.name)
+        .bind(normalize_characters(&document.characters))
+        .execute(&self.db.pool)
+        .await?;
+
+        Ok(script_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn character_order_and_duplicates_keep_the_id() {
+        let id = custom_script_id(&ids(&["washerwoman", "imp", "chef"]), &[]);
+        assert_eq!(custom_script_id(&ids(&["chef", "imp", "washerwoman"]), &[]), id);
+        assert_eq!(custom_script_id(&ids(&["Imp", "chef", "washer_woman", "chef"]), &[]), id);
+        assert!(id.starts_with("custom-"));
+    }
+
+    #[test]
+    fn bootlegger_rules_change_the_id() {
+        let characters = ids(&["washerwoman", "imp", "chef"]);
+        let plain = custom_script_id(&characters, &[]);
+        let bootlegged = custom_script_id(&characters, &ids(&["The Imp may choose two players"]));
+        assert_ne!(plain, bootlegged);
+        // Rule order and surrounding whitespace don't matter
+        assert_eq!(
+            custom_script_id(&characters, &ids(&["b", " a "])),
+            custom_script_id(&characters, &ids(&["a", "b", ""]))
+        );
+    }
+
+    #[test]
+    fn official_names_map_to_editions() {
+        assert_eq!(official_script_id("Sects & Violets"), Some("snv"));
+        assert_eq!(official_script_id("SECTS AND VIOLETS"), Some("snv"));
+        assert_eq!(official_script_id("Trouble Brewing"), Some("tb"));
+        assert_eq!(official_script_id("bmr"), Some("bmr"));
+        assert_eq!(official_script_id("Sects & Violence"), None);
+    }
+
+    #[test]
+    fn non_latin_names_stay_distinct() {
+        let cyrillic = name_script_id("Тёмная ночь");
+        let japanese = name_script_id("暗い夜");
+        assert_eq!(cyrillic, "name-тёмнаяночь");
+        assert_ne!(cyrillic, japanese);
+        assert_ne!(japanese, name_script_id("明るい朝"));
+    }
+
+    #[test]
+    fn emoji_only_names_are_hashed() {
+        let moon = name_script_id("🌙🌙");
+        assert!(moon.starts_with("name-"));
+        assert_eq!(moon.len(), "name-".len() + 16);
+        assert_eq!(name_script_id(" 🌙🌙 "), moon);
+        assert_ne!(name_script_id("🩸"), moon);
+    }
+}
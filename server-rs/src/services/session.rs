@@ -1,11 +1,10 @@
-use crate::{database::Database, error::AppResult, models::WebSession};
+use crate::{database::Database, error::AppResult, models::{Session, WebSession}};
 
-#[allow(dead_code)]
+#[derive(Clone)]
 pub struct SessionService {
     db: Database,
 }
 
-#[allow(dead_code)]
 impl SessionService {
     pub fn new(db: Database) -> Self {
         Self { db }
@@ -13,7 +12,7 @@ impl SessionService {
 
     pub async fn get_session_by_token(&self, token: &str) -> AppResult<Option<WebSession>> {
         let session = sqlx::query_as::<_, WebSession>(
-            "SELECT session_id, token, discord_user_id, created_at, expires_at 
+            "SELECT session_id, token, discord_user_id, created_at, expires_at, verified 
              FROM web_sessions 
              WHERE token = $1 AND expires_at > EXTRACT(epoch FROM now())"
         )
@@ -24,7 +23,28 @@ impl SessionService {
         Ok(session)
     }
 
-    pub async fn create_session(&self, token: &str, discord_user_id: i64, expires_in_seconds: i64) -> AppResult<WebSession> {
+    pub async fn get_session_by_id(&self, session_id: &str) -> AppResult<Option<WebSession>> {
+        let session = sqlx::query_as::<_, WebSession>(
+            "SELECT session_id, token, discord_user_id, created_at, expires_at, verified 
+             FROM web_sessions 
+             WHERE session_id = $1 AND expires_at > EXTRACT(epoch FROM now())"
+        )
+        .bind(session_id)
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        Ok(session)
+    }
+
+    /// Create a web session for a Discord user. `verified` marks sessions
+    /// whose user was proven through Discord OAuth.
+    pub async fn create_session(
+        &self,
+        token: &str,
+        discord_user_id: i64,
+        verified: bool,
+        expires_in_seconds: i64,
+    ) -> AppResult<WebSession> {
         let session_id = uuid::Uuid::new_v4().to_string();
         let current_time = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -33,21 +53,76 @@ impl SessionService {
         let expires_at = current_time + expires_in_seconds;
 
         let session = sqlx::query_as::<_, WebSession>(
-            "INSERT INTO web_sessions (session_id, token, discord_user_id, created_at, expires_at)
-             VALUES ($1, $2, $3, $4, $5)
-             RETURNING session_id, token, discord_user_id, created_at, expires_at"
+            "INSERT INTO web_sessions (session_id, token, discord_user_id, created_at, expires_at, verified)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             RETURNING session_id, token, discord_user_id, created_at, expires_at, verified"
         )
         .bind(&session_id)
         .bind(token)
         .bind(discord_user_id)
         .bind(current_time)
         .bind(expires_at)
+        .bind(verified)
         .fetch_one(&self.db.pool)
         .await?;
 
         Ok(session)
     }
 
+    /// Link a web session to a Discord user. Nothing proves the user, so a
+    /// session switching to a different user is no longer verified. Returns
+    /// `None` if the session doesn't exist.
+    pub async fn link_discord_user(&self, session_id: &str, discord_user_id: i64) -> AppResult<Option<WebSession>> {
+        let session = sqlx::query_as::<_, WebSession>(
+            "UPDATE web_sessions
+             SET discord_user_id = $2, verified = COALESCE(verified AND discord_user_id = $2, false)
+             WHERE session_id = $1
+             RETURNING session_id, token, discord_user_id, created_at, expires_at, verified"
+        )
+        .bind(session_id)
+        .bind(discord_user_id)
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        Ok(session)
+    }
+
+    /// Get the Discord guild sessions a user is currently storytelling
+    pub async fn get_guild_sessions_for_storyteller(&self, discord_user_id: i64) -> AppResult<Vec<Session>> {
+        let sessions = sqlx::query_as::<_, Session>(
+            "SELECT guild_id, category_id, destination_channel_id, grimoire_link, 
+                    exception_channel_id, announce_channel_id, active_game_id, 
+                    created_at, last_active, storyteller_user_id, session_code 
+             FROM sessions 
+             WHERE storyteller_user_id = $1 
+             ORDER BY last_active DESC"
+        )
+        .bind(discord_user_id)
+        .fetch_all(&self.db.pool)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    /// Get the Discord guild session linked to a live session code
+    pub async fn get_guild_session_by_code(&self, session_code: &str) -> AppResult<Option<Session>> {
+        let session = sqlx::query_as::<_, Session>(
+            "SELECT guild_id, category_id, destination_channel_id, grimoire_link, 
+                    exception_channel_id, announce_channel_id, active_game_id, 
+                    created_at, last_active, storyteller_user_id, session_code 
+             FROM sessions 
+             WHERE session_code = $1 
+             ORDER BY last_active DESC 
+             LIMIT 1"
+        )
+        .bind(session_code)
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        Ok(session)
+    }
+
+    #[allow(dead_code)]
     pub async fn cleanup_expired_sessions(&self) -> AppResult<u64> {
         let result = sqlx::query(
             "DELETE FROM web_sessions WHERE expires_at < EXTRACT(epoch FROM now())"
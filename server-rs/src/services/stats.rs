@@ -0,0 +1,323 @@
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::{
+    database::Database,
+    error::AppResult,
+    models::{
+        Alignment, GameFilter, HeadToHead, Leaderboard, LeaderboardEntry, LeaderboardMetric, Pairing,
+        PlayerPairing, StatsScope, StorytellerScript, StorytellerStats, StorytellerTotals,
+        TimeBucket, Timeseries, TimeseriesPoint,
+    },
+    services::game::{push_game_filters, SEAT_ALIGNMENT_SQL},
+};
+
+/// Aggregate statistics across players and games
+pub struct StatsService {
+    db: Database,
+}
+
+impl StatsService {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+
+    /// Rank linked players by a metric over decided games in scope. Players
+    /// need `min_games` games (on the relevant team, for team win rates and
+    /// ratings) to be ranked.
+    pub async fn get_leaderboard(
+        &self,
+        metric: LeaderboardMetric,
+        scope: &StatsScope,
+        min_games: i64,
+        limit: i64,
+    ) -> AppResult<Leaderboard> {
+        let mut query = leaderboard_query(metric, scope, min_games);
+        query.push(" LIMIT ").push_bind(limit);
+
+        let entries = query
+            .build_query_as::<LeaderboardEntry>()
+            .fetch_all(&self.db.pool)
+            .await?;
+
+        Ok(Leaderboard {
+            metric,
+            min_games,
+            entries,
+        })
+    }
+
+    /// A player's most frequent teammates or opponents over decided games in
+    /// scope, optionally only games the player finished on one side
+    pub async fn get_pairings(
+        &self,
+        discord_id: i64,
+        pairing: Pairing,
+        team: Option<Alignment>,
+        scope: &StatsScope,
+        min_games: i64,
+        limit: i64,
+    ) -> AppResult<Vec<PlayerPairing>> {
+        let comparison = match pairing {
+            Pairing::Teammates => "=",
+            Pairing::Opponents => "<>",
+        };
+
+        let mut query = QueryBuilder::<Postgres>::new(format!(
+            "SELECT
+                other.discord_id,
+                MAX(other.player_name) AS player_name,
+                COUNT(*) AS games,
+                COUNT(*) FILTER (WHERE gp.winning_team) AS wins,
+                COUNT(*) FILTER (WHERE NOT gp.winning_team) AS losses,
+                ROUND(AVG(CASE WHEN gp.winning_team THEN 1.0 WHEN NOT gp.winning_team THEN 0.0 END) * 100, 2)::float8 AS win_rate
+            FROM game_players gp
+            JOIN game_players other ON other.game_id = gp.game_id
+                AND other.discord_id IS NOT NULL
+                AND other.discord_id <> gp.discord_id
+            JOIN games ON games.game_id = gp.game_id
+            WHERE games.is_active = false
+              AND games.winner IN ('Good', 'Evil')
+              AND {player} {comparison} {other}",
+            player = SEAT_ALIGNMENT_SQL,
+            other = seat_alignment_of("other"),
+            comparison = comparison,
+        ));
+        query.push(" AND gp.discord_id = ").push_bind(discord_id);
+        if let Some(team) = team {
+            query.push(format!(" AND {} = ", SEAT_ALIGNMENT_SQL)).push_bind(team.as_str());
+        }
+        push_game_filters(&mut query, &GameFilter::from(scope));
+        query.push(" GROUP BY other.discord_id HAVING COUNT(*) >= ").push_bind(min_games);
+        query.push(" ORDER BY games DESC, win_rate DESC NULLS LAST, other.discord_id LIMIT ").push_bind(limit);
+
+        let pairings = query
+            .build_query_as::<PlayerPairing>()
+            .fetch_all(&self.db.pool)
+            .await?;
+
+        Ok(pairings)
+    }
+
+    /// How two players fare together and against each other over decided
+    /// games in scope
+    pub async fn get_head_to_head(&self, discord_id: i64, other_id: i64, scope: &StatsScope) -> AppResult<HeadToHead> {
+        let win_rate = |condition: &str| {
+            format!(
+                "ROUND(AVG(CASE WHEN gp.winning_team THEN 1.0 WHEN NOT gp.winning_team THEN 0.0 END) \
+                 FILTER (WHERE {}) * 100, 2)::float8",
+                condition
+            )
+        };
+        let player = SEAT_ALIGNMENT_SQL;
+        let other = seat_alignment_of("other");
+        let same = format!("{} = {}", player, other);
+        let opposed = format!("{} <> {}", player, other);
+
+        let mut query = QueryBuilder::<Postgres>::new(format!(
+            "SELECT
+                {discord_id}::bigint AS discord_id,
+                {other_id}::bigint AS other_discord_id,
+                COUNT(*) AS games_together,
+                COUNT(*) FILTER (WHERE {same}) AS teammate_games,
+                COUNT(*) FILTER (WHERE {same} AND gp.winning_team) AS teammate_wins,
+                {same_rate} AS teammate_win_rate,
+                COUNT(*) FILTER (WHERE {same} AND {player} = 'Good') AS good_teammate_games,
+                COUNT(*) FILTER (WHERE {same} AND {player} = 'Good' AND gp.winning_team) AS good_teammate_wins,
+                COUNT(*) FILTER (WHERE {same} AND {player} = 'Evil') AS evil_teammate_games,
+                COUNT(*) FILTER (WHERE {same} AND {player} = 'Evil' AND gp.winning_team) AS evil_teammate_wins,
+                COUNT(*) FILTER (WHERE {opposed}) AS opponent_games,
+                COUNT(*) FILTER (WHERE {opposed} AND gp.winning_team) AS opponent_wins,
+                COUNT(*) FILTER (WHERE {opposed} AND NOT gp.winning_team) AS opponent_losses,
+                {opposed_rate} AS opponent_win_rate
+            FROM game_players gp
+            JOIN game_players other ON other.game_id = gp.game_id
+            JOIN games ON games.game_id = gp.game_id
+            WHERE games.is_active = false
+              AND games.winner IN ('Good', 'Evil')",
+            same_rate = win_rate(&same),
+            opposed_rate = win_rate(&opposed),
+        ));
+        query.push(" AND gp.discord_id = ").push_bind(discord_id);
+        query.push(" AND other.discord_id = ").push_bind(other_id);
+        push_game_filters(&mut query, &GameFilter::from(scope));
+
+        let head_to_head = query
+            .build_query_as::<HeadToHead>()
+            .fetch_one(&self.db.pool)
+            .await?;
+
+        Ok(head_to_head)
+    }
+
+    /// Aggregate the completed and cancelled games a storyteller ran in scope.
+    /// Returns `None` if they haven't run any.
+    pub async fn get_storyteller_stats(&self, discord_id: i64, scope: &StatsScope) -> AppResult<Option<StorytellerStats>> {
+        let mut query = QueryBuilder::<Postgres>::new(
+            "SELECT
+                COUNT(*) FILTER (WHERE winner IS DISTINCT FROM 'Cancelled') AS games_run,
+                COUNT(*) FILTER (WHERE winner = 'Cancelled') AS cancelled_games,
+                ROUND(AVG(CASE WHEN winner = 'Cancelled' THEN 1.0 ELSE 0.0 END) * 100, 2)::float8 AS cancellation_rate,
+                COUNT(*) FILTER (WHERE winner = 'Good') AS good_wins,
+                COUNT(*) FILTER (WHERE winner = 'Evil') AS evil_wins,
+                ROUND(AVG(CASE WHEN winner = 'Evil' THEN 1.0 WHEN winner = 'Good' THEN 0.0 END) * 100, 2)::float8 AS evil_win_rate,
+                ROUND(AVG(player_count) FILTER (WHERE winner IS DISTINCT FROM 'Cancelled'), 2)::float8 AS average_player_count,
+                ROUND(AVG(end_time - start_time) FILTER (WHERE winner IS DISTINCT FROM 'Cancelled')::numeric, 1)::float8
+                    AS average_duration_seconds
+            FROM games
+            WHERE is_active = false AND storyteller_user_id = "
+        );
+        query.push_bind(discord_id);
+        push_game_filters(&mut query, &GameFilter::from(scope));
+
+        let totals = query
+            .build_query_as::<StorytellerTotals>()
+            .fetch_one(&self.db.pool)
+            .await?;
+
+        if totals.games_run + totals.cancelled_games == 0 {
+            return Ok(None);
+        }
+
+        let mut query = QueryBuilder::<Postgres>::new(
+            "SELECT
+                games.script_id,
+                MAX(scripts.name) AS script,
+                COUNT(*) AS games,
+                COUNT(*) FILTER (WHERE winner = 'Good') AS good_wins,
+                COUNT(*) FILTER (WHERE winner = 'Evil') AS evil_wins
+            FROM games
+            JOIN scripts ON scripts.script_id = games.script_id
+            WHERE is_active = false
+              AND winner IS DISTINCT FROM 'Cancelled'
+              AND storyteller_user_id = "
+        );
+        query.push_bind(discord_id);
+        push_game_filters(&mut query, &GameFilter::from(scope));
+        query.push(" GROUP BY games.script_id ORDER BY games DESC, script");
+
+        let scripts = query
+            .build_query_as::<StorytellerScript>()
+            .fetch_all(&self.db.pool)
+            .await?;
+
+        Ok(Some(StorytellerStats {
+            discord_id,
+            totals,
+            scripts,
+        }))
+    }
+
+    /// Games played, unique players and win split of decided games in scope,
+    /// bucketed by completion date. Buckets between the first and last game
+    /// are included even when empty.
+    pub async fn get_timeseries(&self, bucket: TimeBucket, scope: &StatsScope) -> AppResult<Timeseries> {
+        let mut query = QueryBuilder::<Postgres>::new(format!(
+            "WITH scoped AS (
+                SELECT games.game_id, games.winner, date_trunc('{unit}', games.completed_at) AS bucket
+                FROM games
+                WHERE games.is_active = false
+                  AND games.winner IN ('Good', 'Evil')
+                  AND games.completed_at IS NOT NULL",
+            unit = bucket.as_str(),
+        ));
+        push_game_filters(&mut query, &GameFilter::from(scope));
+        query.push(format!(
+            "),
+            buckets AS (
+                SELECT generate_series(MIN(bucket), MAX(bucket), INTERVAL '1 {unit}') AS bucket FROM scoped
+            ),
+            totals AS (
+                SELECT
+                    bucket,
+                    COUNT(*) AS games_played,
+                    COUNT(*) FILTER (WHERE winner = 'Good') AS good_wins,
+                    COUNT(*) FILTER (WHERE winner = 'Evil') AS evil_wins
+                FROM scoped
+                GROUP BY bucket
+            ),
+            players AS (
+                SELECT scoped.bucket, COUNT(DISTINCT gp.discord_id) AS unique_players
+                FROM scoped
+                JOIN game_players gp ON gp.game_id = scoped.game_id
+                WHERE gp.discord_id IS NOT NULL
+                GROUP BY scoped.bucket
+            )
+            SELECT
+                buckets.bucket::date AS bucket_start,
+                COALESCE(totals.games_played, 0) AS games_played,
+                COALESCE(players.unique_players, 0) AS unique_players,
+                COALESCE(totals.good_wins, 0) AS good_wins,
+                COALESCE(totals.evil_wins, 0) AS evil_wins,
+                ROUND(totals.good_wins * 100.0 / NULLIF(totals.games_played, 0), 2)::float8 AS good_win_rate
+            FROM buckets
+            LEFT JOIN totals ON totals.bucket = buckets.bucket
+            LEFT JOIN players ON players.bucket = buckets.bucket
+            ORDER BY buckets.bucket",
+            unit = bucket.as_str(),
+        ));
+
+        let points = query
+            .build_query_as::<TimeseriesPoint>()
+            .fetch_all(&self.db.pool)
+            .await?;
+
+        Ok(Timeseries { bucket, points })
+    }
+}
+
+/// Ranked per-player totals over decided games in scope, without a limit
+pub fn leaderboard_query(metric: LeaderboardMetric, scope: &StatsScope, min_games: i64) -> QueryBuilder<'static, Postgres> {
+    let (metric_column, eligibility_column) = match metric {
+        LeaderboardMetric::Wins => ("wins", "games_played"),
+        LeaderboardMetric::WinRate => ("win_rate", "games_played"),
+        LeaderboardMetric::GamesPlayed => ("games_played", "games_played"),
+        LeaderboardMetric::SurvivalRate => ("survival_rate", "games_played"),
+        LeaderboardMetric::GoodWinRate => ("good_win_rate", "good_games"),
+        LeaderboardMetric::EvilWinRate => ("evil_win_rate", "evil_games"),
+        LeaderboardMetric::GoodRating => ("good_rating", "good_games"),
+        LeaderboardMetric::EvilRating => ("evil_rating", "evil_games"),
+    };
+
+    let mut query = QueryBuilder::<Postgres>::new(format!(
+        "SELECT RANK() OVER (ORDER BY {metric} DESC NULLS LAST) AS rank, s.* FROM (
+            SELECT
+                gp.discord_id,
+                MAX(gp.player_name) AS player_name,
+                COUNT(*) AS games_played,
+                COUNT(*) FILTER (WHERE gp.winning_team) AS wins,
+                COUNT(*) FILTER (WHERE NOT gp.winning_team) AS losses,
+                ROUND(AVG(CASE WHEN gp.winning_team THEN 1.0 ELSE 0.0 END) * 100, 2)::float8 AS win_rate,
+                ROUND(AVG(CASE WHEN gp.survived THEN 1.0 ELSE 0.0 END) * 100, 2)::float8 AS survival_rate,
+                COUNT(*) FILTER (WHERE {alignment} = 'Good') AS good_games,
+                ROUND(AVG(CASE WHEN gp.winning_team THEN 1.0 ELSE 0.0 END)
+                    FILTER (WHERE {alignment} = 'Good') * 100, 2)::float8 AS good_win_rate,
+                COUNT(*) FILTER (WHERE {alignment} = 'Evil') AS evil_games,
+                ROUND(AVG(CASE WHEN gp.winning_team THEN 1.0 ELSE 0.0 END)
+                    FILTER (WHERE {alignment} = 'Evil') * 100, 2)::float8 AS evil_win_rate,
+                ROUND(MAX(pr.good_rating)::numeric, 1)::float8 AS good_rating,
+                ROUND(MAX(pr.evil_rating)::numeric, 1)::float8 AS evil_rating
+            FROM game_players gp
+            JOIN games ON games.game_id = gp.game_id
+            LEFT JOIN player_ratings pr ON pr.discord_id = gp.discord_id
+            WHERE gp.discord_id IS NOT NULL
+              AND games.is_active = false
+              AND games.winner IN ('Good', 'Evil')",
+        metric = metric_column,
+        alignment = SEAT_ALIGNMENT_SQL,
+    ));
+    push_game_filters(&mut query, &GameFilter::from(scope));
+    query.push(format!(
+        " GROUP BY gp.discord_id
+        ) s
+        WHERE {} >= ",
+        eligibility_column
+    ));
+    query.push_bind(min_games);
+    query.push(" ORDER BY rank, games_played DESC, discord_id");
+    query
+}
+
+/// The side a seat aliased as `alias` finished on
+fn seat_alignment_of(alias: &str) -> String {
+    SEAT_ALIGNMENT_SQL.replace("gp.", &format!("{}.", alias))
+}
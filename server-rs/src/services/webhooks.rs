@@ -0,0 +1,626 @@
+use chrono::Utc;
+use futures::future::join_all;
+use hmac::{Hmac, Mac};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::Url;
+use sha2::Sha256;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+use crate::{
+    database::Database,
+    error::{AppError, AppResult},
+    models::{GameCorrectionEntry, Webhook, WebhookCreate, WebhookCreated, WebhookDelivery, WebhookEvent, WebhookPayload},
+    services::game::GameService,
+};
+
+/// Webhooks one API key may register per guild
+const MAX_WEBHOOKS_PER_GUILD: i64 = 10;
+
+/// Attempts before a delivery is marked failed
+const MAX_ATTEMPTS: i32 = 8;
+
+/// Delay before the first retry, doubled for each later one
+const RETRY_BASE_SECONDS: i64 = 30;
+const RETRY_MAX_SECONDS: i64 = 6 * 3600;
+
+/// How long a claimed delivery is held before another worker may retry it,
+/// in case the process dies mid-request
+const CLAIM_LEASE_SECONDS: i32 = 300;
+
+const DELIVERY_BATCH: i64 = 20;
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Finished deliveries are kept in the log this long
+const DELIVERY_RETENTION_DAYS: i32 = 30;
+
+/// A delivery claimed by the worker, with what it needs to send it
+#[derive(sqlx::FromRow)]
+struct DueDelivery {
+    id: i64,
+    event: String,
+    payload: String,
+    attempts: i32,
+    url: String,
+    secret: String,
+}
+
+/// Registers webhooks, queues game events for them and delivers the queue in
+/// the background
+#[derive(Clone)]
+pub struct WebhookService {
+    db: Database,
+    game: Arc<GameService>,
+    wake: Arc<Notify>,
+    /// Whether receivers may be on loopback or private networks, so a local
+    /// receiver can stand in during development
+    allow_local: bool,
+}
+
+impl WebhookService {
+    pub fn new(db: Database, allow_local: bool) -> Self {
+        let service = Self {
+            game: Arc::new(GameService::new(db.clone())),
+            db,
+            wake: Arc::new(Notify::new()),
+            allow_local,
+        };
+
+        // Spawn delivery worker
+        let worker = service.clone();
+        tokio::spawn(async move {
+            let client = delivery_client(worker.allow_local);
+
+            loop {
+                match worker.deliver_due(&client).await {
+                    // A full batch means more may be waiting
+                    Ok(delivered) if delivered as i64 == DELIVERY_BATCH => continue,
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!("Failed to deliver webhooks: {}", e),
+                }
+
+                tokio::select! {
+                    _ = worker.wake.notified() => {}
+                    _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                }
+            }
+        });
+
+        // Spawn cleanup task
+        let pool = service.db.pool.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(3600)); // 1 hour
+            loop {
+                interval.tick().await;
+                let result = sqlx::query(
+                    "DELETE FROM webhook_deliveries
+                     WHERE status <> 'pending' AND created_at < CURRENT_TIMESTAMP - make_interval(days => $1)"
+                )
+                .bind(DELIVERY_RETENTION_DAYS)
+                .execute(&pool)
+                .await;
+
+                if let Err(e) = result {
+                    tracing::warn!("Failed to clean up webhook deliveries: {}", e);
+                }
+            }
+        });
+
+        service
+    }
+
+    /// Check that a webhook URL points at the public internet, so webhooks
+    /// can't be used to reach the server's own network. Deliveries are
+    /// checked again when sent, as a host can be re-pointed after this.
+    pub async fn check_target(&self, url: &str) -> AppResult<()> {
+        if self.allow_local {
+            return Ok(());
+        }
+
+        let url = Url::parse(url).map_err(|_| AppError::Validation("Invalid webhook URL".to_string()))?;
+        let host = url.host_str().ok_or_else(|| AppError::Validation("Invalid webhook URL".to_string()))?;
+        let result = match literal_address(&url) {
+            Some(ip) if is_public_address(ip) => Ok(()),
+            Some(_) => Err(format!("{} is not a public address", host)),
+            None => resolve_public(host).await.map(|_| ()),
+        };
+
+        result.map_err(|e| AppError::Validation(format!("Webhook URL is not allowed: {}", e)))
+    }
+
+    /// Register a webhook for a guild, generating its signing secret
+    pub async fn create_webhook(
+        &self,
+        api_key_id: i32,
+        guild_id: i64,
+        webhook: &WebhookCreate,
+    ) -> AppResult<WebhookCreated> {
+        let (count,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM webhooks WHERE api_key_id = $1 AND guild_id = $2"
+        )
+        .bind(api_key_id)
+        .bind(guild_id)
+        .fetch_one(&self.db.pool)
+        .await?;
+
+        if count >= MAX_WEBHOOKS_PER_GUILD {
+            return Err(AppError::Conflict(format!(
+                "At most {} webhooks can be registered per guild", MAX_WEBHOOKS_PER_GUILD
+            )));
+        }
+
+        let mut events: Vec<&str> = webhook.events.iter().map(WebhookEvent::as_str).collect();
+        events.sort_unstable();
+        events.dedup();
+
+        let secret = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+        let created = sqlx::query_as::<_, Webhook>(
+            "INSERT INTO webhooks (api_key_id, guild_id, url, secret, events)
+             VALUES ($1, $2, $3, $4, $5)
+             RETURNING id, guild_id, url, events, created_at"
+        )
+        .bind(api_key_id)
+        .bind(guild_id)
+        .bind(&webhook.url)
+        .bind(&secret)
+        .bind(&events)
+        .fetch_one(&self.db.pool)
+        .await?;
+
+        Ok(WebhookCreated { webhook: created, secret })
+    }
+
+    /// List the webhooks an API key registered for a guild
+    pub async fn list_webhooks(&self, api_key_id: i32, guild_id: i64) -> AppResult<Vec<Webhook>> {
+        let webhooks = sqlx::query_as::<_, Webhook>(
+            "SELECT id, guild_id, url, events, created_at
+             FROM webhooks
+             WHERE api_key_id = $1 AND guild_id = $2
+             ORDER BY id"
+        )
+        .bind(api_key_id)
+        .bind(guild_id)
+        .fetch_all(&self.db.pool)
+        .await?;
+
+        Ok(webhooks)
+    }
+
+    /// Delete a webhook along with its pending deliveries and delivery log.
+    /// Returns false if the key has no such webhook in the guild.
+    pub async fn delete_webhook(&self, api_key_id: i32, guild_id: i64, webhook_id: i32) -> AppResult<bool> {
+        let result = sqlx::query(
+            "DELETE FROM webhooks WHERE id = $1 AND api_key_id = $2 AND guild_id = $3"
+        )
+        .bind(webhook_id)
+        .bind(api_key_id)
+        .bind(guild_id)
+        .execute(&self.db.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Get the most recent deliveries to a webhook, or `None` if the key has
+    /// no such webhook in the guild
+    pub async fn get_deliveries(
+        &self,
+        api_key_id: i32,
+        guild_id: i64,
+        webhook_id: i32,
+        limit: i64,
+    ) -> AppResult<Option<Vec<WebhookDelivery>>> {
+        let owned: Option<(i32,)> = sqlx::query_as(
+            "SELECT id FROM webhooks WHERE id = $1 AND api_key_id = $2 AND guild_id = $3"
+        )
+        .bind(webhook_id)
+        .bind(api_key_id)
+        .bind(guild_id)
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        if owned.is_none() {
+            return Ok(None);
+        }
+
+        let deliveries = sqlx::query_as::<_, WebhookDelivery>(
+            "SELECT id, event, status, attempts, next_attempt_at, last_status_code, last_error,
+                    created_at, delivered_at
+             FROM webhook_deliveries
+             WHERE webhook_id = $1
+             ORDER BY created_at DESC, id DESC
+             LIMIT $2"
+        )
+        .bind(webhook_id)
+        .bind(limit)
+        .fetch_all(&self.db.pool)
+        .await?;
+
+        Ok(Some(deliveries))
+    }
+
+    /// Queue an event about a game for every webhook of its guild that
+    /// subscribes to it
+    pub async fn notify(
+        &self,
+        event: WebhookEvent,
+        game_id: i32,
+        corrections: &[GameCorrectionEntry],
+    ) -> AppResult<()> {
+        let webhook_ids: Vec<i32> = sqlx::query_scalar(
+            "SELECT webhooks.id
+             FROM webhooks
+             JOIN games ON games.guild_id = webhooks.guild_id
+             WHERE games.game_id = $1 AND $2 = ANY(webhooks.events)"
+        )
+        .bind(game_id)
+        .bind(event.as_str())
+        .fetch_all(&self.db.pool)
+        .await?;
+
+        if webhook_ids.is_empty() {
+            return Ok(());
+        }
+
+        let game = self.game.get_game_detail(game_id).await?
+            .ok_or_else(|| AppError::NotFound("Game not found".to_string()))?;
+        let payload = serde_json::to_string(&WebhookPayload {
+            event,
+            occurred_at: Utc::now().naive_utc(),
+            game: &game,
+            corrections,
+        })
+        .map_err(|e| AppError::Internal(format!("Failed to encode webhook payload: {}", e)))?;
+
+        sqlx::query(
+            "INSERT INTO webhook_deliveries (webhook_id, event, payload)
+             SELECT UNNEST($1::int[]), $2, $3"
+        )
+        .bind(&webhook_ids)
+        .bind(event.as_str())
+        .bind(&payload)
+        .execute(&self.db.pool)
+        .await?;
+
+        self.wake.notify_one();
+        Ok(())
+    }
+
+    /// Claim a batch of due deliveries and send them, returning how many were
+    /// attempted
+    async fn deliver_due(&self, client: &reqwest::Client) -> AppResult<usize> {
+        let due = sqlx::query_as::<_, DueDelivery>(
+            "UPDATE webhook_deliveries
+             SET attempts = webhook_deliveries.attempts + 1,
+                 next_attempt_at = CURRENT_TIMESTAMP + make_interval(secs => $2)
+             FROM webhooks
+             WHERE webhook_deliveries.id IN (
+                     SELECT id FROM webhook_deliveries
+                     WHERE status = 'pending' AND next_attempt_at <= CURRENT_TIMESTAMP
+                     ORDER BY next_attempt_at
+                     LIMIT $1
+                     FOR UPDATE SKIP LOCKED
+                 )
+               AND webhooks.id = webhook_deliveries.webhook_id
+             RETURNING webhook_deliveries.id, webhook_deliveries.event, webhook_deliveries.payload,
+                       webhook_deliveries.attempts, webhooks.url, webhooks.secret"
+        )
+        .bind(DELIVERY_BATCH)
+        .bind(CLAIM_LEASE_SECONDS as f64)
+        .fetch_all(&self.db.pool)
+        .await?;
+
+        let attempted = due.len();
+        let results = join_all(due.iter().map(|delivery| send(client, delivery, self.allow_local))).await;
+        for (delivery, (status_code, error)) in due.iter().zip(results) {
+            self.record_attempt(delivery, status_code, error).await?;
+        }
+
+        Ok(attempted)
+    }
+
+    async fn record_attempt(
+        &self,
+        delivery: &DueDelivery,
+        status_code: Option<u16>,
+        error: Option<String>,
+    ) -> AppResult<()> {
+        let status_code = status_code.map(i32::from);
+
+        if error.is_none() {
+            sqlx::query(
+                "UPDATE webhook_deliveries
+                 SET status = 'delivered', delivered_at = CURRENT_TIMESTAMP, next_attempt_at = NULL,
+                     last_status_code = $2, last_error = NULL
+                 WHERE id = $1"
+            )
+            .bind(delivery.id)
+            .bind(status_code)
+            .execute(&self.db.pool)
+            .await?;
+            return Ok(());
+        }
+
+        if delivery.attempts >= MAX_ATTEMPTS {
+            tracing::warn!("Giving up on webhook delivery {} after {} attempts", delivery.id, delivery.attempts);
+        }
+
+        sqlx::query(
+            "UPDATE webhook_deliveries
+             SET status = CASE WHEN $4 THEN 'failed' ELSE 'pending' END,
+                 next_attempt_at = CASE WHEN $4 THEN NULL
+                                        ELSE CURRENT_TIMESTAMP + make_interval(secs => $5) END,
+                 last_status_code = $2, last_error = $3
+             WHERE id = $1"
+        )
+        .bind(delivery.id)
+        .bind(status_code)
+        .bind(error)
+        .bind(delivery.attempts >= MAX_ATTEMPTS)
+        .bind(retry_delay_seconds(delivery.attempts) as f64)
+        .execute(&self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Exponential backoff after a failed attempt
+fn retry_delay_seconds(attempts: i32) -> i64 {
+    let doublings = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    (RETRY_BASE_SECONDS << doublings).min(RETRY_MAX_SECONDS)
+}
+
+/// HTTP client for deliveries. Unless local receivers are allowed, host
+/// names only resolve to public addresses, checked as each delivery connects
+/// so a host re-pointed at an internal address after registration is refused.
+fn delivery_client(allow_local: bool) -> reqwest::Client {
+    let mut builder = reqwest::Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none());
+    if !allow_local {
+        builder = builder.dns_resolver(Arc::new(PublicResolver));
+    }
+
+    builder.build().expect("Failed to build webhook HTTP client")
+}
+
+/// Resolves host names to their public addresses only
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs = resolve_public(name.as_str()).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Resolve a host name, refusing it if any of its addresses isn't public
+async fn resolve_public(host: &str) -> Result<Vec<SocketAddr>, String> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0))
+        .await
+        .map_err(|e| format!("Failed to resolve {}: {}", host, e))?
+        .collect();
+
+    if addrs.is_empty() {
+        return Err(format!("{} has no addresses", host));
+    }
+    if addrs.iter().any(|addr| !is_public_address(addr.ip())) {
+        return Err(format!("{} resolves to a non-public address", host));
+    }
+    Ok(addrs)
+}
+
+/// The address of a URL whose host is an IP address rather than a name
+fn literal_address(url: &Url) -> Option<IpAddr> {
+    // IPv6 hosts are bracketed
+    let host = url.host_str()?;
+    host.trim_start_matches('[').trim_end_matches(']').parse().ok()
+}
+
+/// Whether an address is on the public internet: not loopback, private
+/// (RFC 1918), shared (RFC 6598), link-local (including cloud metadata at
+/// 169.254.169.254), unique-local, multicast or unspecified
+fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || first == 0
+                || (first == 100 && (64..128).contains(&second)))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_public_address(IpAddr::V4(mapped));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// POST a delivery, returning the response status and an error unless the
+/// receiver answered 2xx
+async fn send(client: &reqwest::Client, delivery: &DueDelivery, allow_local: bool) -> (Option<u16>, Option<String>) {
+    // Host names are checked by the client's resolver, but addresses
+    // aren't resolved
+    if !allow_local {
+        let url = match Url::parse(&delivery.url) {
+            Ok(url) => url,
+            Err(e) => return (None, Some(format!("Invalid webhook URL: {}", e))),
+        };
+        if literal_address(&url).is_some_and(|ip| !is_public_address(ip)) {
+            return (None, Some("Webhook URL is not a public address".to_string()));
+        }
+    }
+
+    let timestamp = Utc::now().timestamp().to_string();
+
+    let result = client
+        .post(&delivery.url)
+        .header("content-type", "application/json")
+        .header("user-agent", concat!("Grimlive-Webhooks/", env!("CARGO_PKG_VERSION")))
+        .header("x-grimlive-event", &delivery.event)
+        .header("x-grimlive-delivery", delivery.id.to_string())
+        .header("x-grimlive-timestamp", &timestamp)
+        .header("x-grimlive-signature", signature(&delivery.secret, &timestamp, &delivery.payload))
+        .body(delivery.payload.clone())
+        .send()
+        .await;
+
+    match result {
+        Ok(response) if response.status().is_success() => (Some(response.status().as_u16()), None),
+        Ok(response) => (
+            Some(response.status().as_u16()),
+            Some(format!("Receiver responded with {}", response.status())),
+        ),
+        Err(e) => (None, Some(e.to_string())),
+    }
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `{timestamp}.{body}` keyed by the
+/// webhook secret. Signing the timestamp lets receivers reject replays.
+fn signature(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn delivery(url: String) -> DueDelivery {
+        DueDelivery {
+            id: 7,
+            event: "game.ended".to_string(),
+            payload: r#"{"event":"game.ended"}"#.to_string(),
+            attempts: 1,
+            url,
+            secret: "secret".to_string(),
+        }
+    }
+
+    /// Accept one request on a local port, answer 200 and hand back the raw
+    /// request
+    async fn receiver() -> (u16, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            loop {
+                let read = socket.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request);
+                if read == 0 || text.ends_with(r#"{"event":"game.ended"}"#) {
+                    break;
+                }
+            }
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        (port, handle)
+    }
+
+    fn header<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+        request.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    }
+
+    #[test]
+    fn signature_is_hmac_of_timestamp_and_body() {
+        assert_eq!(
+            signature("secret", "1700000000", r#"{"event":"game.ended"}"#),
+            "sha256=a5be419d4e8e1093e3cb5798672fa4dc719cd9d87bba3dfd2adf803ccb4976e1"
+        );
+        assert_ne!(
+            signature("secret", "1700000001", r#"{"event":"game.ended"}"#),
+            signature("secret", "1700000000", r#"{"event":"game.ended"}"#)
+        );
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_the_cap() {
+        assert_eq!(retry_delay_seconds(0), RETRY_BASE_SECONDS);
+        assert_eq!(retry_delay_seconds(1), RETRY_BASE_SECONDS);
+        assert_eq!(retry_delay_seconds(2), RETRY_BASE_SECONDS * 2);
+        assert_eq!(retry_delay_seconds(4), RETRY_BASE_SECONDS * 8);
+        assert_eq!(retry_delay_seconds(MAX_ATTEMPTS), RETRY_BASE_SECONDS << (MAX_ATTEMPTS - 1));
+        assert_eq!(retry_delay_seconds(i32::MAX), RETRY_MAX_SECONDS);
+    }
+
+    #[test]
+    fn only_public_addresses_are_allowed() {
+        let refused = [
+            "127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "0.0.0.0",
+            "::1", "fd00::1", "fe80::1", "::ffff:127.0.0.1",
+        ];
+        for ip in refused {
+            assert!(!is_public_address(ip.parse().unwrap()), "{} should be refused", ip);
+        }
+        for ip in ["1.1.1.1", "93.184.216.34", "2606:4700:4700::1111"] {
+            assert!(is_public_address(ip.parse().unwrap()), "{} should be allowed", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_payload_to_local_receiver() {
+        let (port, handle) = receiver().await;
+        let delivery = delivery(format!("http://127.0.0.1:{}/hook", port));
+
+        let (status, error) = send(&delivery_client(true), &delivery, true).await;
+        assert_eq!((status, error), (Some(200), None));
+
+        let request = handle.await.unwrap();
+        assert!(request.starts_with("POST /hook HTTP/1.1"));
+        assert_eq!(header(&request, "x-grimlive-event"), Some("game.ended"));
+        assert_eq!(header(&request, "x-grimlive-delivery"), Some("7"));
+        let timestamp = header(&request, "x-grimlive-timestamp").unwrap();
+        assert_eq!(
+            header(&request, "x-grimlive-signature"),
+            Some(signature("secret", timestamp, &delivery.payload).as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_local_receivers_unless_allowed() {
+        let (port, handle) = receiver().await;
+        let client = delivery_client(false);
+
+        for url in [format!("http://127.0.0.1:{}/hook", port), format!("http://localhost:{}/hook", port)] {
+            let (status, error) = send(&client, &delivery(url), false).await;
+            assert_eq!(status, None);
+            assert!(error.is_some());
+        }
+
+        // Nothing reached the receiver
+        assert!(tokio::time::timeout(Duration::from_millis(200), handle).await.is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use axum::{
+    body::Body,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use futures::{channel::mpsc, SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{postgres::PgRow, FromRow, PgPool, Postgres, QueryBuilder};
+use utoipa::ToSchema;
+
+/// Rows buffered between the query and a slow client
+const EXPORT_BUFFER_ROWS: usize = 256;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    #[default]
+    Ndjson,
+}
+
+impl ExportFormat {
+    fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+
+    /// Encode a row as one line. CSV rows take `columns` in order; NDJSON
+    /// rows keep every field.
+    fn encode_row<T: Serialize>(&self, row: &T, columns: &[&str]) -> serde_json::Result<String> {
+        match self {
+            ExportFormat::Ndjson => Ok(serde_json::to_string(row)? + "\n"),
+            ExportFormat::Csv => {
+                let value = serde_json::to_value(row)?;
+                let cells: Vec<String> = columns
+                    .iter()
+                    .map(|column| csv_cell(value.get(*column).unwrap_or(&Value::Null)))
+                    .collect();
+                Ok(cells.join(",") + "\r\n")
+            }
+        }
+    }
+}
+
+fn csv_cell(value: &Value) -> String {
+    let text = match value {
+        Value::Null => return String::new(),
+        Value::String(text) => text.clone(),
+        Value::Bool(_) | Value::Number(_) => return value.to_string(),
+        // Nested values such as the players list are written as JSON
+        Value::Array(_) | Value::Object(_) => value.to_string(),
+    };
+
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+/// Stream every row of a query as a CSV or NDJSON download without holding
+/// the result set in memory. A database error mid-stream aborts the body.
+pub fn stream_export<T>(
+    pool: PgPool,
+    mut query: QueryBuilder<'static, Postgres>,
+    format: ExportFormat,
+    columns: &'static [&'static str],
+    name: &str,
+) -> Response
+where
+    T: for<'r> FromRow<'r, PgRow> + Serialize + Send + Unpin + 'static,
+{
+    let (mut sender, receiver) = mpsc::channel::<Result<String, std::io::Error>>(EXPORT_BUFFER_ROWS);
+
+    tokio::spawn(async move {
+        if format == ExportFormat::Csv && sender.send(Ok(columns.join(",") + "\r\n")).await.is_err() {
+            return;
+        }
+
+        let mut rows = query.build_query_as::<T>().fetch(&pool);
+        while let Some(row) = rows.next().await {
+            let line = row
+                .map_err(std::io::Error::other)
+                .and_then(|row| format.encode_row(&row, columns).map_err(std::io::Error::other));
+
+            let failed = line.is_err();
+            if let Err(ref e) = line {
+                tracing::error!("Export failed: {}", e);
+            }
+            // Stop once the client has gone away
+            if sender.send(line).await.is_err() || failed {
+                return;
+            }
+        }
+    });
+
+    let disposition = format!("attachment; filename=\"{}.{}\"", name, format.extension());
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(receiver),
+    )
+        .into_response()
+}
@@ -1 +1,3 @@
+pub mod export;
+pub mod pagination;
 pub mod validation;
@@ -0,0 +1,149 @@
+use axum::{
+    http::{header, Uri},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::error::{AppError, AppResult};
+
+/// Opaque keyset cursor: the sort key and id of the last row on a page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub sort_key: i64,
+    pub id: i64,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        hex::encode(format!("{}:{}", self.sort_key, self.id))
+    }
+
+    pub fn decode(cursor: &str) -> AppResult<Self> {
+        let invalid = || AppError::Validation("Invalid cursor".to_string());
+
+        let bytes = hex::decode(cursor).map_err(|_| invalid())?;
+        let text = String::from_utf8(bytes).map_err(|_| invalid())?;
+        let (sort_key, id) = text.split_once(':').ok_or_else(invalid)?;
+
+        Ok(Self {
+            sort_key: sort_key.parse().map_err(|_| invalid())?,
+            id: id.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// A page of results with the cursor for the next one
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Build a page from up to `limit + 1` rows, where the extra row only
+    /// signals that another page exists
+    pub fn from_rows(mut rows: Vec<T>, limit: i64, cursor_of: impl Fn(&T) -> Cursor) -> Self {
+        let has_more = rows.len() as i64 > limit;
+        rows.truncate(limit as usize);
+
+        let next_cursor = if has_more {
+            rows.last().map(|row| cursor_of(row).encode())
+        } else {
+            None
+        };
+
+        Self { data: rows, next_cursor }
+    }
+}
+
+impl<T: Serialize> Page<T> {
+    /// Respond with the page, adding a `Link: rel="next"` header pointing at
+    /// the same request with the next cursor
+    pub fn into_response_for(self, uri: &Uri) -> Response {
+        let link = self.next_cursor.as_deref().map(|cursor| next_link(uri, cursor));
+        let mut response = Json(self).into_response();
+
+        if let Some(link) = link.and_then(|link| link.parse().ok()) {
+            response.headers_mut().insert(header::LINK, link);
+        }
+
+        response
+    }
+}
+
+fn next_link(uri: &Uri, cursor: &str) -> String {
+    let mut params: Vec<&str> = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|param| {
+            let key = param.split('=').next().unwrap_or_default();
+            !param.is_empty() && key != "cursor" && key != "offset"
+        })
+        .collect();
+
+    let cursor_param = format!("cursor={}", urlencoding::encode(cursor));
+    params.push(&cursor_param);
+
+    format!("<{}?{}>; rel=\"next\"", uri.path(), params.join("&"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor_of(row: &i64) -> Cursor {
+        Cursor { sort_key: row * 10, id: *row }
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        for cursor in [
+            Cursor { sort_key: 0, id: 0 },
+            Cursor { sort_key: 1_700_000_000_000, id: 42 },
+            Cursor { sort_key: -5, id: i64::MAX },
+        ] {
+            assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+        }
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        let not_numbers = [hex::encode("abc:1"), hex::encode("1:abc"), hex::encode("12"), hex::encode("1:2:3")];
+        for cursor in ["zz", "123", ""].into_iter().chain(not_numbers.iter().map(String::as_str)) {
+            assert!(
+                matches!(Cursor::decode(cursor), Err(AppError::Validation(_))),
+                "{:?} should be rejected",
+                cursor
+            );
+        }
+    }
+
+    #[test]
+    fn exactly_limit_rows_is_the_last_page() {
+        let page = Page::from_rows(vec![1, 2, 3], 3, cursor_of);
+        assert_eq!(page.data, vec![1, 2, 3]);
+        assert_eq!(page.next_cursor, None);
+    }
+
+    #[test]
+    fn extra_row_gives_a_cursor_for_the_last_kept_row() {
+        let page = Page::from_rows(vec![1, 2, 3, 4], 3, cursor_of);
+        assert_eq!(page.data, vec![1, 2, 3]);
+        assert_eq!(page.next_cursor, Some(cursor_of(&3).encode()));
+    }
+
+    #[test]
+    fn next_link_replaces_offset_and_cursor() {
+        let uri: Uri = "/api/v1/games?limit=10&offset=20&cursor=old&winner=Good".parse().unwrap();
+        assert_eq!(
+            next_link(&uri, "abc"),
+            "</api/v1/games?limit=10&winner=Good&cursor=abc>; rel=\"next\""
+        );
+
+        let uri: Uri = "/api/v1/games".parse().unwrap();
+        assert_eq!(next_link(&uri, "abc"), "</api/v1/games?cursor=abc>; rel=\"next\"");
+    }
+}
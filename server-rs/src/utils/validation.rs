@@ -1,6 +1,8 @@
-use crate::error::{AppError, AppResult};
+use crate::{
+    error::{AppError, AppResult},
+    models::{GameFilter, PlayerGameFilter, StatsScope, WebhookEvent},
+};
 
-#[allow(dead_code)]
 pub fn validate_discord_id(discord_id: &str) -> AppResult<()> {
     if discord_id.is_empty() || discord_id.len() > 20 {
         return Err(AppError::Validation("Invalid Discord ID".to_string()));
@@ -21,6 +23,26 @@ pub fn validate_script_name(script_name: &str) -> AppResult<()> {
     Ok(())
 }
 
+/// Custom script contents sent when starting a game
+pub fn validate_script_contents(characters: &[String], bootlegger: &[String]) -> AppResult<()> {
+    if characters.len() > 200 || characters.iter().any(|id| id.is_empty() || id.len() > 100) {
+        return Err(AppError::Validation("Script characters must be at most 200 IDs of 1-100 characters".to_string()));
+    }
+    if bootlegger.len() > 50 || bootlegger.iter().any(|rule| rule.len() > 500) {
+        return Err(AppError::Validation("Scripts can have at most 50 bootlegger rules of up to 500 characters".to_string()));
+    }
+
+    Ok(())
+}
+
+pub fn validate_role_id(role_id: &str) -> AppResult<()> {
+    if role_id.is_empty() || role_id.len() > 100 {
+        return Err(AppError::Validation("Invalid role ID".to_string()));
+    }
+    
+    Ok(())
+}
+
 pub fn validate_api_key_name(name: &str) -> AppResult<()> {
     if name.is_empty() || name.len() > 50 {
         return Err(AppError::Validation("API key name must be 1-50 characters".to_string()));
@@ -30,9 +52,110 @@ pub fn validate_api_key_name(name: &str) -> AppResult<()> {
 }
 
 pub fn validate_rate_limit(rate_limit: i32) -> AppResult<()> {
-    if rate_limit < 1 || rate_limit > 10000 {
+    if !(1..=10000).contains(&rate_limit) {
         return Err(AppError::Validation("Rate limit must be between 1 and 10000".to_string()));
     }
     
     Ok(())
 }
+
+pub fn validate_api_key_guilds(guild_ids: &[i64]) -> AppResult<()> {
+    if guild_ids.is_empty() || guild_ids.len() > 50 {
+        return Err(AppError::Validation("API keys can be restricted to 1-50 guilds".to_string()));
+    }
+    
+    Ok(())
+}
+
+pub fn validate_winner(winner: &str) -> AppResult<()> {
+    if winner != "Good" && winner != "Evil" {
+        return Err(AppError::Validation("Winner must be Good or Evil".to_string()));
+    }
+    
+    Ok(())
+}
+
+pub fn validate_correction_reason(reason: &str) -> AppResult<()> {
+    if reason.len() > 500 {
+        return Err(AppError::Validation("Correction reason must be at most 500 characters".to_string()));
+    }
+    
+    Ok(())
+}
+
+pub fn validate_game_filter(filter: &GameFilter) -> AppResult<()> {
+    if let Some(ref script) = filter.script {
+        validate_script_name(script)?;
+    }
+    if let Some(ref winner) = filter.winner {
+        validate_winner(winner)?;
+    }
+    
+    let players_in_range = |count: Option<i32>| count.is_none_or(|count| (1..=30).contains(&count));
+    if !players_in_range(filter.min_players) || !players_in_range(filter.max_players) {
+        return Err(AppError::Validation("Player counts must be between 1 and 30".to_string()));
+    }
+    if let (Some(min), Some(max)) = (filter.min_players, filter.max_players) {
+        if min > max {
+            return Err(AppError::Validation("min_players must not exceed max_players".to_string()));
+        }
+    }
+    if let (Some(from), Some(to)) = (filter.from, filter.to) {
+        if from > to {
+            return Err(AppError::Validation("from must not be after to".to_string()));
+        }
+    }
+    
+    Ok(())
+}
+
+pub fn validate_stats_scope(scope: &StatsScope) -> AppResult<()> {
+    validate_game_filter(&GameFilter::from(scope))
+}
+
+pub fn validate_player_game_filter(filter: &PlayerGameFilter) -> AppResult<()> {
+    if let Some(ref role) = filter.role {
+        validate_role_id(role)?;
+    }
+    if let Some(ref team) = filter.team {
+        let team = team.to_lowercase();
+        if !["good", "evil", "townsfolk", "outsider", "minion", "demon", "traveller"].contains(&team.as_str()) {
+            return Err(AppError::Validation(
+                "Team must be good, evil, townsfolk, outsider, minion, demon or traveller".to_string(),
+            ));
+        }
+    }
+    if let Some(ref script) = filter.script {
+        validate_script_name(script)?;
+    }
+    Ok(())
+}
+
+/// Webhooks must use HTTPS, except outside production so a local receiver
+/// can stand in during development
+pub fn validate_webhook_url(url: &str, allow_http: bool) -> AppResult<()> {
+    if url.len() > 2048 {
+        return Err(AppError::Validation("Webhook URL must be at most 2048 characters".to_string()));
+    }
+
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|_| AppError::Validation("Invalid webhook URL".to_string()))?;
+    match parsed.scheme() {
+        "https" => {}
+        "http" if allow_http => {}
+        _ => return Err(AppError::Validation("Webhook URL must use HTTPS".to_string())),
+    }
+    if parsed.host_str().is_none() {
+        return Err(AppError::Validation("Invalid webhook URL".to_string()));
+    }
+
+    Ok(())
+}
+
+pub fn validate_webhook_events(events: &[WebhookEvent]) -> AppResult<()> {
+    if events.is_empty() {
+        return Err(AppError::Validation("Webhooks must subscribe to at least one event".to_string()));
+    }
+
+    Ok(())
+}
@@ -8,7 +8,7 @@
 
 import store from "../store";
 import { logger } from "../utils/logger";
-import { fetchWithTimeout } from "../utils/fetch";
+import { fetchIdempotent, fetchWithTimeout } from "../utils/fetch";
 
 class StatsService {
   constructor() {
@@ -128,7 +128,7 @@ class StatsService {
     if (!this.isEnabled() || !this.token || !this.currentGameId) return;
 
     try {
-      const response = await fetchWithTimeout(`${this.baseUrl}/player/add`, {
+      const response = await fetchIdempotent(`${this.baseUrl}/player/add`, {
         method: "POST",
         headers: {
           "Content-Type": "application/json",
@@ -159,14 +159,6 @@ class StatsService {
     }
   }
 
-  async updatePlayerRole(playerName, role, finalRole) {
-    await this.store.dispatch("stats/updatePlayerRole", {
-      playerName,
-      role,
-      finalRole,
-    });
-  }
-
   async getGameStats(gameId) {
     if (!this.isEnabled()) return;
 
@@ -4,7 +4,7 @@
  */
 
 import { logger } from "../../utils/logger";
-import { fetchWithTimeout } from "../../utils/fetch";
+import { fetchIdempotent, fetchWithTimeout } from "../../utils/fetch";
 
 const state = () => ({
   // Discord OAuth state
@@ -132,14 +132,14 @@ const actions = {
    */
   async startGame(
     { commit, state },
-    { script, customName, players, sessionCode },
+    { script, customName, players, sessionCode, characters, bootlegger },
   ) {
     if (!state.discordUserId || !state.statsToken) {
       throw new Error("Discord login required for stats tracking");
     }
 
     try {
-      const response = await fetchWithTimeout(`${state.baseUrl}/game/start`, {
+      const response = await fetchIdempotent(`${state.baseUrl}/game/start`, {
         method: "POST",
         headers: {
           "Content-Type": "application/json",
@@ -151,6 +151,8 @@ const actions = {
           players,
           storytellerId: state.discordUserId,
           sessionCode: sessionCode || state.sessionCode,
+          characters,
+          bootlegger,
         }),
       });
 
@@ -180,7 +182,7 @@ const actions = {
     }
 
     try {
-      const response = await fetchWithTimeout(`${state.baseUrl}/game/end`, {
+      const response = await fetchIdempotent(`${state.baseUrl}/game/end`, {
         method: "POST",
         headers: {
           "Content-Type": "application/json",
@@ -213,7 +215,7 @@ const actions = {
     }
 
     try {
-      const response = await fetchWithTimeout(`${state.baseUrl}/game/cancel`, {
+      const response = await fetchIdempotent(`${state.baseUrl}/game/cancel`, {
         method: "POST",
         headers: {
           "Content-Type": "application/json",
@@ -242,13 +244,16 @@ const actions = {
   /**
    * Update a player's role in the current game
    */
-  async updatePlayerRole({ state }, { playerName, role, finalRole }) {
+  async updatePlayerRole(
+    { state },
+    { playerName, playerNumber, roleId, roleName, roleTeam, isFinal, discordId },
+  ) {
     if (!state.currentGameId || !state.statsToken) {
       return;
     }
 
     try {
-      await fetchWithTimeout(`${state.baseUrl}/game/update-role`, {
+      await fetchIdempotent(`${state.baseUrl}/game/update-role`, {
         method: "POST",
         headers: {
           "Content-Type": "application/json",
@@ -257,8 +262,12 @@ const actions = {
         body: JSON.stringify({
           game_id: state.currentGameId,
           player_name: playerName,
-          role,
-          final_role: finalRole,
+          seat_number: playerNumber,
+          role_id: roleId,
+          role_name: roleName,
+          team: roleTeam,
+          is_final: isFinal,
+          discord_id: discordId,
         }),
       });
     } catch (error) {
@@ -91,6 +91,12 @@ class LiveSession {
    * @private
    */
   _onOpen() {
+    // Signed-in clients identify themselves so games can be recorded
+    // server-side with their Discord accounts. The server never relays this.
+    const { statsToken } = this._store.state.stats;
+    if (statsToken) {
+      this._send("auth", statsToken);
+    }
     if (this._isSpectator) {
       this._sendDirect(
         "host",
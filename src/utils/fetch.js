@@ -17,3 +17,33 @@ export async function fetchWithTimeout(url, options = {}, timeout = 10000) {
     throw error;
   }
 }
+
+/**
+ * Send a game write with an Idempotency-Key, retrying on timeouts and
+ * network errors. Every attempt carries the same key, so a write that
+ * reached the server before the connection dropped is replayed rather than
+ * applied twice.
+ */
+export async function fetchIdempotent(
+  url,
+  options = {},
+  { retries = 2, timeout = 10000, backoff = 1000 } = {},
+) {
+  const request = {
+    ...options,
+    headers: { ...options.headers, "Idempotency-Key": crypto.randomUUID() },
+  };
+
+  for (let attempt = 0; ; attempt++) {
+    try {
+      return await fetchWithTimeout(url, request, timeout);
+    } catch (error) {
+      if (attempt >= retries) {
+        throw error;
+      }
+      await new Promise((resolve) =>
+        setTimeout(resolve, backoff * 2 ** attempt),
+      );
+    }
+  }
+}